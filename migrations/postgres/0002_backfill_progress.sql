@@ -0,0 +1,7 @@
+-- Next publish time to request for each backfill job, so an interrupted
+-- backfill resumes where it stopped.
+CREATE TABLE backfill_progress (
+    job        TEXT        PRIMARY KEY,
+    next_time  BIGINT      NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,14 @@
+-- Every distinct (feed_id, publish_time) price observed from any source.
+CREATE TABLE observations (
+    feed_id             TEXT    NOT NULL,
+    symbol              TEXT    NOT NULL,
+    publish_time        INTEGER NOT NULL,
+    price_mantissa      INTEGER NOT NULL,
+    confidence_mantissa INTEGER NOT NULL,
+    exponent            INTEGER NOT NULL,
+    source              TEXT    NOT NULL,
+    fetched_at          TEXT    NOT NULL,
+    PRIMARY KEY (feed_id, publish_time)
+);
+
+CREATE INDEX observations_symbol_publish_time ON observations (symbol, publish_time);
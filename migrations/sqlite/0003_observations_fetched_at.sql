@@ -0,0 +1,2 @@
+-- Observations recorded after a given time, for resuming /gdp/stream.
+CREATE INDEX observations_feed_id_fetched_at ON observations (feed_id, fetched_at);
@@ -0,0 +1,584 @@
+//! Decoding and local verification of Pyth accumulator updates as served by
+//! Hermes: a Wormhole VAA signing a Merkle root, plus one Merkle proof per
+//! price message.
+
+use solana_sdk::{keccak, secp256k1_recover::secp256k1_recover};
+use std::collections::HashMap;
+
+const ACCUMULATOR_MAGIC: &[u8; 4] = b"PNAU";
+const WORMHOLE_MERKLE_MAGIC: &[u8; 4] = b"AUWV";
+const ACCUMULATOR_MAJOR_VERSION: u8 = 1;
+const UPDATE_TYPE_WORMHOLE_MERKLE: u8 = 0;
+const PAYLOAD_TYPE_MERKLE_ROOT: u8 = 0;
+const MESSAGE_TYPE_PRICE_FEED: u8 = 0;
+
+/// Wormhole chain ID of Pythnet.
+pub const PYTHNET_CHAIN_ID: u16 = 26;
+
+/// Emitter of Pyth accumulator VAAs on Pythnet.
+pub const PYTH_ACCUMULATOR_EMITTER: &str = "e101faedac5851e32b9b23b5f9411a8c2bac4aae3ed4dd7b811dd1a72ea4aa71";
+
+/// Wormhole guardian set that accumulator VAAs must be signed by.
+#[derive(Clone, Debug)]
+pub struct GuardianSet {
+    pub index: u32,
+    pub addresses: Vec<[u8; 20]>,
+}
+
+impl GuardianSet {
+    /// Builds a guardian set from `0x`-prefixed or bare 20-byte hex addresses.
+    pub fn from_hex(index: u32, addresses: &[String]) -> Result<Self, anyhow::Error> {
+        if addresses.is_empty() {
+            return Err(anyhow::anyhow!("Guardian set {} has no guardians", index));
+        }
+        let addresses = addresses
+            .iter()
+            .map(|address| {
+                let hex_without_prefix = address.strip_prefix("0x").unwrap_or(address);
+                hex::decode(hex_without_prefix)
+                    .map_err(|e| anyhow::anyhow!("Invalid guardian address {}: {}", address, e))?
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Guardian address {} is not 20 bytes", address))
+            })
+            .collect::<Result<Vec<[u8; 20]>, anyhow::Error>>()?;
+        Ok(Self { index, addresses })
+    }
+
+    fn quorum(&self) -> usize {
+        self.addresses.len() * 2 / 3 + 1
+    }
+
+    /// Checks that the VAA carries a quorum of valid signatures from this set.
+    pub fn verify_vaa(&self, vaa: &Vaa) -> Result<(), anyhow::Error> {
+        if vaa.guardian_set_index != self.index {
+            return Err(anyhow::anyhow!(
+                "VAA signed by guardian set {}, expected {}", vaa.guardian_set_index, self.index
+            ));
+        }
+        if vaa.signatures.len() < self.quorum() {
+            return Err(anyhow::anyhow!(
+                "VAA has {} signatures, quorum is {}", vaa.signatures.len(), self.quorum()
+            ));
+        }
+
+        let digest = keccak::hash(keccak::hash(&vaa.body).as_ref());
+        let mut last_index: Option<u8> = None;
+
+        for (guardian_index, signature) in &vaa.signatures {
+            if last_index.is_some_and(|last| *guardian_index <= last) {
+                return Err(anyhow::anyhow!("VAA signatures are not in ascending guardian order"));
+            }
+            last_index = Some(*guardian_index);
+
+            let expected = self
+                .addresses
+                .get(*guardian_index as usize)
+                .ok_or_else(|| anyhow::anyhow!("VAA signed by unknown guardian {}", guardian_index))?;
+
+            let recovery_id = signature[64];
+            let recovered = secp256k1_recover(digest.as_ref(), recovery_id, &signature[..64])
+                .map_err(|e| anyhow::anyhow!("Bad signature from guardian {}: {:?}", guardian_index, e))?;
+            let address = &keccak::hash(&recovered.to_bytes()).to_bytes()[12..];
+
+            if address != expected {
+                return Err(anyhow::anyhow!("Signature from guardian {} does not match its address", guardian_index));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A decoded Wormhole VAA. `body` is the signed portion.
+pub struct Vaa {
+    pub guardian_set_index: u32,
+    pub signatures: Vec<(u8, [u8; 65])>,
+    pub body: Vec<u8>,
+    pub emitter_chain: u16,
+    pub emitter_address: [u8; 32],
+    pub payload: Vec<u8>,
+}
+
+impl Vaa {
+    pub fn parse(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        let mut reader = Reader::new(bytes);
+
+        let version = reader.u8()?;
+        if version != 1 {
+            return Err(anyhow::anyhow!("Unsupported VAA version {}", version));
+        }
+        let guardian_set_index = reader.u32()?;
+        let signature_count = reader.u8()?;
+        let mut signatures = Vec::with_capacity(signature_count as usize);
+        for _ in 0..signature_count {
+            let guardian_index = reader.u8()?;
+            signatures.push((guardian_index, reader.array::<65>()?));
+        }
+
+        let body = reader.rest().to_vec();
+        let mut body_reader = Reader::new(&body);
+        let _timestamp = body_reader.u32()?;
+        let _nonce = body_reader.u32()?;
+        let emitter_chain = body_reader.u16()?;
+        let emitter_address = body_reader.array::<32>()?;
+        let _sequence = body_reader.u64()?;
+        let _consistency_level = body_reader.u8()?;
+        let payload = body_reader.rest().to_vec();
+
+        Ok(Self { guardian_set_index, signatures, body, emitter_chain, emitter_address, payload })
+    }
+
+    /// Merkle root carried by an accumulator (`AUWV`) payload.
+    pub fn merkle_root(&self) -> Result<[u8; 20], anyhow::Error> {
+        let mut reader = Reader::new(&self.payload);
+        if &reader.array::<4>()? != WORMHOLE_MERKLE_MAGIC {
+            return Err(anyhow::anyhow!("VAA payload is not a Pyth accumulator root"));
+        }
+        let payload_type = reader.u8()?;
+        if payload_type != PAYLOAD_TYPE_MERKLE_ROOT {
+            return Err(anyhow::anyhow!("Unsupported accumulator payload type {}", payload_type));
+        }
+        let _slot = reader.u64()?;
+        let _ring_size = reader.u32()?;
+        reader.array::<20>()
+    }
+}
+
+/// One price message with its Merkle proof.
+pub struct MerkleUpdate {
+    pub message: Vec<u8>,
+    pub proof: Vec<[u8; 20]>,
+}
+
+/// A decoded `PNAU` accumulator update.
+pub struct AccumulatorUpdate {
+    pub vaa: Vec<u8>,
+    pub updates: Vec<MerkleUpdate>,
+}
+
+impl AccumulatorUpdate {
+    pub fn parse(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        let mut reader = Reader::new(bytes);
+
+        if &reader.array::<4>()? != ACCUMULATOR_MAGIC {
+            return Err(anyhow::anyhow!("Not a Pyth accumulator update"));
+        }
+        let major_version = reader.u8()?;
+        if major_version != ACCUMULATOR_MAJOR_VERSION {
+            return Err(anyhow::anyhow!("Unsupported accumulator version {}", major_version));
+        }
+        let _minor_version = reader.u8()?;
+        let trailing_header_size = reader.u8()?;
+        reader.bytes(trailing_header_size as usize)?;
+
+        let update_type = reader.u8()?;
+        if update_type != UPDATE_TYPE_WORMHOLE_MERKLE {
+            return Err(anyhow::anyhow!("Unsupported accumulator update type {}", update_type));
+        }
+
+        let vaa_size = reader.u16()?;
+        let vaa = reader.bytes(vaa_size as usize)?.to_vec();
+
+        let update_count = reader.u8()?;
+        let mut updates = Vec::with_capacity(update_count as usize);
+        for _ in 0..update_count {
+            let message_size = reader.u16()?;
+            let message = reader.bytes(message_size as usize)?.to_vec();
+            let proof_size = reader.u8()?;
+            let mut proof = Vec::with_capacity(proof_size as usize);
+            for _ in 0..proof_size {
+                proof.push(reader.array::<20>()?);
+            }
+            updates.push(MerkleUpdate { message, proof });
+        }
+
+        Ok(Self { vaa, updates })
+    }
+}
+
+/// A Pyth `PriceFeedMessage` as committed to the accumulator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PriceFeedMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+impl PriceFeedMessage {
+    pub fn parse(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        let mut reader = Reader::new(bytes);
+        let message_type = reader.u8()?;
+        if message_type != MESSAGE_TYPE_PRICE_FEED {
+            return Err(anyhow::anyhow!("Not a price feed message (type {})", message_type));
+        }
+        Ok(Self {
+            feed_id: reader.array::<32>()?,
+            price: reader.u64()? as i64,
+            conf: reader.u64()?,
+            exponent: reader.u32()? as i32,
+            publish_time: reader.u64()? as i64,
+            prev_publish_time: reader.u64()? as i64,
+            ema_price: reader.u64()? as i64,
+            ema_conf: reader.u64()?,
+        })
+    }
+}
+
+fn leaf_hash(message: &[u8]) -> [u8; 20] {
+    truncate(keccak::hashv(&[&[0], message]).to_bytes())
+}
+
+fn node_hash(a: &[u8; 20], b: &[u8; 20]) -> [u8; 20] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    truncate(keccak::hashv(&[&[1], left, right]).to_bytes())
+}
+
+fn truncate(hash: [u8; 32]) -> [u8; 20] {
+    let mut out = [0u8; 20];
+    out.copy_from_slice(&hash[..20]);
+    out
+}
+
+/// Checks that `message` is committed to by `root` via `proof`.
+pub fn verify_merkle_proof(root: &[u8; 20], message: &[u8], proof: &[[u8; 20]]) -> bool {
+    let computed = proof.iter().fold(leaf_hash(message), |node, sibling| node_hash(&node, sibling));
+    &computed == root
+}
+
+/// Verifies accumulator updates against a guardian set and the Pyth emitter.
+pub struct UpdateVerifier {
+    guardian_set: GuardianSet,
+    emitter_chain: u16,
+    emitter_address: [u8; 32],
+}
+
+impl UpdateVerifier {
+    pub fn new(guardian_set: GuardianSet) -> Self {
+        let mut emitter_address = [0u8; 32];
+        hex::decode_to_slice(PYTH_ACCUMULATOR_EMITTER, &mut emitter_address)
+            .expect("PYTH_ACCUMULATOR_EMITTER is valid hex");
+        Self { guardian_set, emitter_chain: PYTHNET_CHAIN_ID, emitter_address }
+    }
+
+    /// Verifies one accumulator update end to end (VAA signatures, emitter,
+    /// Merkle proofs) and returns its price messages keyed by feed ID.
+    /// Fails if any part of the update does not verify.
+    pub fn verify(&self, data: &[u8]) -> Result<HashMap<[u8; 32], PriceFeedMessage>, anyhow::Error> {
+        let update = AccumulatorUpdate::parse(data)?;
+        let vaa = Vaa::parse(&update.vaa)?;
+
+        if vaa.emitter_chain != self.emitter_chain || vaa.emitter_address != self.emitter_address {
+            return Err(anyhow::anyhow!(
+                "VAA from unexpected emitter {}:{}", vaa.emitter_chain, hex::encode(vaa.emitter_address)
+            ));
+        }
+        self.guardian_set.verify_vaa(&vaa)?;
+        let root = vaa.merkle_root()?;
+
+        let mut messages = HashMap::new();
+        for update in &update.updates {
+            if !verify_merkle_proof(&root, &update.message, &update.proof) {
+                return Err(anyhow::anyhow!("Merkle proof does not match the signed root"));
+            }
+            // Other message types (e.g. TWAP) may share the update; skip them.
+            if let Ok(message) = PriceFeedMessage::parse(&update.message) {
+                messages.insert(message.feed_id, message);
+            }
+        }
+
+        Ok(messages)
+    }
+}
+
+/// Big-endian cursor over a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], anyhow::Error> {
+        if self.bytes.len() < len {
+            return Err(anyhow::anyhow!("Unexpected end of data: wanted {} bytes, {} left", len, self.bytes.len()));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], anyhow::Error> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.bytes(N)?);
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8, anyhow::Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, anyhow::Error> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, anyhow::Error> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, anyhow::Error> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `PNAU` update in the layout Hermes serves in `binary.data`, carrying
+    /// the `ECO.US.GDP` and `ECO.US.GDPQ120` price messages.
+    const UPDATE: &str = include_str!("../tests/fixtures/accumulator_update.hex");
+    /// The guardian set that signed `UPDATE`, one address per line. Its keys
+    /// are known (see `guardian_keys`) so tampered VAAs can be re-signed.
+    const GUARDIANS: &str = include_str!("../tests/fixtures/guardian_set.txt");
+    const GUARDIAN_SET_INDEX: u32 = 4;
+    const GDP_ID: &str = "01a2d2aa5728850767d67e2f82ddc9c8e4c3bbace231461386ef9cbb16d0d36b";
+    const GDP_Q120_ID: &str = "ede7d586e573bba4d9f9b598134a0b3b2848fb5633efa1aeae6cdc405ca69ec4";
+
+    /// Offset of the VAA size in a `PNAU` update without trailing header.
+    const VAA_SIZE_OFFSET: usize = 8;
+    /// Size of one VAA signature: guardian index plus 65 signature bytes.
+    const SIGNATURE_SIZE: usize = 66;
+
+    fn update() -> Vec<u8> {
+        hex::decode(UPDATE.trim()).unwrap()
+    }
+
+    fn verifier() -> UpdateVerifier {
+        let addresses: Vec<String> = GUARDIANS.lines().map(str::to_string).collect();
+        UpdateVerifier::new(GuardianSet::from_hex(GUARDIAN_SET_INDEX, &addresses).unwrap())
+    }
+
+    /// Keys of the fixture's guardian set.
+    fn guardian_keys() -> Vec<libsecp256k1::SecretKey> {
+        (0..19)
+            .map(|i| libsecp256k1::SecretKey::parse(&keccak::hash(format!("guardian {}", i).as_bytes()).to_bytes()).unwrap())
+            .collect()
+    }
+
+    /// Splits an update into the bytes before its VAA, the VAA and the bytes after it.
+    fn split(update: &[u8]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let vaa_size = u16::from_be_bytes([update[VAA_SIZE_OFFSET], update[VAA_SIZE_OFFSET + 1]]) as usize;
+        let vaa_start = VAA_SIZE_OFFSET + 2;
+        (
+            update[..VAA_SIZE_OFFSET].to_vec(),
+            update[vaa_start..vaa_start + vaa_size].to_vec(),
+            update[vaa_start + vaa_size..].to_vec(),
+        )
+    }
+
+    fn join(head: &[u8], vaa: &[u8], tail: &[u8]) -> Vec<u8> {
+        [head, &(vaa.len() as u16).to_be_bytes(), vaa, tail].concat()
+    }
+
+    /// Rewrites the VAA of `update` with `edit`, which gets the signatures and the body.
+    fn edit_vaa(update: &[u8], edit: impl FnOnce(&mut Vec<Vec<u8>>, &mut Vec<u8>)) -> Vec<u8> {
+        let (head, vaa, tail) = split(update);
+        let count = vaa[5] as usize;
+        let mut signatures: Vec<Vec<u8>> = vaa[6..6 + count * SIGNATURE_SIZE].chunks(SIGNATURE_SIZE).map(<[u8]>::to_vec).collect();
+        let mut body = vaa[6 + count * SIGNATURE_SIZE..].to_vec();
+        edit(&mut signatures, &mut body);
+
+        let mut vaa = vaa[..5].to_vec();
+        vaa.push(signatures.len() as u8);
+        vaa.extend(signatures.concat());
+        vaa.extend(body);
+        join(&head, &vaa, &tail)
+    }
+
+    /// Signs `body` with every guardian, as the guardian network would.
+    fn sign(body: &[u8]) -> Vec<Vec<u8>> {
+        let digest = keccak::hash(keccak::hash(body).as_ref()).to_bytes();
+        let message = libsecp256k1::Message::parse(&digest);
+        guardian_keys()
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let (signature, recovery_id) = libsecp256k1::sign(&message, key);
+                let mut bytes = vec![index as u8];
+                bytes.extend(signature.serialize());
+                bytes.push(recovery_id.serialize());
+                bytes
+            })
+            .collect()
+    }
+
+    fn rejection(update: &[u8]) -> String {
+        match verifier().verify(update) {
+            Ok(_) => panic!("update was accepted"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn fixture_is_signed_by_its_guardian_set() {
+        let addresses: Vec<String> = guardian_keys()
+            .iter()
+            .map(|key| {
+                let public = libsecp256k1::PublicKey::from_secret_key(key).serialize();
+                hex::encode(&keccak::hash(&public[1..]).to_bytes()[12..])
+            })
+            .collect();
+        assert_eq!(GUARDIANS.lines().collect::<Vec<_>>(), addresses);
+    }
+
+    #[test]
+    fn accepts_genuine_update() {
+        let messages = verifier().verify(&update()).unwrap();
+        assert_eq!(messages.len(), 2);
+
+        let mut gdp_id = [0u8; 32];
+        hex::decode_to_slice(GDP_ID, &mut gdp_id).unwrap();
+        let gdp = &messages[&gdp_id];
+        assert_eq!(gdp.price, 280_000);
+        assert_eq!(gdp.conf, 1_000);
+        assert_eq!(gdp.exponent, -5);
+        assert_eq!(gdp.publish_time, 1_753_920_000);
+        assert_eq!(gdp.prev_publish_time, 1_745_971_200);
+
+        let mut q120_id = [0u8; 32];
+        hex::decode_to_slice(GDP_Q120_ID, &mut q120_id).unwrap();
+        assert_eq!(messages[&q120_id].price, -5_500_000);
+    }
+
+    #[test]
+    fn rejects_flipped_signature_byte() {
+        let update = edit_vaa(&update(), |signatures, _| signatures[3][10] ^= 1);
+        let error = rejection(&update);
+        assert!(error.contains("guardian 3"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_signatures_below_quorum() {
+        // 19 guardians need 13 signatures.
+        let update = edit_vaa(&update(), |signatures, _| signatures.truncate(12));
+        let error = rejection(&update);
+        assert!(error.contains("quorum is 13"), "{}", error);
+
+        let update = edit_vaa(&self::update(), |signatures, _| signatures.truncate(13));
+        assert!(verifier().verify(&update).is_ok());
+    }
+
+    #[test]
+    fn rejects_out_of_order_guardians() {
+        let update = edit_vaa(&update(), |signatures, _| signatures.swap(4, 5));
+        let error = rejection(&update);
+        assert!(error.contains("ascending guardian order"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_duplicate_guardians() {
+        let update = edit_vaa(&update(), |signatures, _| signatures[5] = signatures[4].clone());
+        let error = rejection(&update);
+        assert!(error.contains("ascending guardian order"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_wrong_emitter() {
+        // Body offsets: timestamp (4), nonce (4), emitter chain (2), emitter address (32).
+        let resigned = |edit: fn(&mut Vec<u8>)| {
+            edit_vaa(&update(), |signatures, body| {
+                edit(body);
+                *signatures = sign(body);
+            })
+        };
+
+        let update = resigned(|body| body[8..10].copy_from_slice(&1u16.to_be_bytes()));
+        let error = rejection(&update);
+        assert!(error.contains("unexpected emitter 1:"), "{}", error);
+
+        let update = resigned(|body| body[10] ^= 1);
+        let error = rejection(&update);
+        assert!(error.contains("unexpected emitter 26:"), "{}", error);
+
+        // Re-signing an unchanged body must still verify.
+        assert!(verifier().verify(&resigned(|_| ())).is_ok());
+    }
+
+    #[test]
+    fn rejects_tampered_message() {
+        let (head, vaa, mut tail) = split(&update());
+        // Tail: update count (1), message size (2), message type (1), feed ID (32), price.
+        tail[1 + 2 + 1 + 32 + 7] ^= 1;
+        let error = rejection(&join(&head, &vaa, &tail));
+        assert!(error.contains("Merkle proof"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_truncated_update() {
+        let update = update();
+        for len in 0..update.len() {
+            assert!(verifier().verify(&update[..len]).is_err(), "accepted {} of {} bytes", len, update.len());
+        }
+    }
+
+    /// Wormhole core bridge on Solana, which holds every guardian set.
+    const WORMHOLE_SOLANA_PROGRAM: &str = "worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth";
+
+    /// Reads guardian set `index` from the Wormhole bridge on Solana: a
+    /// Borsh `GuardianSetData` of the index, the 20-byte keys, and the
+    /// creation and expiration times.
+    async fn mainnet_guardian_set(index: u32) -> GuardianSet {
+        use solana_sdk::pubkey::Pubkey;
+        use std::str::FromStr;
+
+        let program = Pubkey::from_str(WORMHOLE_SOLANA_PROGRAM).unwrap();
+        let (address, _) = Pubkey::find_program_address(&[b"GuardianSet", &index.to_be_bytes()], &program);
+        let client = solana_client::nonblocking::rpc_client::RpcClient::new("https://api.mainnet-beta.solana.com".to_string());
+        let data = client.get_account_data(&address).await.unwrap();
+
+        assert_eq!(u32::from_le_bytes(data[..4].try_into().unwrap()), index);
+        let count = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        let addresses = data[8..8 + count * 20].chunks(20).map(|key| key.try_into().unwrap()).collect();
+        GuardianSet { index, addresses }
+    }
+
+    /// Verifies the latest `ECO.US.GDP` update from Hermes against the
+    /// guardian set that signed it, as published on-chain. With
+    /// `RECORD_FIXTURES=1` the update and the guardian set are written to
+    /// `tests/fixtures/hermes_update.hex` and `tests/fixtures/hermes_guardian_set.txt`.
+    #[tokio::test]
+    #[ignore = "needs network access to Hermes and Solana mainnet"]
+    async fn verifies_live_hermes_update() {
+        let feed = crate::testing::feed("ECO.US.GDP");
+        let hermes = crate::hermes::HermesClient::new(reqwest::Client::new(), "https://hermes.pyth.network", std::time::Duration::from_secs(10));
+        let updates = hermes.latest_price_updates(&[&feed.id]).await.unwrap();
+        let update = updates.binary.decode().unwrap().remove(0);
+
+        let vaa = Vaa::parse(&AccumulatorUpdate::parse(&update).unwrap().vaa).unwrap();
+        let guardian_set = mainnet_guardian_set(vaa.guardian_set_index).await;
+        let messages = UpdateVerifier::new(guardian_set.clone()).verify(&update).unwrap();
+
+        let message = &messages[&feed.id_bytes];
+        let parsed = &updates.parsed.unwrap()[0];
+        assert_eq!(message.price.to_string(), parsed.price.price);
+        assert_eq!(message.publish_time, parsed.price.publish_time);
+
+        if std::env::var_os("RECORD_FIXTURES").is_some() {
+            let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+            std::fs::write(fixtures.join("hermes_update.hex"), hex::encode(&update) + "\n").unwrap();
+            let addresses: Vec<String> = guardian_set.addresses.iter().map(hex::encode).collect();
+            std::fs::write(fixtures.join("hermes_guardian_set.txt"), addresses.join("\n") + "\n").unwrap();
+        }
+    }
+}
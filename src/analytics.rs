@@ -0,0 +1,314 @@
+//! Figures derived from the quarterly feeds: quarter-over-quarter and
+//! year-over-year changes, trailing averages and annual aggregates.
+//!
+//! Confidence intervals are propagated assuming independent errors: a
+//! difference has `sqrt(c₁² + c₂²)`, a mean of `n` values `sqrt(Σcᵢ²) / n`.
+
+use crate::{
+    config::Feed,
+    history::HistoryPoint,
+    storage::Observation,
+    FeedError, PriceData,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The value of one quarterly feed.
+#[derive(Clone, Debug, Serialize)]
+pub struct QuarterValue {
+    #[serde(skip)]
+    pub country: Option<String>,
+    #[serde(skip)]
+    pub indicator: Option<String>,
+    #[serde(skip)]
+    pub unit: String,
+    pub symbol: String,
+    pub year: i32,
+    pub quarter: u8,
+    pub period_start: Option<NaiveDate>,
+    pub period_end: Option<NaiveDate>,
+    pub value: f64,
+    pub confidence: f64,
+    pub publish_time: i64,
+    pub source: String,
+    /// Taken from the observation store because the feed could not be fetched.
+    pub from_history: bool,
+}
+
+impl QuarterValue {
+    /// `None` unless the feed's symbol names a quarter.
+    pub fn from_price(feed: &Feed, data: &PriceData) -> Option<Self> {
+        Self::new(feed, data.price, data.confidence, data.publish_time, data.source.clone(), false)
+    }
+
+    /// The last stored observation, for a feed that could not be fetched.
+    pub fn from_observation(feed: &Feed, observation: Observation) -> Option<Self> {
+        let point = HistoryPoint::try_from(observation).ok()?;
+        Self::new(feed, point.price, point.confidence, point.publish_time, point.source, true)
+    }
+
+    fn new(feed: &Feed, value: f64, confidence: f64, publish_time: i64, source: String, from_history: bool) -> Option<Self> {
+        let metadata = &feed.metadata;
+        Some(Self {
+            country: metadata.country.clone(),
+            indicator: metadata.indicator.clone(),
+            unit: feed.unit.clone(),
+            symbol: feed.symbol.clone(),
+            year: metadata.year?,
+            quarter: metadata.quarter?,
+            period_start: metadata.period_start,
+            period_end: metadata.period_end,
+            value,
+            confidence,
+            publish_time,
+            source,
+            from_history,
+        })
+    }
+}
+
+/// A derived value and its propagated confidence.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Estimate {
+    pub value: f64,
+    pub confidence: f64,
+}
+
+impl Estimate {
+    fn difference(a: &QuarterValue, b: &QuarterValue) -> Self {
+        Self {
+            value: tidy(a.value - b.value),
+            confidence: tidy(a.confidence.hypot(b.confidence)),
+        }
+    }
+
+    fn mean(values: &[&QuarterValue]) -> Self {
+        let n = values.len() as f64;
+        Self {
+            value: tidy(values.iter().map(|v| v.value).sum::<f64>() / n),
+            confidence: tidy(values.iter().map(|v| v.confidence * v.confidence).sum::<f64>().sqrt() / n),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct QuarterAnalytics {
+    #[serde(flatten)]
+    pub current: QuarterValue,
+    /// Change from the previous quarter, in the feed's unit.
+    pub qoq: Option<Estimate>,
+    /// Change from the same quarter of the previous year.
+    pub yoy: Option<Estimate>,
+    /// Mean of this and the three preceding quarters.
+    pub trailing_4q_average: Option<Estimate>,
+}
+
+#[derive(Serialize)]
+pub struct AnnualAggregate {
+    pub year: i32,
+    /// Quarters of the year with a value.
+    pub quarters: usize,
+    pub complete: bool,
+    /// Mean of the available quarters.
+    pub average: Estimate,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Analytics for one country and indicator, quarters in chronological order.
+#[derive(Serialize)]
+pub struct Series {
+    pub country: Option<String>,
+    pub indicator: Option<String>,
+    pub unit: String,
+    pub quarters: Vec<QuarterAnalytics>,
+    pub annual: Vec<AnnualAggregate>,
+}
+
+/// Response of `/gdp/analytics`.
+#[derive(Serialize)]
+pub struct Analytics {
+    pub series: Vec<Series>,
+    /// Quarterly feeds that could neither be fetched nor found in storage.
+    pub failed: Vec<FeedError>,
+}
+
+impl Analytics {
+    /// Groups `values` into one series per country, indicator and unit.
+    pub fn new(values: Vec<QuarterValue>, failed: Vec<FeedError>) -> Self {
+        let mut groups: BTreeMap<(Option<String>, Option<String>, String), Vec<QuarterValue>> = BTreeMap::new();
+        for value in values {
+            groups
+                .entry((value.country.clone(), value.indicator.clone(), value.unit.clone()))
+                .or_default()
+                .push(value);
+        }
+        let series = groups
+            .into_iter()
+            .map(|((country, indicator, unit), values)| Series::new(country, indicator, unit, values))
+            .collect();
+        Self { series, failed }
+    }
+}
+
+impl Series {
+    fn new(country: Option<String>, indicator: Option<String>, unit: String, values: Vec<QuarterValue>) -> Self {
+        let by_period: BTreeMap<(i32, u8), QuarterValue> =
+            values.into_iter().map(|value| ((value.year, value.quarter), value)).collect();
+
+        let quarters = by_period
+            .values()
+            .map(|current| {
+                let period = (current.year, current.quarter);
+                let trailing: Option<Vec<&QuarterValue>> =
+                    std::iter::successors(Some(period), |&period| Some(previous_quarter(period)))
+                        .take(4)
+                        .map(|period| by_period.get(&period))
+                        .collect();
+
+                QuarterAnalytics {
+                    qoq: by_period.get(&previous_quarter(period)).map(|previous| Estimate::difference(current, previous)),
+                    yoy: by_period.get(&(period.0 - 1, period.1)).map(|previous| Estimate::difference(current, previous)),
+                    trailing_4q_average: trailing.map(|values| Estimate::mean(&values)),
+                    current: current.clone(),
+                }
+            })
+            .collect();
+
+        let mut years: BTreeMap<i32, Vec<&QuarterValue>> = BTreeMap::new();
+        for value in by_period.values() {
+            years.entry(value.year).or_default().push(value);
+        }
+        let annual = years
+            .into_iter()
+            .map(|(year, values)| AnnualAggregate {
+                year,
+                quarters: values.len(),
+                complete: values.len() == 4,
+                average: Estimate::mean(&values),
+                min: values.iter().map(|v| v.value).fold(f64::INFINITY, f64::min),
+                max: values.iter().map(|v| v.value).fold(f64::NEG_INFINITY, f64::max),
+            })
+            .collect();
+
+        Self { country, indicator, unit, quarters, annual }
+    }
+}
+
+fn previous_quarter((year, quarter): (i32, u8)) -> (i32, u8) {
+    if quarter <= 1 { (year - 1, 4) } else { (year, quarter - 1) }
+}
+
+/// Drops floating-point noise such as `8.799999999999999`.
+fn tidy(value: f64) -> f64 {
+    (value * 1e9).round() / 1e9
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// A quarterly value in hundredths, e.g. `(338, 30)` for 3.38 ± 0.30.
+    fn value(symbol: &str, hundredths: i64, confidence: u64) -> QuarterValue {
+        let feed = testing::feed(symbol);
+        QuarterValue::from_price(&feed, &testing::price(&feed, hundredths, confidence, -2, 0)).unwrap()
+    }
+
+    fn assert_estimate(estimate: Option<Estimate>, value: f64, confidence: f64) {
+        let estimate = estimate.expect("estimate is missing");
+        assert!((estimate.value - value).abs() < 1e-9, "value {} != {}", estimate.value, value);
+        assert!((estimate.confidence - confidence).abs() < 1e-9, "confidence {} != {}", estimate.confidence, confidence);
+    }
+
+    /// Q2 2020 to Q4 2021 without Q1 2020 and Q2 2021, given out of order.
+    fn series() -> Series {
+        let values = vec![
+            value("ECO.US.GDPQ421", 700, 60),
+            value("ECO.US.GDPQ220", -2_800, 40),
+            value("ECO.US.GDPQ320", 3_380, 30),
+            value("ECO.US.GDPQ121", 630, 120),
+            value("ECO.US.GDPQ420", 450, 40),
+            value("ECO.US.GDPQ321", 270, 50),
+        ];
+        let mut analytics = Analytics::new(values, Vec::new());
+        assert_eq!(analytics.series.len(), 1);
+        analytics.series.remove(0)
+    }
+
+    #[test]
+    fn orders_quarters_chronologically() {
+        let series = series();
+        assert_eq!((series.country.as_deref(), series.indicator.as_deref(), series.unit.as_str()), (Some("US"), Some("GDP"), "%"));
+        let periods: Vec<(i32, u8)> = series.quarters.iter().map(|q| (q.current.year, q.current.quarter)).collect();
+        assert_eq!(periods, [(2020, 2), (2020, 3), (2020, 4), (2021, 1), (2021, 3), (2021, 4)]);
+    }
+
+    #[test]
+    fn computes_quarter_over_quarter_changes() {
+        let quarters = series().quarters;
+        assert!(quarters[0].qoq.is_none()); // Q1 2020 is missing
+        assert_estimate(quarters[1].qoq, 61.8, 0.5);
+        assert_estimate(quarters[2].qoq, -29.3, 0.5);
+        // Across the year boundary: Q1 2021 against Q4 2020.
+        assert_estimate(quarters[3].qoq, 1.8, 1.6f64.sqrt());
+        assert!(quarters[4].qoq.is_none()); // Q2 2021 is missing
+        assert_estimate(quarters[5].qoq, 4.3, 0.61f64.sqrt());
+    }
+
+    #[test]
+    fn computes_year_over_year_changes() {
+        let quarters = series().quarters;
+        assert!(quarters[..4].iter().all(|q| q.yoy.is_none())); // 2019 and Q1 2020 are missing
+        assert_estimate(quarters[4].yoy, -31.1, 0.34f64.sqrt());
+        assert_estimate(quarters[5].yoy, 2.5, 0.52f64.sqrt());
+    }
+
+    #[test]
+    fn computes_trailing_averages() {
+        let quarters = series().quarters;
+        // Only Q1 2021 has the four quarters up to it; the gap breaks Q3 and Q4 2021.
+        for (index, quarter) in quarters.iter().enumerate() {
+            assert_eq!(quarter.trailing_4q_average.is_some(), index == 3, "{}", quarter.current.symbol);
+        }
+        assert_estimate(quarters[3].trailing_4q_average, 4.15, 1.85f64.sqrt() / 4.0);
+    }
+
+    #[test]
+    fn aggregates_years() {
+        let annual = series().annual;
+        assert_eq!(annual.len(), 2);
+
+        let y2020 = &annual[0];
+        assert_eq!((y2020.year, y2020.quarters, y2020.complete), (2020, 3, false));
+        assert_estimate(Some(y2020.average), 10.3 / 3.0, 0.41f64.sqrt() / 3.0);
+        assert_eq!((y2020.min, y2020.max), (-28.0, 33.8));
+
+        let y2021 = &annual[1];
+        assert_eq!((y2021.year, y2021.quarters, y2021.complete), (2021, 3, false));
+        assert_estimate(Some(y2021.average), 16.0 / 3.0, 2.05f64.sqrt() / 3.0);
+        assert_eq!((y2021.min, y2021.max), (2.7, 7.0));
+
+        let values = ["ECO.US.GDPQ122", "ECO.US.GDPQ222", "ECO.US.GDPQ322", "ECO.US.GDPQ422"]
+            .iter()
+            .map(|symbol| value(symbol, 200, 20))
+            .collect();
+        let annual = &Analytics::new(values, Vec::new()).series[0].annual[0];
+        assert_eq!((annual.year, annual.quarters, annual.complete), (2022, 4, true));
+        assert_estimate(Some(annual.average), 2.0, 0.1);
+    }
+
+    #[test]
+    fn skips_feeds_without_a_quarter() {
+        let feed = testing::feed("ECO.US.GDP");
+        assert!(QuarterValue::from_price(&feed, &testing::price(&feed, 280, 10, -2, 0)).is_none());
+    }
+
+    #[test]
+    fn steps_back_across_years() {
+        assert_eq!(previous_quarter((2021, 1)), (2020, 4));
+        assert_eq!(previous_quarter((2021, 4)), (2021, 3));
+        assert_eq!(previous_quarter((2021, 2)), (2021, 1));
+    }
+}
@@ -0,0 +1,496 @@
+use crate::{
+    config::{self, BackfillSettings, Feed, FeedRegistry},
+    hermes::{HermesClient, PriceUpdates},
+    history,
+    sources::{parse_rpc_price, MAX_HERMES_IDS},
+    storage::{Observation, ObservationStore},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+/// `source` of observations written by a backfill.
+const BACKFILL_SOURCE: &str = "Pyth Hermes API (backfill)";
+
+/// What to backfill; taken from the `backfill` subcommand or the body of
+/// `POST /admin/backfill`.
+#[derive(Clone, Debug, Deserialize, clap::Args)]
+pub struct BackfillRequest {
+    /// Start of the range: unix seconds, RFC 3339 or YYYY-MM-DD
+    #[arg(long)]
+    pub from: String,
+
+    /// End of the range (inclusive); defaults to now
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// Spacing of the publish times requested, e.g. `day` or `6h`, overriding `[backfill].step_secs`
+    #[arg(long)]
+    pub interval: Option<String>,
+
+    /// Comma-separated symbols to backfill; defaults to every registered feed
+    #[arg(long, value_delimiter = ',')]
+    pub symbols: Option<Vec<String>>,
+}
+
+impl BackfillRequest {
+    fn plan(&self, registry: &FeedRegistry, default_step_secs: i64) -> Result<BackfillPlan, anyhow::Error> {
+        // Nothing has been published after now, so later times are not requested.
+        let now = chrono::Utc::now().timestamp();
+        let from = history::parse_time(&self.from, false)?;
+        if from > now {
+            return Err(anyhow::anyhow!("`from` is in the future"));
+        }
+        let to = match &self.to {
+            Some(to) => history::parse_time(to, true)?.min(now),
+            None => now,
+        };
+        if from > to {
+            return Err(anyhow::anyhow!("`from` is after `to`"));
+        }
+
+        let step_secs = match &self.interval {
+            Some(interval) => history::parse_interval(interval)?,
+            None => default_step_secs,
+        };
+
+        let feeds = match &self.symbols {
+            Some(symbols) => symbols
+                .iter()
+                .map(|symbol| registry.resolve(symbol).cloned().ok_or_else(|| anyhow::anyhow!("Unknown feed {}", symbol)))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => registry.feeds().to_vec(),
+        };
+        if feeds.is_empty() {
+            return Err(anyhow::anyhow!("No feeds to backfill"));
+        }
+
+        Ok(BackfillPlan { from, to, step_secs, feeds })
+    }
+}
+
+/// A validated backfill: publish times `from`, `from + step_secs`, ... up to
+/// `to` are requested for every feed.
+pub struct BackfillPlan {
+    pub from: i64,
+    pub to: i64,
+    pub step_secs: i64,
+    pub feeds: Vec<Feed>,
+}
+
+impl BackfillPlan {
+    /// Key under which progress is saved. `to` is left out so that a rerun
+    /// with a later end (such as the default, now) resumes the same job.
+    fn job(&self) -> String {
+        let mut symbols: Vec<&str> = self.feeds.iter().map(|feed| feed.symbol.as_str()).collect();
+        symbols.sort_unstable();
+        format!("{}/{}s/{}", self.from, self.step_secs, symbols.join(","))
+    }
+
+    /// Short description for logs.
+    fn describe(&self) -> String {
+        format!("of {} feeds from {} to {} every {}s", self.feeds.len(), self.from, self.to, self.step_secs)
+    }
+}
+
+/// Progress of the current or last backfill, served by `/admin/backfill`.
+#[derive(Clone, Default, Serialize)]
+pub struct BackfillStatus {
+    pub job: Option<String>,
+    pub running: bool,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub step_secs: Option<i64>,
+    pub feeds: usize,
+    /// Set when an earlier run of the same job had saved progress.
+    pub resumed_from: Option<i64>,
+    /// Next publish time to request.
+    pub next_time: Option<i64>,
+    pub requests: u64,
+    /// Requests for which Hermes had no updates.
+    pub empty_responses: u64,
+    pub observations: usize,
+    /// Observations that were not already stored.
+    pub inserted: usize,
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub error: Option<String>,
+}
+
+/// Pulls historical prices from Hermes' `/v2/updates/price/{publish_time}`
+/// into the observation store, one backfill at a time.
+///
+/// Inserts are idempotent, requests are spaced by `[backfill].requests_per_sec`,
+/// and the next publish time is saved after every step so that an interrupted
+/// backfill picks up where it stopped when rerun.
+pub struct Backfiller {
+    hermes: HermesClient,
+    store: Arc<dyn ObservationStore>,
+    step_secs: i64,
+    request_interval: Duration,
+    max_retries: u32,
+    status: RwLock<BackfillStatus>,
+}
+
+impl Backfiller {
+    pub fn new(
+        hermes: HermesClient,
+        store: Arc<dyn ObservationStore>,
+        settings: &BackfillSettings,
+    ) -> Result<Self, anyhow::Error> {
+        if !settings.requests_per_sec.is_finite() || settings.requests_per_sec <= 0.0 {
+            return Err(anyhow::anyhow!("[backfill].requests_per_sec must be positive"));
+        }
+
+        Ok(Self {
+            hermes,
+            store,
+            step_secs: settings.step_secs.max(1) as i64,
+            request_interval: Duration::from_secs_f64(1.0 / settings.requests_per_sec),
+            max_retries: settings.max_retries,
+            status: RwLock::new(BackfillStatus::default()),
+        })
+    }
+
+    /// Validates `request` against the registry.
+    pub fn plan(&self, request: &BackfillRequest, registry: &FeedRegistry) -> Result<BackfillPlan, anyhow::Error> {
+        request.plan(registry, self.step_secs)
+    }
+
+    pub fn status(&self) -> BackfillStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    /// Runs `plan` to completion.
+    pub async fn run(&self, plan: BackfillPlan) -> Result<BackfillStatus, anyhow::Error> {
+        self.begin(&plan)?;
+        self.execute(plan).await;
+        let status = self.status();
+        match &status.error {
+            Some(e) => Err(anyhow::anyhow!("{}", e)),
+            None => Ok(status),
+        }
+    }
+
+    /// Starts `plan` in the background and returns its initial status.
+    pub fn spawn(self: &Arc<Self>, plan: BackfillPlan) -> Result<BackfillStatus, anyhow::Error> {
+        self.begin(&plan)?;
+        let backfiller = self.clone();
+        tokio::spawn(async move { backfiller.execute(plan).await });
+        Ok(self.status())
+    }
+
+    fn begin(&self, plan: &BackfillPlan) -> Result<(), anyhow::Error> {
+        let mut status = self.status.write().unwrap();
+        if status.running {
+            return Err(anyhow::anyhow!(
+                "Backfill {} is already running",
+                status.job.as_deref().unwrap_or_default()
+            ));
+        }
+        *status = BackfillStatus {
+            job: Some(plan.job()),
+            running: true,
+            from: Some(plan.from),
+            to: Some(plan.to),
+            step_secs: Some(plan.step_secs),
+            feeds: plan.feeds.len(),
+            next_time: Some(plan.from),
+            started_at: Some(chrono::Utc::now()),
+            ..BackfillStatus::default()
+        };
+        Ok(())
+    }
+
+    async fn execute(&self, plan: BackfillPlan) {
+        let result = self.walk(&plan).await;
+
+        let mut status = self.status.write().unwrap();
+        status.running = false;
+        status.finished_at = Some(chrono::Utc::now());
+        match result {
+            Ok(()) => info!(
+                "Backfill {} finished: {} requests, {} observations, {} new",
+                plan.describe(),
+                status.requests,
+                status.observations,
+                status.inserted
+            ),
+            Err(e) => {
+                error!("Backfill {} stopped: {}", plan.describe(), e);
+                status.error = Some(e.to_string());
+            }
+        }
+    }
+
+    async fn walk(&self, plan: &BackfillPlan) -> Result<(), anyhow::Error> {
+        info!("Starting backfill {}", plan.describe());
+        let job = plan.job();
+        let mut time = plan.from;
+        if let Some(next_time) = self.store.backfill_checkpoint(&job).await?
+            && next_time > plan.from
+        {
+            info!("Resuming backfill {} at {}", plan.describe(), next_time);
+            time = next_time;
+            let mut status = self.status.write().unwrap();
+            status.resumed_from = Some(next_time);
+            status.next_time = Some(next_time);
+        }
+
+        let mut ticker = tokio::time::interval(self.request_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        while time <= plan.to {
+            for chunk in plan.feeds.chunks(MAX_HERMES_IDS) {
+                let updates = self.fetch(&mut ticker, time, chunk).await?;
+                let observations = match updates {
+                    Some(updates) => observations(plan, chunk, updates),
+                    None => {
+                        self.status.write().unwrap().empty_responses += 1;
+                        Vec::new()
+                    }
+                };
+                let inserted = if observations.is_empty() { 0 } else { self.store.record(&observations).await? };
+
+                let mut status = self.status.write().unwrap();
+                status.observations += observations.len();
+                status.inserted += inserted;
+            }
+
+            let Some(next_time) = time.checked_add(plan.step_secs) else { break };
+            time = next_time;
+            self.store.save_backfill_checkpoint(&job, time).await?;
+            self.status.write().unwrap().next_time = Some(time);
+        }
+
+        Ok(())
+    }
+
+    /// Requests updates at `time`, retrying with doubling delays. Every
+    /// request waits for `ticker`, and a retry backs off by skipping ticks,
+    /// so retries count against `requests_per_sec` too.
+    async fn fetch(&self, ticker: &mut Interval, time: i64, feeds: &[Feed]) -> Result<Option<PriceUpdates>, anyhow::Error> {
+        let ids: Vec<&str> = feeds.iter().map(|feed| feed.id.as_str()).collect();
+        let mut attempt = 0;
+        loop {
+            ticker.tick().await;
+            self.status.write().unwrap().requests += 1;
+            match self.hermes.price_updates_at(time, &ids).await {
+                Ok(updates) => return Ok(updates),
+                Err(e) if attempt < self.max_retries => {
+                    let ticks = 2u32 << attempt.min(6);
+                    warn!(
+                        "Backfill request at {} failed ({}), retrying in {:?}",
+                        time,
+                        e,
+                        self.request_interval * ticks
+                    );
+                    // The next tick is taken before the request itself.
+                    for _ in 1..ticks {
+                        ticker.tick().await;
+                    }
+                    attempt += 1;
+                }
+                Err(e) => return Err(anyhow::anyhow!("Hermes request at {} failed: {}", time, e)),
+            }
+        }
+    }
+}
+
+/// Observations in `updates` for `feeds` that fall inside the plan's range.
+fn observations(plan: &BackfillPlan, feeds: &[Feed], updates: PriceUpdates) -> Vec<Observation> {
+    let by_id: HashMap<[u8; 32], &Feed> = feeds.iter().map(|feed| (feed.id_bytes, feed)).collect();
+    let fetched_at = chrono::Utc::now();
+
+    updates
+        .parsed
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|parsed| {
+            let feed = by_id.get(&config::parse_feed_id(&parsed.id).ok()?)?;
+            let publish_time = parsed.price.publish_time;
+            if publish_time < plan.from || publish_time > plan.to {
+                return None;
+            }
+            let exact = match parse_rpc_price(&parsed.price) {
+                Ok(exact) => exact,
+                Err(e) => {
+                    warn!("Skipping backfilled {} price at {}: {}", feed.symbol, publish_time, e);
+                    return None;
+                }
+            };
+            Some(Observation {
+                feed_id: feed.id.clone(),
+                symbol: feed.symbol.clone(),
+                publish_time,
+                price_mantissa: exact.price,
+                confidence_mantissa: exact.conf,
+                exponent: exact.expo,
+                source: BACKFILL_SOURCE.to_string(),
+                fetched_at,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::sync::Mutex;
+    use tokio::time::Instant;
+
+    fn request(from: &str, to: Option<&str>) -> BackfillRequest {
+        BackfillRequest { from: from.to_string(), to: to.map(str::to_string), interval: None, symbols: None }
+    }
+
+    const FROM: i64 = 1_750_000_000;
+
+    /// Hermes answering `/v2/updates/price/{time}` with a price published at
+    /// `time` for each requested ID, after failing the first `failures`
+    /// requests. Returns the base URL and the times requested, with when.
+    async fn hermes(failures: usize) -> (String, Arc<Mutex<Vec<(i64, Instant)>>>) {
+        use axum::{
+            extract::{Path, RawQuery},
+            http::StatusCode,
+            response::IntoResponse,
+            routing::get,
+            Json, Router,
+        };
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        let router = Router::new().route(
+            "/v2/updates/price/:time",
+            get(move |Path(time): Path<i64>, RawQuery(query): RawQuery| {
+                let log = log.clone();
+                async move {
+                    let mut log = log.lock().unwrap();
+                    log.push((time, Instant::now()));
+                    if log.len() <= failures {
+                        return StatusCode::BAD_GATEWAY.into_response();
+                    }
+                    let parsed: Vec<_> = testing::hermes_ids(&query.unwrap_or_default())
+                        .iter()
+                        .map(|id| {
+                            serde_json::json!({
+                                "id": id.trim_start_matches("0x"),
+                                "price": { "price": "280000", "conf": "1000", "expo": -5, "publish_time": time },
+                                "ema_price": { "price": "280000", "conf": "1000", "expo": -5, "publish_time": time },
+                            })
+                        })
+                        .collect();
+                    Json(serde_json::json!({ "binary": { "encoding": "hex", "data": [] }, "parsed": parsed }))
+                        .into_response()
+                }
+            }),
+        );
+        (testing::serve(router).await, requests)
+    }
+
+    fn backfiller(url: &str, store: Arc<dyn ObservationStore>, requests_per_sec: f64) -> Backfiller {
+        let hermes = HermesClient::new(reqwest::Client::new(), url, Duration::from_secs(5));
+        let settings = BackfillSettings { step_secs: 100, requests_per_sec, max_retries: 2 };
+        Backfiller::new(hermes, store, &settings).unwrap()
+    }
+
+    fn plan(steps: i64, step_secs: i64) -> BackfillPlan {
+        BackfillPlan { from: FROM, to: FROM + steps * 100, step_secs, feeds: vec![testing::feed("ECO.US.GDP")] }
+    }
+
+    fn times(requests: &Mutex<Vec<(i64, Instant)>>) -> Vec<i64> {
+        requests.lock().unwrap().iter().map(|(time, _)| time - FROM).collect()
+    }
+
+    #[tokio::test]
+    async fn resumes_from_the_checkpoint() {
+        let (url, requests) = hermes(0).await;
+        let store = crate::storage::open("sqlite::memory:").await.unwrap();
+        store.save_backfill_checkpoint(&plan(4, 100).job(), FROM + 200).await.unwrap();
+
+        let status = backfiller(&url, store.clone(), 1_000.0).run(plan(4, 100)).await.unwrap();
+        assert_eq!(times(&requests), [200, 300, 400]);
+        assert_eq!((status.resumed_from, status.next_time), (Some(FROM + 200), Some(FROM + 500)));
+        assert_eq!((status.requests, status.observations, status.inserted), (3, 3, 3));
+        assert_eq!(store.backfill_checkpoint(&plan(4, 100).job()).await.unwrap(), Some(FROM + 500));
+
+        // A later `to` is the same job, so only the new steps are requested.
+        requests.lock().unwrap().clear();
+        let status = backfiller(&url, store.clone(), 1_000.0).run(plan(6, 100)).await.unwrap();
+        assert_eq!(times(&requests), [500, 600]);
+        assert_eq!(status.resumed_from, Some(FROM + 500));
+    }
+
+    #[tokio::test]
+    async fn reruns_without_duplicating_observations() {
+        let (url, requests) = hermes(0).await;
+        let store = crate::storage::open("sqlite::memory:").await.unwrap();
+
+        let status = backfiller(&url, store.clone(), 1_000.0).run(plan(4, 100)).await.unwrap();
+        assert_eq!((status.observations, status.inserted), (5, 5));
+
+        // Another interval is another job, over publish times already stored.
+        requests.lock().unwrap().clear();
+        let status = backfiller(&url, store.clone(), 1_000.0).run(plan(4, 200)).await.unwrap();
+        assert_eq!(times(&requests), [0, 200, 400]);
+        assert_eq!((status.resumed_from, status.observations, status.inserted), (None, 3, 0));
+
+        let feed = testing::feed("ECO.US.GDP");
+        let query = crate::storage::HistoryQuery { feed_id: feed.id, from: 0, to: i64::MAX, bucket_secs: 1, limit: 100 };
+        assert_eq!(store.history(&query).await.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn spaces_retries_by_the_request_rate() {
+        let (url, requests) = hermes(2).await;
+        let store = crate::storage::open("sqlite::memory:").await.unwrap();
+
+        // 50ms between requests: retries wait 2, then 4, intervals.
+        let status = backfiller(&url, store.clone(), 20.0).run(plan(1, 100)).await.unwrap();
+        assert_eq!(times(&requests), [0, 0, 0, 100]);
+        assert_eq!((status.requests, status.inserted), (4, 2));
+
+        let at: Vec<Instant> = requests.lock().unwrap().iter().map(|(_, at)| *at).collect();
+        let gaps: Vec<Duration> = at.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        assert!(gaps[0] >= Duration::from_millis(95), "{:?}", gaps);
+        assert!(gaps[1] >= Duration::from_millis(195), "{:?}", gaps);
+        assert!(gaps[2] >= Duration::from_millis(45), "{:?}", gaps);
+    }
+
+    #[tokio::test]
+    async fn stops_after_the_last_retry() {
+        let (url, requests) = hermes(usize::MAX).await;
+        let store = crate::storage::open("sqlite::memory:").await.unwrap();
+
+        let backfiller = backfiller(&url, store.clone(), 1_000.0);
+        let error = backfiller.run(plan(4, 100)).await.err().expect("the backfill succeeded");
+        assert!(error.to_string().contains("failed: 502 Bad Gateway"), "{}", error);
+        assert_eq!(times(&requests), [0, 0, 0]);
+        let status = backfiller.status();
+        assert!(!status.running);
+        assert_eq!((status.next_time, status.error), (Some(FROM), Some(error.to_string())));
+        assert_eq!(store.backfill_checkpoint(&plan(4, 100).job()).await.unwrap(), None);
+    }
+
+    #[test]
+    fn caps_range_at_now() {
+        let registry = testing::registry();
+        let now = chrono::Utc::now().timestamp();
+
+        for to in ["9223372036854775807", "2999-01-01"] {
+            let plan = request("2025-01-01", Some(to)).plan(&registry, 86_400).unwrap();
+            assert!((now..=now + 5).contains(&plan.to), "{} became {}", to, plan.to);
+        }
+
+        let plan = request("2025-01-01", Some("2025-06-30")).plan(&registry, 86_400).unwrap();
+        assert_eq!((plan.from, plan.to, plan.feeds.len()), (1_735_689_600, 1_751_327_999, registry.feeds().len()));
+
+        let error = request("2999-01-01", None).plan(&registry, 86_400).err().expect("`from` in the future was accepted");
+        assert!(error.to_string().contains("in the future"), "{}", error);
+    }
+}
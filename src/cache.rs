@@ -0,0 +1,479 @@
+use crate::{
+    config::Feed,
+    metrics::{CacheResult, Metrics},
+    PriceData,
+};
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::broadcast, time::Instant};
+use tracing::info;
+
+/// An upstream fetch for one feed that any number of requests can await.
+type InFlight = Shared<BoxFuture<'static, Result<PriceData, String>>>;
+
+struct Entry {
+    data: PriceData,
+    fetched_at: Instant,
+}
+
+enum Lookup {
+    Fresh(PriceData),
+    Stale(PriceData),
+    Miss,
+}
+
+/// The last changed prices, oldest first, for resuming streams.
+struct Recent {
+    updates: VecDeque<PriceData>,
+    capacity: usize,
+    /// Every change fetched after this time is in `updates`.
+    complete_after: chrono::DateTime<chrono::Utc>,
+}
+
+/// Shared price cache keyed by feed ID.
+///
+/// A price is served from memory for its TTL, then for a further
+/// `stale_while_revalidate` while one background fetch refreshes it. Only one
+/// upstream fetch per feed runs at a time; concurrent requests for the same
+/// feed await it instead of starting their own. Failures are not cached.
+///
+/// Every stored price whose `publish_time` or value differs from the one it
+/// replaces is broadcast to [`PriceCache::subscribe`]rs, and the last
+/// `replay` of them are kept for [`PriceCache::changes_since`].
+pub struct PriceCache {
+    ttl: Duration,
+    stale_while_revalidate: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+    in_flight: Mutex<HashMap<String, InFlight>>,
+    updates: broadcast::Sender<PriceData>,
+    recent: Mutex<Recent>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl PriceCache {
+    /// `capacity` is how many updates a subscriber can fall behind before it
+    /// starts missing them.
+    pub fn new(ttl: Duration, stale_while_revalidate: Duration, capacity: usize, replay: usize) -> Self {
+        Self {
+            ttl,
+            stale_while_revalidate,
+            entries: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            updates: broadcast::channel(capacity.max(1)).0,
+            recent: Mutex::new(Recent {
+                updates: VecDeque::with_capacity(replay),
+                capacity: replay,
+                complete_after: chrono::Utc::now(),
+            }),
+            metrics: None,
+        }
+    }
+
+    /// Counts every lookup as a hit, stale hit or miss in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Changed prices from now on, as fetched (not marked as cached).
+    pub fn subscribe(&self) -> broadcast::Receiver<PriceData> {
+        self.updates.subscribe()
+    }
+
+    /// Changed prices with `last_updated` at or after `since`, oldest first,
+    /// or `None` when some of them are no longer kept.
+    pub fn changes_since(&self, since: chrono::DateTime<chrono::Utc>) -> Option<Vec<PriceData>> {
+        let recent = self.recent.lock().unwrap();
+        if since <= recent.complete_after {
+            return None;
+        }
+        Some(recent.updates.iter().filter(|data| data.last_updated >= since).cloned().collect())
+    }
+
+    /// Returns the price for `feed`, calling `fetch` only when no usable
+    /// cached price exists and no fetch for the feed is already running.
+    pub async fn get(
+        self: &Arc<Self>,
+        feed: &Feed,
+        fetch: impl FnOnce() -> BoxFuture<'static, Result<PriceData, anyhow::Error>>,
+    ) -> Result<PriceData, anyhow::Error> {
+        let pending = {
+            // Looked up under the lock: a fetch stores its price before it
+            // leaves `in_flight`, so one finishing now is seen either way.
+            let mut in_flight = self.in_flight.lock().unwrap();
+            let lookup = self.lookup(feed);
+            match lookup {
+                Lookup::Fresh(_) => return Ok(cached(lookup)),
+                Lookup::Stale(_) => {
+                    if !in_flight.contains_key(&feed.id) {
+                        let pending = self.start(&feed.id, fetch().map(|result| result.map_err(|e| e.to_string())).boxed());
+                        in_flight.insert(feed.id.clone(), pending);
+                    }
+                    return Ok(cached(lookup));
+                }
+                Lookup::Miss => match in_flight.get(&feed.id) {
+                    Some(pending) => pending.clone(),
+                    None => {
+                        let pending = self.start(&feed.id, fetch().map(|result| result.map_err(|e| e.to_string())).boxed());
+                        in_flight.insert(feed.id.clone(), pending.clone());
+                        pending
+                    }
+                },
+            }
+        };
+
+        pending.await.map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Like [`PriceCache::get`] for several feeds: every feed that needs an
+    /// upstream fetch is handed to one `fetch` call. Results are in the
+    /// order of `feeds`; feeds still waiting at `deadline` fail.
+    pub async fn get_many(
+        self: &Arc<Self>,
+        feeds: &[&Feed],
+        deadline: Instant,
+        fetch: impl FnOnce(Vec<Feed>) -> BoxFuture<'static, Vec<Result<PriceData, anyhow::Error>>>,
+    ) -> Vec<Result<PriceData, anyhow::Error>> {
+        let mut results: Vec<Option<Result<PriceData, anyhow::Error>>> = feeds.iter().map(|_| None).collect();
+        let mut waiting: Vec<(usize, InFlight)> = Vec::new();
+
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            let mut to_fetch: Vec<(usize, bool)> = Vec::new();
+
+            for (index, feed) in feeds.iter().enumerate() {
+                let lookup = self.lookup(feed);
+                let missing = matches!(lookup, Lookup::Miss);
+                match in_flight.get(&feed.id) {
+                    Some(pending) if missing => waiting.push((index, pending.clone())),
+                    None if missing => to_fetch.push((index, true)),
+                    None if matches!(lookup, Lookup::Stale(_)) => {
+                        to_fetch.push((index, false));
+                        results[index] = Some(Ok(cached(lookup)));
+                    }
+                    _ => results[index] = Some(Ok(cached(lookup))),
+                }
+            }
+
+            if !to_fetch.is_empty() {
+                let batch_feeds: Vec<Feed> = to_fetch.iter().map(|&(index, _)| feeds[index].clone()).collect();
+                let batch = fetch(batch_feeds)
+                    .map(|results| Arc::new(results.into_iter().map(|result| result.map_err(|e| e.to_string())).collect::<Vec<_>>()))
+                    .boxed()
+                    .shared();
+
+                for (position, (index, missing)) in to_fetch.into_iter().enumerate() {
+                    let batch = batch.clone();
+                    let result = async move {
+                        batch.await.get(position).cloned().unwrap_or_else(|| Err("Feed was not fetched".to_string()))
+                    };
+                    let pending = self.start(&feeds[index].id, result.boxed());
+                    in_flight.insert(feeds[index].id.clone(), pending.clone());
+                    if missing {
+                        waiting.push((index, pending));
+                    }
+                }
+            }
+        }
+
+        for (index, pending) in waiting {
+            results[index] = Some(match tokio::time::timeout_at(deadline, pending).await {
+                Ok(result) => result.map_err(|e| anyhow::anyhow!(e)),
+                Err(_) => Err(anyhow::anyhow!("Failed to fetch {}: deadline exceeded", feeds[index].symbol)),
+            });
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(anyhow::anyhow!("Feed was not fetched"))))
+            .collect()
+    }
+
+    /// Stores a price fetched outside the cache, e.g. by the poller.
+    /// Returns whether its publish time or value changed.
+    pub fn insert(&self, feed: &Feed, data: PriceData) -> bool {
+        self.store(feed.id.clone(), data)
+    }
+
+    /// The most recent price stored for `feed`, however old, marked as cached.
+    pub fn latest(&self, feed: &Feed) -> Option<PriceData> {
+        let data = self.entries.lock().unwrap().get(&feed.id)?.data.clone();
+        Some(cached(Lookup::Fresh(data)))
+    }
+
+    fn lookup(&self, feed: &Feed) -> Lookup {
+        let ttl = feed.cache_ttl.unwrap_or(self.ttl);
+        let lookup = match self.entries.lock().unwrap().get(&feed.id) {
+            Some(entry) if entry.fetched_at.elapsed() < ttl => Lookup::Fresh(entry.data.clone()),
+            Some(entry) if entry.fetched_at.elapsed() < ttl + self.stale_while_revalidate => {
+                Lookup::Stale(entry.data.clone())
+            }
+            _ => Lookup::Miss,
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.record_cache(match lookup {
+                Lookup::Fresh(_) => CacheResult::Hit,
+                Lookup::Stale(_) => CacheResult::Stale,
+                Lookup::Miss => CacheResult::Miss,
+            });
+        }
+        lookup
+    }
+
+    /// The most recent price stored for every feed, as fetched (not marked
+    /// as cached).
+    pub fn snapshot(&self) -> Vec<PriceData> {
+        self.entries.lock().unwrap().values().map(|entry| entry.data.clone()).collect()
+    }
+
+    /// Wraps `fetch` so that it stores its result and clears its in-flight
+    /// entry, and spawns it so it completes even if every waiter goes away.
+    fn start(self: &Arc<Self>, id: &str, fetch: BoxFuture<'static, Result<PriceData, String>>) -> InFlight {
+        let cache = self.clone();
+        let id = id.to_string();
+        let pending = async move {
+            let result = fetch.await;
+            if let Ok(data) = &result {
+                cache.store(id.clone(), data.clone());
+            }
+            cache.in_flight.lock().unwrap().remove(&id);
+            result
+        }
+        .boxed()
+        .shared();
+
+        tokio::spawn(pending.clone());
+        pending
+    }
+
+    fn store(&self, id: String, data: PriceData) -> bool {
+        let previous = self
+            .entries
+            .lock()
+            .unwrap()
+            .insert(id, Entry { data: data.clone(), fetched_at: Instant::now() });
+        let changed = previous.is_none_or(|entry| {
+            entry.data.publish_time != data.publish_time
+                || entry.data.exact.price != data.exact.price
+                || entry.data.exact.expo != data.exact.expo
+        });
+        if changed {
+            let mut recent = self.recent.lock().unwrap();
+            if recent.capacity > 0 {
+                if recent.updates.len() == recent.capacity
+                    && let Some(evicted) = recent.updates.pop_front()
+                {
+                    recent.complete_after = recent.complete_after.max(evicted.last_updated);
+                }
+                recent.updates.push_back(data.clone());
+            } else {
+                recent.complete_after = recent.complete_after.max(data.last_updated);
+            }
+            // No receivers is not an error: nobody is streaming.
+            let _ = self.updates.send(data);
+        }
+        changed
+    }
+}
+
+/// Marks a price served from the cache in its `source`.
+fn cached(lookup: Lookup) -> PriceData {
+    let (mut data, label) = match lookup {
+        Lookup::Fresh(data) => (data, "cached"),
+        Lookup::Stale(data) => (data, "cached, revalidating"),
+        Lookup::Miss => unreachable!("cache miss has no data"),
+    };
+    info!("Serving {} from cache ({})", data.symbol, label);
+    data.source = format!("{} ({})", data.source, label);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn cache(ttl_secs: u64, swr_secs: u64, replay: usize) -> Arc<PriceCache> {
+        Arc::new(PriceCache::new(Duration::from_secs(ttl_secs), Duration::from_secs(swr_secs), 16, replay))
+    }
+
+    /// A fetch that counts its calls and returns `mantissa` after `delay`.
+    fn fetch(
+        calls: &Arc<AtomicUsize>,
+        feed: &Feed,
+        mantissa: i64,
+        delay: Duration,
+    ) -> impl FnOnce() -> BoxFuture<'static, Result<PriceData, anyhow::Error>> {
+        let calls = calls.clone();
+        let feed = feed.clone();
+        move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                tokio::time::sleep(delay).await;
+                Ok(testing::price(&feed, mantissa, 1_000, -5, 1_753_920_000 + mantissa))
+            }
+            .boxed()
+        }
+    }
+
+    fn failing_fetch(calls: &Arc<AtomicUsize>) -> impl FnOnce() -> BoxFuture<'static, Result<PriceData, anyhow::Error>> {
+        let calls = calls.clone();
+        move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Err(anyhow::anyhow!("upstream down"))
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn serves_fresh_prices_for_the_ttl() {
+        let cache = cache(10, 0, 0);
+        let feed = testing::feed("ECO.US.GDP");
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let first = cache.get(&feed, fetch(&calls, &feed, 1, Duration::ZERO)).await.unwrap();
+        assert_eq!(first.source, "test");
+
+        tokio::time::advance(Duration::from_secs(9)).await;
+        let cached = cache.get(&feed, fetch(&calls, &feed, 2, Duration::ZERO)).await.unwrap();
+        assert_eq!(cached.source, "test (cached)");
+        assert_eq!(cached.exact.price, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let refetched = cache.get(&feed, fetch(&calls, &feed, 3, Duration::ZERO)).await.unwrap();
+        assert_eq!((refetched.source.as_str(), refetched.exact.price), ("test", 3));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // A feed's own TTL overrides the cache's.
+        let mut short = testing::feed("ECO.US.GDPQ125");
+        short.cache_ttl = Some(Duration::from_secs(2));
+        cache.get(&short, fetch(&calls, &short, 4, Duration::ZERO)).await.unwrap();
+        tokio::time::advance(Duration::from_secs(2)).await;
+        cache.get(&short, fetch(&calls, &short, 5, Duration::ZERO)).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn revalidates_stale_prices_in_the_background() {
+        let cache = cache(10, 20, 0);
+        let feed = testing::feed("ECO.US.GDP");
+        let calls = Arc::new(AtomicUsize::new(0));
+        cache.get(&feed, fetch(&calls, &feed, 1, Duration::ZERO)).await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(15)).await;
+        // Served at once while one slow refresh runs.
+        let stale = cache.get(&feed, fetch(&calls, &feed, 2, Duration::from_secs(5))).await.unwrap();
+        assert_eq!((stale.source.as_str(), stale.exact.price), ("test (cached, revalidating)", 1));
+        let again = cache.get(&feed, fetch(&calls, &feed, 3, Duration::ZERO)).await.unwrap();
+        assert_eq!(again.exact.price, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        let refreshed = cache.get(&feed, fetch(&calls, &feed, 4, Duration::ZERO)).await.unwrap();
+        assert_eq!((refreshed.source.as_str(), refreshed.exact.price), ("test (cached)", 2));
+
+        // Past TTL + stale-while-revalidate the request waits for a fetch.
+        tokio::time::advance(Duration::from_secs(31)).await;
+        let missed = cache.get(&feed, fetch(&calls, &feed, 5, Duration::ZERO)).await.unwrap();
+        assert_eq!((missed.source.as_str(), missed.exact.price), ("test", 5));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shares_one_fetch_between_concurrent_requests() {
+        let cache = cache(10, 0, 0);
+        let feed = testing::feed("ECO.US.GDP");
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let (first, second) = tokio::join!(
+            cache.get(&feed, fetch(&calls, &feed, 1, Duration::from_secs(1))),
+            cache.get(&feed, fetch(&calls, &feed, 2, Duration::from_secs(1))),
+        );
+        assert_eq!(first.unwrap().exact.price, 1);
+        assert_eq!(second.unwrap().exact.price, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A batch waits on the fetch already running instead of repeating it.
+        let other = testing::feed("ECO.US.GDPQ125");
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let single = cache.get(&feed, fetch(&calls, &feed, 3, Duration::from_secs(1)));
+        let batch_calls = calls.clone();
+        let feeds = [&feed, &other];
+        let batch = cache.get_many(&feeds, Instant::now() + Duration::from_secs(5), move |feeds| {
+            batch_calls.fetch_add(1, Ordering::SeqCst);
+            let symbols: Vec<String> = feeds.iter().map(|feed| feed.symbol.clone()).collect();
+            assert_eq!(symbols, ["ECO.US.GDPQ125"]);
+            async move { feeds.iter().map(|feed| Ok(testing::price(feed, 4, 1_000, -5, 1))).collect() }.boxed()
+        });
+        let (single, batch) = tokio::join!(single, batch);
+        assert_eq!(single.unwrap().exact.price, 3);
+        let prices: Vec<i64> = batch.into_iter().map(|result| result.unwrap().exact.price).collect();
+        assert_eq!(prices, [3, 4]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_cache_failures() {
+        let cache = cache(10, 0, 0);
+        let feed = testing::feed("ECO.US.GDP");
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let (first, second) = tokio::join!(cache.get(&feed, failing_fetch(&calls)), cache.get(&feed, failing_fetch(&calls)));
+        assert_eq!(first.err().unwrap().to_string(), "upstream down");
+        assert_eq!(second.err().unwrap().to_string(), "upstream down");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        cache.get(&feed, fetch(&calls, &feed, 1, Duration::ZERO)).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(cache.latest(&feed).is_some());
+    }
+
+    #[tokio::test]
+    async fn replays_recent_changes() {
+        let cache = cache(10, 0, 2);
+        let feed = testing::feed("ECO.US.GDP");
+        let mut updates = cache.subscribe();
+        let start = chrono::Utc::now() + chrono::Duration::hours(1);
+        let at = |mantissa: i64, publish_time: i64, seconds: i64| {
+            let mut data = testing::price(&feed, mantissa, 1_000, -5, publish_time);
+            data.last_updated = start + chrono::Duration::seconds(seconds);
+            data
+        };
+
+        assert!(cache.insert(&feed, at(1, 100, 0)));
+        // Same publish time and price: not a change.
+        assert!(!cache.insert(&feed, at(1, 100, 1)));
+        assert!(cache.insert(&feed, at(2, 100, 2)));
+        assert!(cache.insert(&feed, at(2, 200, 3)));
+
+        let broadcast: Vec<i64> = (0..3).map(|_| updates.try_recv().unwrap().publish_time).collect();
+        assert_eq!(broadcast, [100, 100, 200]);
+        assert!(updates.try_recv().is_err());
+
+        // The first change was evicted, so only later resumes are complete.
+        assert!(cache.changes_since(start).is_none());
+        let replayed: Vec<(i64, i64)> = cache
+            .changes_since(start + chrono::Duration::seconds(1))
+            .unwrap()
+            .iter()
+            .map(|data| (data.exact.price, data.publish_time))
+            .collect();
+        assert_eq!(replayed, [(2, 100), (2, 200)]);
+        assert_eq!(cache.changes_since(start + chrono::Duration::seconds(3)).unwrap().len(), 1);
+        assert!(cache.changes_since(start + chrono::Duration::seconds(4)).unwrap().is_empty());
+        // Before the cache existed nothing is known.
+        assert!(cache.changes_since(start - chrono::Duration::hours(2)).is_none());
+    }
+}
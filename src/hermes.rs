@@ -0,0 +1,353 @@
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Client for the Pyth Hermes v2 REST API.
+#[derive(Clone)]
+pub struct HermesClient {
+    http_client: Client,
+    base_url: String,
+    timeout: Duration,
+}
+
+/// Response of `/v2/updates/price/*`: the accumulator update for every
+/// requested feed plus, when requested, the decoded prices.
+#[derive(Deserialize)]
+pub struct PriceUpdates {
+    pub binary: BinaryUpdate,
+    #[serde(default)]
+    pub parsed: Option<Vec<ParsedPriceUpdate>>,
+}
+
+#[derive(Deserialize)]
+pub struct BinaryUpdate {
+    pub encoding: String,
+    pub data: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ParsedPriceUpdate {
+    pub id: String,
+    pub price: RpcPrice,
+    pub ema_price: RpcPrice,
+    #[serde(default)]
+    pub metadata: PriceMetadata,
+}
+
+#[derive(Deserialize)]
+pub struct RpcPrice {
+    pub price: String,
+    pub conf: String,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+#[derive(Default, Deserialize)]
+pub struct PriceMetadata {
+    pub slot: Option<u64>,
+    pub prev_publish_time: Option<i64>,
+}
+
+impl BinaryUpdate {
+    /// Decodes the accumulator update messages into raw bytes.
+    pub fn decode(&self) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+        if self.encoding != "hex" {
+            return Err(anyhow::anyhow!("Unsupported Hermes update encoding {}", self.encoding));
+        }
+        self.data
+            .iter()
+            .map(|data| hex::decode(data).map_err(|e| anyhow::anyhow!("Invalid Hermes update data: {}", e)))
+            .collect()
+    }
+}
+
+impl HermesClient {
+    pub fn new(http_client: Client, base_url: &str, timeout: Duration) -> Self {
+        Self {
+            http_client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            timeout,
+        }
+    }
+
+    /// Latest price updates for the given feed IDs. Unknown IDs are ignored
+    /// by Hermes rather than failing the whole request.
+    pub async fn latest_price_updates(&self, ids: &[&str]) -> Result<PriceUpdates, anyhow::Error> {
+        self.get_price_updates("/v2/updates/price/latest", ids)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Pyth Hermes API failed: 404 Not Found"))
+    }
+
+    /// The first price updates published at or after `publish_time` (unix
+    /// seconds), or `None` when Hermes has none for these feeds.
+    pub async fn price_updates_at(&self, publish_time: i64, ids: &[&str]) -> Result<Option<PriceUpdates>, anyhow::Error> {
+        self.get_price_updates(&format!("/v2/updates/price/{}", publish_time), ids).await
+    }
+
+    /// Opens Hermes' Server-Sent Events stream of price updates for the
+    /// given feed IDs. Only the connection is bounded by the timeout; the
+    /// stream stays open until Hermes or the caller closes it.
+    pub async fn stream_price_updates(&self, ids: &[&str]) -> Result<PriceStream, anyhow::Error> {
+        let api_url = self.price_updates_url("/v2/updates/price/stream", ids)?;
+        let response = tokio::time::timeout(self.timeout, self.http_client.get(api_url).send())
+            .await
+            .map_err(|_| anyhow::anyhow!("Pyth Hermes stream did not answer within {:?}", self.timeout))?
+            // The URL lists every feed ID; leave it out of the error.
+            .map_err(|e| anyhow::anyhow!("Pyth Hermes stream connection failed: {}", e.without_url()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("Pyth Hermes stream failed: {} {}", status, body.trim()));
+        }
+        Ok(PriceStream { response, buffer: Vec::new() })
+    }
+
+    fn price_updates_url(&self, path: &str, ids: &[&str]) -> Result<reqwest::Url, anyhow::Error> {
+        let mut api_url = reqwest::Url::parse(&format!("{}{}", self.base_url, path))
+            .map_err(|e| anyhow::anyhow!("Invalid Hermes URL: {}", e))?;
+        {
+            let mut query = api_url.query_pairs_mut();
+            for id in ids {
+                query.append_pair("ids[]", id);
+            }
+            query
+                .append_pair("encoding", "hex")
+                .append_pair("parsed", "true")
+                .append_pair("ignore_invalid_price_ids", "true");
+        }
+        Ok(api_url)
+    }
+
+    /// `None` when Hermes answers 404, which it does for IDs or times it
+    /// has no updates for.
+    async fn get_price_updates(&self, path: &str, ids: &[&str]) -> Result<Option<PriceUpdates>, anyhow::Error> {
+        let api_url = self.price_updates_url(path, ids)?;
+
+        let response = self.http_client
+            .get(api_url)
+            .timeout(self.timeout)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("Pyth Hermes API failed: {} {}", status, body.trim()));
+        }
+
+        let response_text = response.text().await?;
+
+        serde_json::from_str(&response_text)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Failed to parse JSON: {} - Response: {}", e, response_text))
+    }
+}
+
+/// An open `/v2/updates/price/stream` connection.
+pub struct PriceStream {
+    response: reqwest::Response,
+    /// Received bytes not yet parsed into events, with `\r` removed.
+    buffer: Vec<u8>,
+}
+
+impl PriceStream {
+    /// The next update, or `None` once Hermes closes the stream.
+    pub async fn next(&mut self) -> Result<Option<PriceUpdates>, anyhow::Error> {
+        loop {
+            while let Some(data) = self.take_event() {
+                // Events without data, such as comments, carry no update.
+                if data.is_empty() {
+                    continue;
+                }
+                return serde_json::from_str(&data)
+                    .map(Some)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse Hermes stream event: {} - Event: {}", e, data));
+            }
+
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend(chunk.iter().filter(|&&byte| byte != b'\r')),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Removes the first complete event from the buffer and returns its
+    /// `data` lines joined by newlines.
+    fn take_event(&mut self) -> Option<String> {
+        let end = self.buffer.windows(2).position(|window| window == b"\n\n")?;
+        let event: Vec<u8> = self.buffer.drain(..end + 2).collect();
+        let event = String::from_utf8_lossy(&event);
+        let data: Vec<&str> = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+        Some(data.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn parsed_price(updates: &PriceUpdates) -> (&str, i64) {
+        let parsed = &updates.parsed.as_ref().unwrap()[0];
+        (parsed.price.price.as_str(), parsed.price.publish_time)
+    }
+
+    #[tokio::test]
+    async fn parses_stream_events() {
+        let feed = testing::feed("ECO.US.GDP");
+        let first = format!("data: {}\r\n\r\n", testing::price_update_json(&feed, 280_000, 1_000));
+        let (head, tail) = first.split_at(first.len() / 2);
+        // Split inside the CRLF that ends the event.
+        let (tail, crlf) = tail.split_at(tail.len() - 3);
+
+        // One JSON document over several `data:` lines, with a comment,
+        // an event type and an ID in between.
+        let second = testing::price_update_json(&feed, 290_000, 2_000);
+        let split = second.find("\"parsed\"").unwrap();
+        let multi_line = format!(
+            "event: price_update\ndata: {}\n: keep-alive\nid: 2\ndata:{}\n\n",
+            &second[..split],
+            &second[split..]
+        );
+
+        let url = testing::serve_sse(vec![vec![
+            b": connected\r\n\r\n".to_vec(),
+            head.as_bytes().to_vec(),
+            tail.as_bytes().to_vec(),
+            crlf.as_bytes().to_vec(),
+            b":\n\n".to_vec(),
+            multi_line.into_bytes(),
+        ]])
+        .await;
+
+        let client = HermesClient::new(Client::new(), &url, Duration::from_secs(5));
+        let mut stream = client.stream_price_updates(&[&feed.id]).await.unwrap();
+        assert_eq!(parsed_price(&stream.next().await.unwrap().unwrap()), ("280000", 1_000));
+        assert_eq!(parsed_price(&stream.next().await.unwrap().unwrap()), ("290000", 2_000));
+        assert!(stream.next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reports_malformed_events() {
+        let feed = testing::feed("ECO.US.GDP");
+        let url = testing::serve_sse(vec![vec![b"data: {\"binary\": \n\n".to_vec()]]).await;
+        let client = HermesClient::new(Client::new(), &url, Duration::from_secs(5));
+        let mut stream = client.stream_price_updates(&[&feed.id]).await.unwrap();
+        let error = stream.next().await.err().expect("malformed event was parsed");
+        assert!(error.to_string().contains("Failed to parse Hermes stream event"), "{}", error);
+    }
+
+    /// A `/v2/updates/price/*` response as Hermes returns it, with the
+    /// fields this client ignores such as `proof_available_time`.
+    fn hermes_response(id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "binary": { "encoding": "hex", "data": ["504e415501000000", "504e4155"] },
+            "parsed": [{
+                "id": id,
+                "price": { "price": "280000", "conf": "1000", "expo": -5, "publish_time": 1_753_920_000 },
+                "ema_price": { "price": "279000", "conf": "1100", "expo": -5, "publish_time": 1_753_920_000 },
+                "metadata": { "slot": 301_234_567, "proof_available_time": 1_753_920_001, "prev_publish_time": 1_745_971_200 },
+            }],
+        })
+    }
+
+    async fn client(router: axum::Router) -> HermesClient {
+        let url = testing::serve(router).await;
+        HermesClient::new(Client::new(), &format!("{}/", url), Duration::from_secs(5))
+    }
+
+    #[tokio::test]
+    async fn parses_latest_price_updates() {
+        use axum::{extract::RawQuery, routing::get, Json, Router};
+
+        let feed = testing::feed("ECO.US.GDP");
+        let id = feed.id.trim_start_matches("0x").to_string();
+        let router = Router::new().route(
+            "/v2/updates/price/latest",
+            get(move |RawQuery(query): RawQuery| async move {
+                let query = query.unwrap_or_default();
+                assert!(query.contains("encoding=hex") && query.contains("parsed=true"), "{}", query);
+                assert!(query.contains("ignore_invalid_price_ids=true"), "{}", query);
+                let ids = testing::hermes_ids(&query);
+                Json(hermes_response(ids[0].trim_start_matches("0x")))
+            }),
+        );
+
+        let updates = client(router).await.latest_price_updates(&[&feed.id]).await.unwrap();
+        let parsed = &updates.parsed.as_ref().unwrap()[0];
+        assert_eq!(parsed.id, id);
+        assert_eq!((parsed.price.price.as_str(), parsed.price.conf.as_str(), parsed.price.expo), ("280000", "1000", -5));
+        assert_eq!(parsed.price.publish_time, 1_753_920_000);
+        assert_eq!((parsed.ema_price.price.as_str(), parsed.ema_price.conf.as_str()), ("279000", "1100"));
+        assert_eq!(parsed.metadata.slot, Some(301_234_567));
+        assert_eq!(parsed.metadata.prev_publish_time, Some(1_745_971_200));
+        assert_eq!(updates.binary.decode().unwrap(), [b"PNAU\x01\x00\x00\x00".to_vec(), b"PNAU".to_vec()]);
+    }
+
+    #[test]
+    fn parses_updates_without_optional_blocks() {
+        let updates: PriceUpdates = serde_json::from_value(serde_json::json!({
+            "binary": { "encoding": "hex", "data": [] },
+        }))
+        .unwrap();
+        assert!(updates.parsed.is_none());
+
+        let mut response = hermes_response("ab");
+        response["parsed"][0].as_object_mut().unwrap().remove("metadata");
+        let updates: PriceUpdates = serde_json::from_value(response).unwrap();
+        let metadata = &updates.parsed.as_ref().unwrap()[0].metadata;
+        assert_eq!((metadata.slot, metadata.prev_publish_time), (None, None));
+    }
+
+    #[tokio::test]
+    async fn treats_404_as_no_updates() {
+        use axum::{http::StatusCode, routing::get, Json, Router};
+
+        let router = Router::new()
+            .route("/v2/updates/price/1700000000", get(|| async { (StatusCode::NOT_FOUND, "Price ids not found") }))
+            .route("/v2/updates/price/1800000000", get(|| async { Json(hermes_response("ab")) }))
+            .route("/v2/updates/price/latest", get(|| async { (StatusCode::NOT_FOUND, "Price ids not found") }));
+        let client = client(router).await;
+
+        assert!(client.price_updates_at(1_700_000_000, &["0xab"]).await.unwrap().is_none());
+        let updates = client.price_updates_at(1_800_000_000, &["0xab"]).await.unwrap().unwrap();
+        assert_eq!(updates.parsed.unwrap()[0].id, "ab");
+        // The latest prices always exist, so a 404 there is an error.
+        let error = client.latest_price_updates(&["0xab"]).await.err().unwrap();
+        assert_eq!(error.to_string(), "Pyth Hermes API failed: 404 Not Found");
+    }
+
+    #[tokio::test]
+    async fn reports_failed_requests() {
+        use axum::{http::StatusCode, routing::get, Router};
+
+        let unavailable = Router::new()
+            .route("/v2/updates/price/latest", get(|| async { (StatusCode::SERVICE_UNAVAILABLE, "overloaded\n") }));
+        let error = client(unavailable).await.latest_price_updates(&["0xab"]).await.err().unwrap();
+        assert_eq!(error.to_string(), "Pyth Hermes API failed: 503 Service Unavailable overloaded");
+
+        let malformed = Router::new().route("/v2/updates/price/latest", get(|| async { "{\"binary\": 1}" }));
+        let error = client(malformed).await.latest_price_updates(&["0xab"]).await.err().unwrap();
+        assert!(error.to_string().starts_with("Failed to parse JSON:"), "{}", error);
+        assert!(error.to_string().ends_with("Response: {\"binary\": 1}"), "{}", error);
+    }
+
+    #[test]
+    fn decodes_only_hex_updates() {
+        let update = |encoding: &str, data: &str| BinaryUpdate { encoding: encoding.to_string(), data: vec![data.to_string()] };
+
+        assert_eq!(update("hex", "00ff").decode().unwrap(), [vec![0x00, 0xff]]);
+        let error = update("base64", "AP8=").decode().unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported Hermes update encoding base64");
+        let error = update("hex", "0g").decode().unwrap_err();
+        assert!(error.to_string().starts_with("Invalid Hermes update data:"), "{}", error);
+    }
+}
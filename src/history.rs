@@ -0,0 +1,263 @@
+//! Query parameters and response of `/gdp/{symbol}/history`.
+
+use crate::{
+    config::Feed,
+    price::ExactPrice,
+    storage::{HistoryQuery, Observation},
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+const DEFAULT_LIMIT: usize = 500;
+const MAX_LIMIT: usize = 10_000;
+
+/// Raw query string. Everything is parsed by hand so that bad values are
+/// reported in the usual `ApiResponse` envelope.
+#[derive(Deserialize)]
+pub struct HistoryParams {
+    /// Unix seconds, RFC 3339 timestamp or `YYYY-MM-DD` (inclusive).
+    from: Option<String>,
+    /// Unix seconds, RFC 3339 timestamp or `YYYY-MM-DD` (inclusive, whole day).
+    to: Option<String>,
+    limit: Option<String>,
+    /// Downsampling bucket: `hour`, `day`, `week`, or a count with an
+    /// `s`/`m`/`h`/`d`/`w` suffix such as `6h`.
+    interval: Option<String>,
+}
+
+impl HistoryParams {
+    pub fn to_query(&self, feed: &Feed) -> Result<HistoryQuery, anyhow::Error> {
+        let from = self.from.as_deref().map(|raw| parse_time(raw, false)).transpose()?;
+        let to = self.to.as_deref().map(|raw| parse_time(raw, true)).transpose()?;
+        if let (Some(from), Some(to)) = (from, to)
+            && from > to
+        {
+            return Err(anyhow::anyhow!("`from` is after `to`"));
+        }
+
+        let limit = match self.limit.as_deref() {
+            Some(raw) => raw
+                .parse::<usize>()
+                .ok()
+                .filter(|limit| (1..=MAX_LIMIT).contains(limit))
+                .ok_or_else(|| anyhow::anyhow!("`limit` must be between 1 and {}", MAX_LIMIT))?,
+            None => DEFAULT_LIMIT,
+        };
+
+        Ok(HistoryQuery {
+            feed_id: feed.id.clone(),
+            from: from.unwrap_or(0),
+            to: to.unwrap_or(i64::MAX),
+            bucket_secs: self.interval.as_deref().map(parse_interval).transpose()?.unwrap_or(1),
+            limit,
+        })
+    }
+}
+
+/// Time series of one feed, oldest point first.
+#[derive(Serialize)]
+pub struct History {
+    pub symbol: String,
+    pub price_feed_id: String,
+    /// Downsampling bucket in seconds; absent when every observation is returned.
+    pub interval_secs: Option<i64>,
+    pub count: usize,
+    pub points: Vec<HistoryPoint>,
+}
+
+impl History {
+    pub fn new(feed: &Feed, query: &HistoryQuery, mut observations: Vec<Observation>) -> Self {
+        observations.reverse();
+        let points: Vec<HistoryPoint> = observations
+            .into_iter()
+            .filter_map(|observation| match HistoryPoint::try_from(observation) {
+                Ok(point) => Some(point),
+                Err(e) => {
+                    warn!("Skipping stored observation of {}: {}", feed.symbol, e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            symbol: feed.symbol.clone(),
+            price_feed_id: feed.id.clone(),
+            interval_secs: (query.bucket_secs > 1).then_some(query.bucket_secs),
+            count: points.len(),
+            points,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct HistoryPoint {
+    pub publish_time: i64,
+    pub price: f64,
+    pub confidence: f64,
+    #[serde(flatten)]
+    pub exact: ExactPrice,
+    pub source: String,
+    pub fetched_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<Observation> for HistoryPoint {
+    type Error = anyhow::Error;
+
+    fn try_from(observation: Observation) -> Result<Self, Self::Error> {
+        let exact = ExactPrice::new(observation.price_mantissa, observation.confidence_mantissa, observation.exponent)?;
+        Ok(Self {
+            publish_time: observation.publish_time,
+            price: exact.price().to_f64(),
+            confidence: exact.conf().to_f64(),
+            exact,
+            source: observation.source,
+            fetched_at: observation.fetched_at,
+        })
+    }
+}
+
+/// Unix seconds, an RFC 3339 timestamp, or a date, which is read as the
+/// start of the day, or its last second when `end_of_day` is set.
+pub fn parse_time(raw: &str, end_of_day: bool) -> Result<i64, anyhow::Error> {
+    if let Ok(secs) = raw.parse::<i64>() {
+        return Ok(secs);
+    }
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(raw) {
+        return Ok(time.timestamp());
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        let time = if end_of_day { date.and_hms_opt(23, 59, 59) } else { date.and_hms_opt(0, 0, 0) };
+        return Ok(time.expect("valid time of day").and_utc().timestamp());
+    }
+    Err(anyhow::anyhow!("Invalid time {}: expected unix seconds, RFC 3339 or YYYY-MM-DD", raw))
+}
+
+pub fn parse_interval(raw: &str) -> Result<i64, anyhow::Error> {
+    let invalid = || anyhow::anyhow!("Invalid interval {}: expected e.g. `day`, `6h` or `3600s`", raw);
+    let secs = match raw {
+        "hour" => 3_600,
+        "day" => 86_400,
+        "week" => 604_800,
+        _ => {
+            let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+            let (count, unit) = raw.split_at(split);
+            let count: i64 = count.parse().map_err(|_| invalid())?;
+            let unit = match unit {
+                "" | "s" => 1,
+                "m" => 60,
+                "h" => 3_600,
+                "d" => 86_400,
+                "w" => 604_800,
+                _ => return Err(invalid()),
+            };
+            count.checked_mul(unit).ok_or_else(invalid)?
+        }
+    };
+    if secs < 1 {
+        return Err(invalid());
+    }
+    Ok(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage, testing};
+
+    #[test]
+    fn parses_times() {
+        for (raw, end_of_day, expected) in [
+            ("0", false, 0),
+            ("1753920000", false, 1_753_920_000),
+            ("-86400", false, -86_400),
+            ("2025-07-31T00:00:00Z", false, 1_753_920_000),
+            ("2025-07-31T02:00:00+02:00", true, 1_753_920_000),
+            ("2025-07-31T00:00:00.999Z", false, 1_753_920_000),
+            ("2025-07-31", false, 1_753_920_000),
+            ("2025-07-31", true, 1_754_006_399),
+            ("1970-01-01", true, 86_399),
+        ] {
+            assert_eq!(parse_time(raw, end_of_day).unwrap(), expected, "{}", raw);
+        }
+        for raw in ["", "yesterday", "2025-13-01", "2025-07-31 00:00:00", "31/07/2025", "1e9"] {
+            assert!(parse_time(raw, false).is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn parses_intervals() {
+        for (raw, expected) in [
+            ("hour", 3_600),
+            ("day", 86_400),
+            ("week", 604_800),
+            ("1", 1),
+            ("3600s", 3_600),
+            ("15m", 900),
+            ("6h", 21_600),
+            ("2d", 172_800),
+            ("1w", 604_800),
+            ("99999999999w", 99_999_999_999 * 604_800),
+        ] {
+            assert_eq!(parse_interval(raw).unwrap(), expected, "{}", raw);
+        }
+        for raw in ["", "0", "0s", "0w", "-1h", "h", "6x", "6 h", "Day", "99999999999999w", "9223372036854775808"] {
+            assert!(parse_interval(raw).is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn builds_queries() {
+        let feed = testing::feed("ECO.US.GDP");
+        let params = |from: Option<&str>, to: Option<&str>, limit: Option<&str>, interval: Option<&str>| HistoryParams {
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+            limit: limit.map(str::to_string),
+            interval: interval.map(str::to_string),
+        };
+
+        let query = params(None, None, None, None).to_query(&feed).unwrap();
+        assert_eq!((query.from, query.to, query.bucket_secs, query.limit), (0, i64::MAX, 1, DEFAULT_LIMIT));
+
+        let query = params(Some("2025-07-31"), Some("2025-07-31"), Some("10"), Some("day")).to_query(&feed).unwrap();
+        assert_eq!((query.from, query.to, query.bucket_secs, query.limit), (1_753_920_000, 1_754_006_399, 86_400, 10));
+        assert_eq!(query.feed_id, feed.id);
+
+        assert!(params(Some("2025-08-01"), Some("2025-07-31"), None, None).to_query(&feed).is_err());
+        assert!(params(None, None, Some("0"), None).to_query(&feed).is_err());
+        assert!(params(None, None, Some("10001"), None).to_query(&feed).is_err());
+        assert!(params(None, None, None, Some("0s")).to_query(&feed).is_err());
+    }
+
+    #[tokio::test]
+    async fn keeps_last_point_per_bucket() {
+        let feed = testing::feed("ECO.US.GDP");
+        let store = storage::open("sqlite::memory:").await.unwrap();
+        let day = 86_400;
+        // Three points on day 0, none on day 1, two on day 2, one on day 3.
+        let times = [10, 500, day - 1, 2 * day, 2 * day + 7_200, 3 * day + 1];
+        let observations: Vec<Observation> = times
+            .iter()
+            .enumerate()
+            .map(|(i, &time)| Observation::new(&feed, &testing::price(&feed, 280_000 + i as i64, 1_000, -5, time)))
+            .collect();
+        assert_eq!(store.record(&observations).await.unwrap(), times.len());
+
+        let query = HistoryQuery { feed_id: feed.id.clone(), from: 0, to: i64::MAX, bucket_secs: day, limit: 100 };
+        let history = History::new(&feed, &query, store.history(&query).await.unwrap());
+        let points: Vec<(i64, i64)> = history.points.iter().map(|point| (point.publish_time, point.exact.price)).collect();
+        assert_eq!(points, [(day - 1, 280_002), (2 * day + 7_200, 280_004), (3 * day + 1, 280_005)]);
+        assert_eq!((history.count, history.interval_secs), (3, Some(day)));
+
+        // `limit` keeps the most recent buckets, still oldest first.
+        let query = HistoryQuery { limit: 2, ..query };
+        let history = History::new(&feed, &query, store.history(&query).await.unwrap());
+        let times: Vec<i64> = history.points.iter().map(|point| point.publish_time).collect();
+        assert_eq!(times, [2 * day + 7_200, 3 * day + 1]);
+
+        // Without downsampling every observation in range is returned.
+        let query = HistoryQuery { from: 500, to: 2 * day, bucket_secs: 1, limit: 100, ..query };
+        let history = History::new(&feed, &query, store.history(&query).await.unwrap());
+        let times: Vec<i64> = history.points.iter().map(|point| point.publish_time).collect();
+        assert_eq!(times, [500, day - 1, 2 * day]);
+        assert_eq!(history.interval_secs, None);
+    }
+}
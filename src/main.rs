@@ -1,21 +1,59 @@
+mod accumulator;
+mod analytics;
+mod backfill;
+mod cache;
+mod config;
+mod hermes;
+mod history;
+mod metrics;
+mod oracle;
+mod poller;
+mod price;
+mod quality;
+mod retry;
+mod sources;
+mod sse;
+mod storage;
+mod subscriber;
+mod symbol;
+#[cfg(test)]
+mod testing;
+mod watcher;
+mod websocket;
+
 use axum::{
-    extract::State,
+    extract::{ws::WebSocketUpgrade, MatchedPath, Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
     response::Json,
     routing::get,
     Router,
-    http::StatusCode,
-    response::Html,
+    response::{sse::{KeepAlive, Sse}, Html, Response},
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use futures::FutureExt;
+use accumulator::{GuardianSet, UpdateVerifier};
+use analytics::{Analytics, QuarterValue};
+use backfill::{BackfillRequest, BackfillStatus, Backfiller};
+use cache::PriceCache;
+use config::{Config, Feed, FeedRegistry, QualityAction, UpstreamArgs, Upstreams, VerificationMode};
+use hermes::HermesClient;
+use history::{History, HistoryParams};
+use metrics::Metrics;
+use poller::{PollSchedule, Poller, PollerStatus};
+use quality::QualityPolicy;
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
-use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+use sources::{HermesSource, PriceSource, SolanaSource, SonicSource, SourceChain};
+use storage::{HistoryQuery, ObservationStore};
+use subscriber::{Subscriber, SubscriberStatus};
+use watcher::{Watcher, WatcherStatus};
+use std::{convert::Infallible, net::SocketAddr, path::PathBuf, str::FromStr, sync::{Arc, RwLock}, time::Duration};
+use subtle::ConstantTimeEq;
 use tower_http::cors::CorsLayer;
 use tracing::{info, warn, error};
 use reqwest::Client;
-use pyth_sdk_solana::state::PriceFeed;
-use borsh::BorshDeserialize;
 
 #[derive(Parser)]
 #[command(name = "sonic-pyth-gdp")]
@@ -23,24 +61,135 @@ use borsh::BorshDeserialize;
 struct Args {
     #[arg(long, default_value = "3000")]
     port: u16,
+
+    /// Path to the TOML config file (feed registry and upstreams)
+    #[arg(long, env = "GDP_CONFIG", default_value = "config/default.toml")]
+    config: PathBuf,
+
+    /// Comma-separated source order, overriding `[sources].order`
+    #[arg(long, env = "PRICE_SOURCES", value_delimiter = ',')]
+    sources: Option<Vec<String>>,
+
+    /// Comma-separated sources to disable, overriding `[sources].disabled`
+    #[arg(long, env = "DISABLED_SOURCES", value_delimiter = ',')]
+    disable_sources: Option<Vec<String>>,
+
+    /// Maximum in-flight upstream requests per source, overriding `[fetch].concurrency`
+    #[arg(long, env = "FETCH_CONCURRENCY")]
+    fetch_concurrency: Option<usize>,
+
+    /// Deadline for multi-feed requests in seconds, overriding `[fetch].deadline_secs`
+    #[arg(long, env = "FETCH_DEADLINE_SECS")]
+    fetch_deadline_secs: Option<u64>,
+
+    /// Hermes update verification mode, overriding `[verification].mode`
+    #[arg(long, env = "HERMES_VERIFY", value_enum)]
+    verify_updates: Option<VerificationMode>,
+
+    /// What to do with stale or low-confidence prices, overriding `[quality].action`
+    #[arg(long, env = "QUALITY_ACTION", value_enum)]
+    quality_action: Option<QualityAction>,
+
+    /// Seconds a fetched price is served from the cache, overriding `[cache].ttl_secs`
+    #[arg(long, env = "CACHE_TTL_SECS")]
+    cache_ttl_secs: Option<u64>,
+
+    /// Refresh every feed in the background and answer from memory, overriding `[poller].enabled`
+    #[arg(long, env = "POLLER_ENABLED")]
+    poller: Option<bool>,
+
+    /// Seconds between poller runs, overriding `[poller].interval_secs`
+    #[arg(long, env = "POLL_INTERVAL_SECS")]
+    poll_interval_secs: Option<u64>,
+
+    /// Follow Hermes' streaming price updates, overriding `[subscriber].enabled`
+    #[arg(long, env = "HERMES_SUBSCRIBER")]
+    hermes_subscriber: Option<bool>,
+
+    /// Watch the on-chain feed accounts over RPC PubSub, overriding `[watcher].enabled`
+    #[arg(long, env = "ACCOUNT_WATCHER")]
+    account_watcher: Option<bool>,
+
+    /// Database observed prices are recorded in, overriding `[storage].url`
+    #[arg(long, env = "DATABASE_URL")]
+    database_url: Option<String>,
+
+    /// Bearer token required by the `/admin` endpoints, which are disabled without one
+    #[arg(long, env = "ADMIN_TOKEN")]
+    admin_token: Option<String>,
+
+    #[command(flatten)]
+    upstreams: UpstreamArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Load historical prices from Hermes into the observation store, then exit
+    Backfill(BackfillRequest),
 }
 
 #[derive(Clone)]
 struct AppState {
     sonic_rpc_client: Arc<RpcClient>,
-    solana_fallback_client: Arc<RpcClient>,
-    http_client: Client,
+    sources: Arc<SourceChain>,
+    registry: Arc<FeedRegistry>,
+    upstreams: Arc<Upstreams>,
+    cache: Arc<PriceCache>,
+    fetch_deadline: Duration,
+    /// Set when the background poller is on; prices are then served from memory only.
+    poller: Option<Arc<RwLock<PollerStatus>>>,
+    /// Set when the Hermes subscriber is on.
+    subscriber: Option<Arc<RwLock<SubscriberStatus>>>,
+    /// Set when the account watcher is on.
+    watcher: Option<Arc<RwLock<WatcherStatus>>>,
+    /// Set when observations are recorded; backs `/gdp/{symbol}/history`.
+    store: Option<Arc<dyn ObservationStore>>,
+    /// Set when observations are recorded; runs `/admin/backfill`.
+    backfiller: Option<Arc<Backfiller>>,
+    admin_token: Option<Arc<str>>,
+    /// Heartbeat and backpressure limits of `/ws` connections; the heartbeat
+    /// is also the keep-alive interval of `/gdp/stream`.
+    stream_limits: websocket::Limits,
+    /// Most events replayed to a resuming `/gdp/stream` client.
+    max_replay: usize,
+    metrics: Arc<Metrics>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PriceData {
     pub symbol: String,
+    /// `exact` as a floating-point number, for display.
     pub price: f64,
     pub confidence: f64,
+    #[serde(flatten)]
+    pub exact: price::ExactPrice,
     pub publish_time: i64,
     pub price_feed_id: String,
     pub last_updated: chrono::DateTime<chrono::Utc>,
     pub source: String,
+    pub description: String,
+    pub unit: String,
+    pub ema_price: Option<f64>,
+    pub ema_confidence: Option<f64>,
+    pub slot: Option<u64>,
+    pub prev_publish_time: Option<i64>,
+    /// Hex-encoded Hermes accumulator updates, when `hermes_retain_updates` is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_data: Option<Vec<String>>,
+    /// Whether the price was checked against a Wormhole-signed accumulator update.
+    pub verified: bool,
+    /// Trading status reported by legacy on-chain price accounts.
+    pub status: Option<oracle::PriceStatus>,
+    /// `publish_time` is older than the feed's `max_age_secs`.
+    pub stale: bool,
+    /// `confidence / |price|` exceeds the feed's `max_confidence_ratio`.
+    pub low_confidence: bool,
+    /// Country, indicator and quarter parsed from `symbol`.
+    #[serde(flatten)]
+    pub metadata: symbol::SymbolInfo,
 }
 
 #[derive(Serialize)]
@@ -51,265 +200,254 @@ struct ApiResponse<T> {
     timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+/// 404 body for a symbol or feed ID that is not in the registry.
+#[derive(Serialize)]
+struct UnknownFeedResponse {
+    success: bool,
+    error: String,
+    known_symbols: Vec<String>,
+    timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+type UnknownFeed = (StatusCode, Json<UnknownFeedResponse>);
+
+/// Query of `/gdp/all`. Filters combine; feeds without a period never match
+/// `year` or `quarter`. `year` and `quarter` are parsed by hand, like
+/// `HistoryParams`, so that bad values are reported in the response envelope.
 #[derive(Deserialize)]
-struct PythPriceData {
-    id: String,
-    price: PythPriceInfo,
+struct FeedsParams {
+    /// Comma-separated symbols or feed IDs; every feed when absent.
+    ids: Option<String>,
+    year: Option<String>,
+    quarter: Option<String>,
+    country: Option<String>,
+    indicator: Option<String>,
+}
+
+/// Parsed `year` and `quarter` filters of [`FeedsParams`].
+struct PeriodFilter {
+    year: Option<i32>,
+    quarter: Option<u8>,
 }
 
+impl FeedsParams {
+    fn period(&self) -> Result<PeriodFilter, anyhow::Error> {
+        let year = match self.year.as_deref() {
+            Some(raw) => Some(raw.trim().parse().map_err(|_| anyhow::anyhow!("Invalid year {}", raw))?),
+            None => None,
+        };
+        let quarter = match self.quarter.as_deref() {
+            Some(raw) => Some(
+                raw.trim()
+                    .parse()
+                    .ok()
+                    .filter(|quarter| (1..=4).contains(quarter))
+                    .ok_or_else(|| anyhow::anyhow!("Invalid quarter {}: expected 1 to 4", raw))?,
+            ),
+            None => None,
+        };
+        Ok(PeriodFilter { year, quarter })
+    }
+
+    fn matches(&self, period: &PeriodFilter, feed: &Feed) -> bool {
+        let metadata = &feed.metadata;
+        period.year.is_none_or(|year| metadata.year == Some(year))
+            && period.quarter.is_none_or(|quarter| metadata.quarter == Some(quarter))
+            && self.country.as_ref().is_none_or(|country| metadata.country.as_ref().is_some_and(|c| c.eq_ignore_ascii_case(country)))
+            && self.indicator.as_ref().is_none_or(|indicator| metadata.indicator.as_ref().is_some_and(|i| i.eq_ignore_ascii_case(indicator)))
+    }
+}
+
+/// Query of `/gdp/analytics`.
+#[derive(Deserialize)]
+struct AnalyticsParams {
+    country: Option<String>,
+    indicator: Option<String>,
+}
+
+impl AnalyticsParams {
+    fn matches(&self, feed: &Feed) -> bool {
+        let metadata = &feed.metadata;
+        metadata.quarter.is_some()
+            && self.country.as_ref().is_none_or(|country| metadata.country.as_ref().is_some_and(|c| c.eq_ignore_ascii_case(country)))
+            && self.indicator.as_ref().is_none_or(|indicator| metadata.indicator.as_ref().is_some_and(|i| i.eq_ignore_ascii_case(indicator)))
+    }
+}
+
+/// Query of `/gdp/stream`.
 #[derive(Deserialize)]
-struct PythPriceInfo {
-    price: String,
-    conf: String,
-    expo: i32,
-    publish_time: i64,
+struct StreamParams {
+    /// Comma-separated symbols or feed IDs; every feed when absent.
+    symbols: Option<String>,
+    /// For clients that cannot set the `Last-Event-ID` header.
+    last_event_id: Option<String>,
+}
+
+/// Response for endpoints returning several feeds: the feeds that were
+/// fetched, plus the reason each of the others failed.
+#[derive(Serialize)]
+struct MultiFeedResponse {
+    success: bool,
+    data: Option<Vec<PriceData>>,
+    failed: Vec<FeedError>,
+    error: Option<String>,
+    timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct FeedError {
+    pub symbol: String,
+    pub price_feed_id: String,
+    pub error: String,
+}
+
+/// Outcome of fetching several feeds: whatever succeeded and the rest.
+struct FeedResults {
+    feeds: Vec<PriceData>,
+    failed: Vec<FeedError>,
 }
 
 impl AppState {
     async fn get_gdp_data(&self) -> Result<PriceData, anyhow::Error> {
-        let gdp_feed_id = "0x01a2d2aa5728850767d67e2f82ddc9c8e4c3bbace231461386ef9cbb16d0d36b";
-        self.get_price_feed(gdp_feed_id, "ECO.US.GDP").await
-    }
-
-    async fn get_all_gdp_feeds(&self) -> Result<Vec<PriceData>, anyhow::Error> {
-        let gdp_feeds = vec![
-            ("0x01a2d2aa5728850767d67e2f82ddc9c8e4c3bbace231461386ef9cbb16d0d36b", "ECO.US.GDP"),
-            ("0xede7d586e573bba4d9f9b598134a0b3b2848fb5633efa1aeae6cdc405ca69ec4", "ECO.US.GDPQ120"),
-            ("0x56ef63838b89bae2fa4ceac09887937e3a8ed5882372e9d3c5f5b0047be99201", "ECO.US.GDPQ121"),
-            ("0xe007fecd2fa29ae39ca6014752d08240d29ab8d26defda84151a35888dc38f72", "ECO.US.GDPQ122"),
-            ("0xed0db24f1d1e79d175b972b2824f17bfdc68adb2b9f0a95bd55001aad9636243", "ECO.US.GDPQ123"),
-            ("0x7d52b237e53197baeabb7e8840afc635e804e4c120304850657b7fe9b5abde6b", "ECO.US.GDPQ124"),
-            ("0x1f0585497d5749086d2a0d31872e3d54983ae1695fe8daa367f416778401a316", "ECO.US.GDPQ125"),
-            ("0x9fc444f6174a9cf849b65c3b30411ecd68a98136ce7e3727c62256675f7137dc", "ECO.US.GDPQ220"),
-            ("0x4e35cd9a603f66fd85f6128d91a9bc129662e64ed022a97f8d95b59f1ebf7c2e", "ECO.US.GDPQ221"),
-            ("0x01da0bbe2e2a28a45eee49168a755321baccf916377337699cf6f23207952623", "ECO.US.GDPQ222"),
-            ("0xd7c07f4fea81886c927eb995a5e007987c426c6b04255a0b9cc2063b990175b4", "ECO.US.GDPQ223"),
-            ("0x5fd1723f5ae19701812061efdfc487b923260c3186694b1f757bc19b3478c26c", "ECO.US.GDPQ224"),
-            ("0xb4ae8f99fe948c259bf1c419a8ef3c99f31b6bfbd11b2bd5e960d5ba395ce66e", "ECO.US.GDPQ225"),
-            ("0xe50aec560231dbcdd10e04bcabc4f18fa492e363b08ca9098e10d658931c3457", "ECO.US.GDPQ320"),
-            ("0x849b55be51fdcb722dc58ad870d69f73c3ea3b020fb2a8039e5b7abed62f2a86", "ECO.US.GDPQ321"),
-            ("0xf8557de55b0ae56652e6ab325eef49a3e999aba6a37f35c36ac6403713dc11a6", "ECO.US.GDPQ322"),
-            ("0xa0158865c183a07659de1f7b86dcf7f34c6b9f7982cc2f22b08c1979e3dee8ea", "ECO.US.GDPQ323"),
-            ("0x8cbea9b9b69b80ddeaa00c4ab9dc54f2b1c104f3fb732ece3b70eeb622296d76", "ECO.US.GDPQ324"),
-            ("0x9700fcc09ccf25204df7e5b87c3cfa7a780ff782c95a39fd5558cf14dbac8591", "ECO.US.GDPQ420"),
-            ("0x3a683ed0c55b14e1521313d45d93136a5adc9945fa8dca02374f8d9870d4d342", "ECO.US.GDPQ421"),
-            ("0xd584777f78a2ac22d8eebddd9cf22f9006a74b6da112e0d673bc6a6599c5f7d1", "ECO.US.GDPQ422"),
-            ("0x44aaa6f2845486fd145561c678ab8b24dfba2f685a30755ad70f3b5cf6e8b3b8", "ECO.US.GDPQ423"),
-            ("0x76bd1d211bed7f8c553f19cc2da845cab538e8b1d9e317d0455c22950fe4e32c", "ECO.US.GDPQ424"),
-        ];
-
-        let mut results = Vec::new();
-        
-        for (feed_id, symbol) in gdp_feeds {
-            info!("🔍 Fetching {} feed", symbol);
-            match self.get_price_feed(feed_id, symbol).await {
-                Ok(data) => {
-                    results.push(data);
-                }
-                Err(e) => {
-                    warn!("Failed to fetch {}: {}", symbol, e);
-                }
-            }
-        }
+        self.get_price_feed(self.registry.headline()).await
+    }
 
-        if results.is_empty() {
-            Err(anyhow::anyhow!("No GDP feeds could be fetched"))
-        } else {
-            Ok(results)
-        }
+    /// Resolves a path or query key against the registry.
+    fn resolve_feed(&self, key: &str) -> Result<&Feed, UnknownFeed> {
+        self.registry.resolve(key).ok_or_else(|| {
+            warn!("Unknown feed {}", key);
+            (
+                StatusCode::NOT_FOUND,
+                Json(UnknownFeedResponse {
+                    success: false,
+                    error: format!("Unknown feed {}", key),
+                    known_symbols: self.registry.symbols(),
+                    timestamp: chrono::Utc::now(),
+                }),
+            )
+        })
     }
 
-    async fn get_price_feed(&self, feed_id: &str, symbol: &str) -> Result<PriceData, anyhow::Error> {
-        info!("Trying Sonic SVM first for {}", symbol);
-        match self.fetch_from_sonic_via_pyth_programs(feed_id, symbol).await {
-            Ok(data) => {
-                info!("Successfully fetched {} from Sonic SVM!", symbol);
-                return Ok(data);
-            }
-            Err(e) => {
-                warn!("Sonic SVM failed for {}: {}", symbol, e);
+    /// Fetches several feeds, from the poller's values when it is on.
+    async fn get_feeds(&self, feeds: Vec<&Feed>) -> FeedResults {
+        if self.poller.is_some() {
+            let mut results = FeedResults { feeds: Vec::new(), failed: Vec::new() };
+            for feed in feeds {
+                match self.latest_polled(feed) {
+                    Ok(data) => results.feeds.push(data),
+                    Err(e) => results.failed.push(FeedError {
+                        symbol: feed.symbol.clone(),
+                        price_feed_id: feed.id.clone(),
+                        error: e.to_string(),
+                    }),
+                }
             }
+            return results;
         }
 
-    
-        info!("Falling back to Pyth Hermes API for {}", symbol);
-        match self.fetch_from_pyth_hermes(feed_id, symbol).await {
-            Ok(data) => {
-                info!("Successfully fetched {} from Pyth Hermes API!", symbol);
-                Ok(data)
-            }
-            Err(e) => {
-                error!("All sources failed for {}: {}", symbol, e);
-                Err(anyhow::anyhow!("Failed to fetch {} from all sources: {}", symbol, e))
+        info!("🔍 Fetching {} feeds", feeds.len());
+
+        let deadline = tokio::time::Instant::now() + self.fetch_deadline;
+        let mut results = FeedResults { feeds: Vec::new(), failed: Vec::new() };
+
+        let sources = self.sources.clone();
+        let fetched = self.cache.get_many(&feeds, deadline, move |feeds| {
+            async move {
+                let feeds: Vec<&Feed> = feeds.iter().collect();
+                sources.fetch_many(&feeds, deadline).await
             }
-        }
-    }
+            .boxed()
+        });
 
-    async fn fetch_from_solana_fallback(&self, feed_id: &str) -> Result<PriceData, anyhow::Error> {
-        info!("Trying to fetch GDP data from Solana RPC (Helius)");
-        
-        
-        let potential_gdp_accounts = vec![
-            "48mYDzV1JWZo93cheTbg9ikvp3PScDvoTAkWFMWtmmc9", 
-            "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG", 
-        ];
-        
-        for account_str in potential_gdp_accounts {
-            info!("Trying Solana account: {}", account_str);
-            
-            match Pubkey::from_str(account_str) {
-                Ok(pubkey) => {
-                    match self.solana_fallback_client.get_account(&pubkey) {
-                        Ok(account) => {
-                            info!("Found account on Solana!");
-                            info!("Account size: {} bytes, owner: {}", account.data.len(), account.owner);
-                            
-                            // Try to parse as Pyth price feed
-                            match PriceFeed::try_from_slice(&account.data) {
-                                Ok(price_feed) => {
-                                    let price = price_feed.get_price_unchecked();
-                                    let price_value = price.price as f64 / 10_f64.powi(price.expo);
-                                    let confidence = price.conf as f64 / 10_f64.powi(price.expo);
-
-                                    info!("Successfully parsed REAL data from Solana: {:.2} (±{:.2})", price_value, confidence);
-
-                                    return Ok(PriceData {
-                                        symbol: if account_str.contains("48mYDzV1") { "US_GDP".to_string() } else { "PRICE_FEED".to_string() },
-                                        price: price_value,
-                                        confidence,
-                                        publish_time: price.publish_time,
-                                        price_feed_id: pubkey.to_string(),
-                                        last_updated: chrono::Utc::now(),
-                                        source: "Solana RPC (Helius)".to_string(),
-                                    });
-                                }
-                                Err(e) => {
-                                    warn!("Account exists but not a valid Pyth price feed: {}", e);
-                                    continue;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            info!("Account {} not found on Solana: {}", account_str, e);
-                            continue;
-                        }
-                    }
+        for (feed, result) in feeds.iter().zip(fetched.await) {
+            match result {
+                Ok(data) => {
+                    results.feeds.push(data);
                 }
                 Err(e) => {
-                    warn!("Invalid account format {}: {}", account_str, e);
-                    continue;
+                    results.failed.push(FeedError {
+                        symbol: feed.symbol.clone(),
+                        price_feed_id: feed.id.clone(),
+                        error: e.to_string(),
+                    });
                 }
             }
         }
-        
-        Err(anyhow::anyhow!("No working GDP feed accounts found on Solana"))
+
+        results
     }
 
-    async fn fetch_from_pyth_hermes(&self, feed_id: &str, symbol: &str) -> Result<PriceData, anyhow::Error> {
-        let api_url = format!("https://hermes.pyth.network/api/latest_price_feeds?ids[]={}", feed_id);
-        
-        info!("Fetching from Pyth Hermes API: {}", api_url);
-        
-        let response = self.http_client
-            .get(&api_url)
-            .timeout(Duration::from_secs(10))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Pyth Hermes API failed: {}", response.status()));
+    async fn get_price_feed(&self, feed: &Feed) -> Result<PriceData, anyhow::Error> {
+        if self.poller.is_some() {
+            return self.latest_polled(feed);
         }
 
-        let response_text = response.text().await?;
-        
-        if response_text.contains("Failed to deserialize") || response_text.contains("Invalid") {
-            return Err(anyhow::anyhow!("Pyth API error: {}", response_text));
+        let sources = self.sources.clone();
+        let owned = feed.clone();
+        self.cache.get(feed, move || async move { sources.fetch(&owned).await }.boxed()).await
+    }
+
+    /// The last polled price, re-checked for staleness: while the poller
+    /// keeps failing it only gets older.
+    fn latest_polled(&self, feed: &Feed) -> Result<PriceData, anyhow::Error> {
+        let data = self
+            .cache
+            .latest(feed)
+            .ok_or_else(|| anyhow::anyhow!("No price for {} yet; the poller has not fetched it", feed.symbol))?;
+        self.sources
+            .recheck(feed, data)
+            .map_err(|e| anyhow::anyhow!("Last polled price of {} is no longer usable: {}", feed.symbol, e))
+    }
+
+    async fn get_history(&self, feed: &Feed, params: &HistoryParams) -> Result<History, anyhow::Error> {
+        let store = self.store.as_ref().ok_or_else(|| anyhow::anyhow!("Observation storage is disabled"))?;
+        let query = params.to_query(feed)?;
+        let observations = store.history(&query).await?;
+        Ok(History::new(feed, &query, observations))
+    }
+
+    /// Current values of the quarterly feeds matching `params`, falling back
+    /// to the last stored observation of feeds that cannot be fetched.
+    async fn get_analytics(&self, params: &AnalyticsParams) -> Analytics {
+        let feeds: Vec<&Feed> = self.registry.feeds().iter().filter(|feed| params.matches(feed)).collect();
+        if feeds.is_empty() {
+            return Analytics::new(Vec::new(), Vec::new());
         }
+        let results = self.get_feeds(feeds).await;
 
-        let api_response: Vec<PythPriceData> = serde_json::from_str(&response_text)
-            .map_err(|e| anyhow::anyhow!("Failed to parse JSON: {} - Response: {}", e, response_text))?;
-        
-        if let Some(price_data) = api_response.into_iter().next() {
-            let price_val: i64 = price_data.price.price.parse()
-                .map_err(|e| anyhow::anyhow!("Failed to parse price: {}", e))?;
-            let conf_val: u64 = price_data.price.conf.parse()
-                .map_err(|e| anyhow::anyhow!("Failed to parse confidence: {}", e))?;
-
-            let price_value = price_val as f64 / 10_f64.powi(price_data.price.expo.abs());
-            let confidence = conf_val as f64 / 10_f64.powi(price_data.price.expo.abs());
-
-            info!("Successfully parsed REAL GDP data: {:.2}% (±{:.2}%)", price_value, confidence);
-
-            Ok(PriceData {
-                symbol: symbol.to_string(),
-                price: price_value,
-                confidence,
-                publish_time: price_data.price.publish_time,
-                price_feed_id: feed_id.to_string(),
-                last_updated: chrono::Utc::now(),
-                source: "Pyth Hermes API".to_string(),
-            })
-        } else {
-            Err(anyhow::anyhow!("No GDP data found in Pyth Hermes response"))
+        let mut values: Vec<QuarterValue> = results
+            .feeds
+            .iter()
+            .filter_map(|data| QuarterValue::from_price(self.registry.resolve(&data.price_feed_id)?, data))
+            .collect();
+
+        let mut failed = Vec::new();
+        for error in results.failed {
+            let stored = match self.registry.resolve(&error.price_feed_id) {
+                Some(feed) => self.latest_stored(feed).await.and_then(|observation| QuarterValue::from_observation(feed, observation)),
+                None => None,
+            };
+            match stored {
+                Some(value) => values.push(value),
+                None => failed.push(error),
+            }
         }
+
+        Analytics::new(values, failed)
     }
 
-    async fn fetch_from_sonic_via_pyth_programs(&self, feed_id: &str, symbol: &str) -> Result<PriceData, anyhow::Error> {
-        info!("Attempting to fetch GDP data through Sonic SVM Pyth Programs");
-        info!("Using Pyth Receiver: rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
-        info!("Using Pyth Price Feed: pythWSnswVUd12oZpeFP8e9CVaEqJg25g1Vtc2biRsT");
-        
-        let pyth_price_feed_program = Pubkey::from_str("pythWSnswVUd12oZpeFP8e9CVaEqJg25g1Vtc2biRsT")?;
-
-        let hex_without_prefix = feed_id.strip_prefix("0x").unwrap_or(feed_id);
-      
-        if let Ok(decoded_bytes) = hex::decode(hex_without_prefix) {
-            if decoded_bytes.len() == 32 {
-                let pubkey = Pubkey::new_from_array(decoded_bytes.try_into().unwrap());
-                info!("Trying direct account lookup for GDP feed: {}", pubkey);
-                
-                match self.sonic_rpc_client.get_account(&pubkey) {
-                    Ok(account) => {
-                        info!("Found GDP feed account on Sonic SVM!");
-                        info!(" Account size: {} bytes, owner: {}", account.data.len(), account.owner);
-                        
-                        
-                        match PriceFeed::try_from_slice(&account.data) {
-                            Ok(price_feed) => {
-                                let price = price_feed.get_price_unchecked();
-                                let price_value = price.price as f64 / 10_f64.powi(price.expo);
-                                let confidence = price.conf as f64 / 10_f64.powi(price.expo);
-
-                                info!("Successfully parsed REAL GDP data from Sonic SVM: {:.2}% (±{:.2}%)", price_value, confidence);
-
-                                return Ok(PriceData {
-                                    symbol: "US_GDP".to_string(),
-                                    price: price_value,
-                                    confidence,
-                                    publish_time: price.publish_time,
-                                    price_feed_id: pubkey.to_string(),
-                                    last_updated: chrono::Utc::now(),
-                                    source: "Sonic SVM Direct Account".to_string(),
-                                });
-                            }
-                            Err(e) => {
-                                warn!("Account exists but not a valid Pyth price feed: {}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        info!("GDP feed account not found on Sonic SVM: {}", e);
-                    }
-                }
+    async fn latest_stored(&self, feed: &Feed) -> Option<storage::Observation> {
+        let store = self.store.as_ref()?;
+        let query = HistoryQuery { feed_id: feed.id.clone(), from: 0, to: i64::MAX, bucket_secs: 1, limit: 1 };
+        match store.history(&query).await {
+            Ok(observations) => observations.into_iter().next(),
+            Err(e) => {
+                warn!("Failed to load the last stored price of {}: {}", feed.symbol, e);
+                None
             }
         }
-        
-        
-        info!("GDP feed accounts not found on Sonic SVM");
-        Err(anyhow::anyhow!("GDP feed accounts not available on Sonic SVM yet"))
     }
 
-
     async fn check_sonic_pyth_programs(&self) -> Result<serde_json::Value, anyhow::Error> {
         
         let pyth_receiver = "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ";
@@ -320,7 +458,7 @@ impl AppState {
         
         match Pubkey::from_str(pyth_receiver) {
             Ok(pubkey) => {
-                match self.sonic_rpc_client.get_account(&pubkey) {
+                match self.sonic_rpc_client.get_account(&pubkey).await {
                     Ok(account) => {
                         results.insert("pyth_receiver".to_string(), serde_json::json!({
                             "address": pyth_receiver,
@@ -333,10 +471,11 @@ impl AppState {
                         info!("Pyth Receiver found on Sonic SVM");
                     },
                     Err(e) => {
+                        let e = self.upstreams.scrub(&e.to_string());
                         results.insert("pyth_receiver".to_string(), serde_json::json!({
                             "address": pyth_receiver,
                             "status": " NOT FOUND",
-                            "error": e.to_string()
+                            "error": e
                         }));
                         warn!("Pyth Receiver not found: {}", e);
                     }
@@ -354,7 +493,7 @@ impl AppState {
         
         match Pubkey::from_str(pyth_price_feed) {
             Ok(pubkey) => {
-                match self.sonic_rpc_client.get_account(&pubkey) {
+                match self.sonic_rpc_client.get_account(&pubkey).await {
                     Ok(account) => {
                         results.insert("pyth_price_feed".to_string(), serde_json::json!({
                             "address": pyth_price_feed,
@@ -367,10 +506,11 @@ impl AppState {
                         info!("Pyth Price Feed program found on Sonic SVM");
                     },
                     Err(e) => {
+                        let e = self.upstreams.scrub(&e.to_string());
                         results.insert("pyth_price_feed".to_string(), serde_json::json!({
                             "address": pyth_price_feed,
                             "status": "NOT FOUND",
-                            "error": e.to_string()
+                            "error": e
                         }));
                         warn!("Pyth Price Feed program not found: {}", e);
                     }
@@ -386,12 +526,12 @@ impl AppState {
         }
         
         Ok(serde_json::json!({
-            "network": "Sonic SVM Mainnet Alpha",
-            "rpc_endpoint": "https://rpc.mainnet-alpha.sonic.game",
+            "network": self.upstreams.sonic_network,
+            "rpc_endpoint": self.upstreams.sonic_rpc_url_redacted(),
             "documentation": "https://docs.sonic.game/additional-tools-and-examples",
             "pyth_programs": results,
             "integration_status": "PURE Sonic SVM + Pyth Integration",
-            "gdp_feed_id": "0x01a2d2aa5728850767d67e2f82ddc9c8e4c3bbace231461386ef9cbb16d0d36b",
+            "gdp_feed_id": self.registry.headline().id,
             "architecture": "Sonic SVM Native Only"
         }))
     }
@@ -407,17 +547,71 @@ async fn health_check() -> Json<ApiResponse<String>> {
     })
 }
 
+async fn poller_status(State(state): State<AppState>) -> Json<ApiResponse<PollerStatus>> {
+    match &state.poller {
+        Some(status) => Json(ApiResponse {
+            success: true,
+            data: Some(status.read().unwrap().clone()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }),
+        None => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some("Poller is disabled".to_string()),
+            timestamp: chrono::Utc::now(),
+        }),
+    }
+}
+
+async fn subscriber_status(State(state): State<AppState>) -> Json<ApiResponse<SubscriberStatus>> {
+    match &state.subscriber {
+        Some(status) => Json(ApiResponse {
+            success: true,
+            data: Some(status.read().unwrap().clone()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }),
+        None => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some("Hermes subscriber is disabled".to_string()),
+            timestamp: chrono::Utc::now(),
+        }),
+    }
+}
+
+async fn watcher_status(State(state): State<AppState>) -> Json<ApiResponse<WatcherStatus>> {
+    match &state.watcher {
+        Some(status) => Json(ApiResponse {
+            success: true,
+            data: Some(status.read().unwrap().clone()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }),
+        None => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some("Account watcher is disabled".to_string()),
+            timestamp: chrono::Utc::now(),
+        }),
+    }
+}
+
 async fn sonic_status(State(state): State<AppState>) -> Json<ApiResponse<serde_json::Value>> {
-    match state.sonic_rpc_client.get_version() {
+    match state.sonic_rpc_client.get_version().await {
         Ok(version) => {
             let sonic_info = serde_json::json!({
-                "network": "Sonic SVM Mainnet Alpha",
-                "rpc_endpoint": "https://rpc.mainnet-alpha.sonic.game",
+                "network": state.upstreams.sonic_network,
+                "rpc_endpoint": state.upstreams.sonic_rpc_url_redacted(),
                 "solana_core_version": version.solana_core,
                 "feature_set": version.feature_set,
                 "status": "Connected to Sonic SVM",
                 "pyth_integration": "Ready for Pyth price feeds",
-                "fallback": "Solana mainnet available"
+                "fallback": "Solana mainnet available",
+                "fallback_rpc_endpoint": state.upstreams.solana_rpc_url_redacted(),
+                "hermes_endpoint": state.upstreams.hermes_url,
+                "commitment": state.upstreams.commitment.commitment.to_string()
             });
             
             Json(ApiResponse {
@@ -428,6 +622,7 @@ async fn sonic_status(State(state): State<AppState>) -> Json<ApiResponse<serde_j
             })
         },
         Err(e) => {
+            let e = state.upstreams.scrub(&e.to_string());
             error!("Sonic RPC connection failed: {}", e);
             Json(ApiResponse {
                 success: false,
@@ -486,28 +681,275 @@ async fn us_gdp_data(State(state): State<AppState>) -> Json<ApiResponse<PriceDat
     }
 }
 
-async fn all_gdp_feeds(State(state): State<AppState>) -> Json<ApiResponse<Vec<PriceData>>> {
-    match state.get_all_gdp_feeds().await {
-        Ok(feeds) => {
-            info!("📊 Successfully fetched {} GDP feeds", feeds.len());
-            
+async fn gdp_feed(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+) -> Result<Json<ApiResponse<PriceData>>, UnknownFeed> {
+    let feed = state.resolve_feed(&key)?;
+    match state.get_price_feed(feed).await {
+        Ok(data) => Ok(Json(ApiResponse {
+            success: true,
+            data: Some(data),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        })),
+        Err(e) => {
+            error!("Failed to fetch {}: {}", feed.symbol, e);
+            Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to fetch {}: {}", feed.symbol, e)),
+                timestamp: chrono::Utc::now(),
+            }))
+        }
+    }
+}
+
+async fn all_gdp_feeds(
+    State(state): State<AppState>,
+    Query(params): Query<FeedsParams>,
+) -> Result<Json<MultiFeedResponse>, UnknownFeed> {
+    let period = match params.period() {
+        Ok(period) => period,
+        Err(e) => {
+            warn!("Rejecting /gdp/all filters: {}", e);
+            return Ok(Json(MultiFeedResponse {
+                success: false,
+                data: None,
+                failed: Vec::new(),
+                error: Some(e.to_string()),
+                timestamp: chrono::Utc::now(),
+            }));
+        }
+    };
+    let mut feeds: Vec<&Feed> = Vec::new();
+    for key in params.ids.iter().flat_map(|ids| ids.split(',')).filter(|key| !key.trim().is_empty()) {
+        let feed = state.resolve_feed(key)?;
+        if !feeds.iter().any(|known| known.id == feed.id) {
+            feeds.push(feed);
+        }
+    }
+    if feeds.is_empty() {
+        feeds = state.registry.feeds().iter().collect();
+    }
+    feeds.retain(|feed| params.matches(&period, feed));
+    if feeds.is_empty() {
+        return Ok(Json(MultiFeedResponse {
+            success: true,
+            data: Some(Vec::new()),
+            failed: Vec::new(),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }));
+    }
+
+    let results = state.get_feeds(feeds).await;
+
+    if results.feeds.is_empty() {
+        error!("Failed to fetch GDP feeds: none of {} feeds could be fetched", results.failed.len());
+        return Ok(Json(MultiFeedResponse {
+            success: false,
+            data: None,
+            failed: results.failed,
+            error: Some("Failed to fetch all GDP feeds: No GDP feeds could be fetched".to_string()),
+            timestamp: chrono::Utc::now(),
+        }));
+    }
+
+    info!("📊 Successfully fetched {} GDP feeds ({} failed)", results.feeds.len(), results.failed.len());
+
+    Ok(Json(MultiFeedResponse {
+        success: true,
+        data: Some(results.feeds),
+        failed: results.failed,
+        error: None,
+        timestamp: chrono::Utc::now(),
+    }))
+}
+
+async fn gdp_history(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<ApiResponse<History>>, UnknownFeed> {
+    let feed = state.resolve_feed(&key)?;
+    match state.get_history(feed, &params).await {
+        Ok(history) => Ok(Json(ApiResponse {
+            success: true,
+            data: Some(history),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        })),
+        Err(e) => {
+            warn!("Failed to load history of {}: {}", feed.symbol, e);
+            Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to load history of {}: {}", feed.symbol, e)),
+                timestamp: chrono::Utc::now(),
+            }))
+        }
+    }
+}
+
+async fn gdp_analytics(
+    State(state): State<AppState>,
+    Query(params): Query<AnalyticsParams>,
+) -> Json<ApiResponse<Analytics>> {
+    let analytics = state.get_analytics(&params).await;
+
+    if analytics.series.is_empty() && !analytics.failed.is_empty() {
+        error!("Failed to compute GDP analytics: none of {} quarterly feeds could be fetched", analytics.failed.len());
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!(
+                "Failed to compute GDP analytics: none of {} quarterly feeds could be fetched or found in storage",
+                analytics.failed.len()
+            )),
+            timestamp: chrono::Utc::now(),
+        });
+    }
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(analytics),
+        error: None,
+        timestamp: chrono::Utc::now(),
+    })
+}
+
+/// Checks the `Authorization: Bearer` header against `--admin-token`.
+fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let Some(token) = &state.admin_token else {
+        return Err((StatusCode::FORBIDDEN, "Admin endpoints are disabled (set ADMIN_TOKEN)".to_string()));
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    // Constant-time comparison, so response times do not reveal how much of the token matched.
+    let matches = provided.is_some_and(|provided| bool::from(provided.as_bytes().ct_eq(token.as_bytes())));
+    if !matches {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string()));
+    }
+    Ok(())
+}
+
+fn backfiller(state: &AppState) -> Result<&Arc<Backfiller>, (StatusCode, String)> {
+    state
+        .backfiller
+        .as_ref()
+        .ok_or_else(|| (StatusCode::SERVICE_UNAVAILABLE, "Observation storage is disabled".to_string()))
+}
+
+fn admin_response(
+    result: Result<BackfillStatus, (StatusCode, String)>,
+) -> (StatusCode, Json<ApiResponse<BackfillStatus>>) {
+    match result {
+        Ok(status) => (
+            StatusCode::OK,
             Json(ApiResponse {
                 success: true,
-                data: Some(feeds),
+                data: Some(status),
                 error: None,
                 timestamp: chrono::Utc::now(),
-            })
-        },
-        Err(e) => {
-            error!("Failed to fetch all GDP feeds: {}", e);
+            }),
+        ),
+        Err((code, e)) => (
+            code,
             Json(ApiResponse {
                 success: false,
                 data: None,
-                error: Some(format!("Failed to fetch all GDP feeds: {}", e)),
+                error: Some(e),
                 timestamp: chrono::Utc::now(),
-            })
+            }),
+        ),
+    }
+}
+
+async fn backfill_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<ApiResponse<BackfillStatus>>) {
+    admin_response(authorize_admin(&state, &headers).and_then(|()| Ok(backfiller(&state)?.status())))
+}
+
+async fn start_backfill(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<BackfillRequest>,
+) -> (StatusCode, Json<ApiResponse<BackfillStatus>>) {
+    admin_response(authorize_admin(&state, &headers).and_then(|()| {
+        let backfiller = backfiller(&state)?;
+        let plan = backfiller
+            .plan(&request, &state.registry)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+        backfiller.spawn(plan).map_err(|e| (StatusCode::CONFLICT, e.to_string()))
+    }))
+}
+
+async fn live_updates(State(state): State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    let limits = state.stream_limits;
+    upgrade.on_upgrade(move |socket| websocket::serve(socket, state, limits))
+}
+
+async fn gdp_stream(
+    State(state): State<AppState>,
+    Query(params): Query<StreamParams>,
+    headers: HeaderMap,
+) -> Result<Sse<impl futures::Stream<Item = Result<axum::response::sse::Event, Infallible>>>, UnknownFeed> {
+    let mut feeds: Vec<Feed> = Vec::new();
+    for key in params.symbols.iter().flat_map(|symbols| symbols.split(',')).filter(|key| !key.trim().is_empty()) {
+        let feed = state.resolve_feed(key)?;
+        if !feeds.iter().any(|known| known.id == feed.id) {
+            feeds.push(feed.clone());
         }
     }
+    if feeds.is_empty() {
+        feeds = state.registry.feeds().to_vec();
+    }
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or(params.last_event_id);
+    let resume = last_event_id.and_then(|raw| {
+        let id = sse::EventId::parse(&raw);
+        if id.is_none() {
+            warn!("Ignoring invalid Last-Event-ID {}", raw);
+        }
+        id
+    });
+
+    let keep_alive = KeepAlive::new().interval(state.stream_limits.heartbeat).text("keep-alive");
+    let max_replay = state.max_replay;
+    Ok(Sse::new(sse::events(state, feeds, resume, max_replay)).keep_alive(keep_alive))
+}
+
+async fn prometheus_metrics(State(state): State<AppState>) -> ([(header::HeaderName, &'static str); 1], String) {
+    let mut latest: Vec<PriceData> = state.cache.snapshot();
+    latest.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        state.metrics.render(&latest),
+    )
+}
+
+/// Counts every routed request and its latency by method, route template
+/// and status.
+async fn track_requests(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let started = tokio::time::Instant::now();
+    let response = next.run(request).await;
+    metrics.record_request(&method, &route, response.status().as_u16(), started.elapsed());
+    response
 }
 
 async fn serve_dashboard() -> Html<String> {
@@ -521,6 +963,31 @@ async fn serve_dashboard() -> Html<String> {
     Html(html)
 }
 
+/// Every route of the API.
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/", get(serve_dashboard))
+        .route("/dashboard", get(serve_dashboard))
+        .route("/health", get(health_check))
+        .route("/sonic/status", get(sonic_status))
+        .route("/sonic/programs", get(sonic_pyth_programs))
+        .route("/gdp", get(us_gdp_data))
+        .route("/gdp/all", get(all_gdp_feeds))
+        .route("/gdp/analytics", get(gdp_analytics))
+        .route("/gdp/stream", get(gdp_stream))
+        .route("/gdp/:symbol", get(gdp_feed))
+        .route("/gdp/:symbol/history", get(gdp_history))
+        .route("/poller/status", get(poller_status))
+        .route("/subscriber/status", get(subscriber_status))
+        .route("/watcher/status", get(watcher_status))
+        .route("/ws", get(live_updates))
+        .route("/admin/backfill", get(backfill_status).post(start_backfill))
+        .route("/metrics", get(prometheus_metrics))
+        .route_layer(middleware::from_fn_with_state(state.metrics.clone(), track_requests))
+        .layer(CorsLayer::permissive())
+        .with_state(state)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
@@ -528,41 +995,263 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let args = Args::parse();
 
+    let mut config = Config::load(&args.config)?;
+    if let Some(action) = args.quality_action {
+        config.quality.action = action;
+    }
+    let registry = config.registry;
+    let upstreams = Upstreams::resolve(config.upstreams, args.upstreams)?;
+
     info!("Starting PURE Sonic SVM GDP Fetcher on port {}", args.port);
-    info!("Sonic SVM RPC: https://rpc.mainnet-alpha.sonic.game");
-    info!("Priority: Sonic SVM → Solana RPC → Hermes API");
-    info!("GDP Feed ID: 0x01a2d2aa5728850767d67e2f82ddc9c8e4c3bbace231461386ef9cbb16d0d36b");
+    info!("Sonic SVM RPC: {}", upstreams.sonic_rpc_url_redacted());
+    info!("Solana RPC: {}", upstreams.solana_rpc_url_redacted());
+    info!("Pyth Hermes: {}", upstreams.hermes_url);
+    if !upstreams.has_solana_api_key() {
+        warn!("No Solana RPC API key configured (SOLANA_RPC_API_KEY or --solana-api-key-file)");
+    }
+    info!("Loaded {} feeds from {}", registry.feeds().len(), args.config.display());
+    info!("GDP Feed ID: {}", registry.headline().id);
     info!("Pyth Receiver: rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
     info!("Pyth Price Feed: pythWSnswVUd12oZpeFP8e9CVaEqJg25g1Vtc2biRsT");
 
+    let upstreams = Arc::new(upstreams);
+    let sonic_rpc_client = Arc::new(RpcClient::new_with_timeout_and_commitment(
+        upstreams.sonic_rpc_url.clone(),
+        upstreams.rpc_timeout,
+        upstreams.commitment,
+    ));
+    let solana_fallback_client = Arc::new(RpcClient::new_with_timeout_and_commitment(
+        upstreams.solana_rpc_endpoint(),
+        upstreams.rpc_timeout,
+        upstreams.commitment,
+    ));
+
+    let hermes_client = HermesClient::new(Client::new(), &upstreams.hermes_url, upstreams.hermes_timeout);
+    let mut hermes_source = HermesSource::new(hermes_client.clone(), upstreams.hermes_retain_updates);
+    let verification = args.verify_updates.unwrap_or(config.verification.mode);
+    if verification != VerificationMode::Off {
+        if config.verification.guardians.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Hermes update verification is {:?} but [verification].guardians in {} is empty; list the addresses of guardian set {}",
+                verification,
+                args.config.display(),
+                config.verification.guardian_set_index,
+            )
+            .into());
+        }
+        let guardian_set = GuardianSet::from_hex(
+            config.verification.guardian_set_index,
+            &config.verification.guardians,
+        )?;
+        info!("Verifying Hermes updates against guardian set {} ({:?})", guardian_set.index, verification);
+        hermes_source = hermes_source.with_verifier(Arc::new(UpdateVerifier::new(guardian_set)), verification);
+    }
+    let hermes_source = Arc::new(hermes_source);
+
+    let sonic_source = Arc::new(SonicSource::new(sonic_rpc_client.clone(), upstreams.clone()));
+    let solana_source = Arc::new(SolanaSource::new(solana_fallback_client, upstreams.clone()));
+    let available: Vec<Arc<dyn PriceSource>> = vec![sonic_source.clone(), solana_source.clone(), hermes_source.clone()];
+    let source_order = args.sources.unwrap_or(config.sources.order);
+    let disabled_sources = args.disable_sources.unwrap_or(config.sources.disabled);
+    let quality = QualityPolicy::new(&config.quality)?;
+    let metrics = Arc::new(Metrics::default());
+    let mut sources = SourceChain::new(available, &source_order, &disabled_sources, &registry)?
+        .with_concurrency(args.fetch_concurrency.unwrap_or(config.fetch.concurrency))
+        .with_quality(quality)
+        .with_metrics(metrics.clone());
+    let store = if config.storage.enabled || args.database_url.is_some() {
+        let url = args.database_url.unwrap_or(config.storage.url);
+        let store = storage::open(&url).await?;
+        info!("Recording observations in {} ({})", store.backend(), config::redact_url(&url));
+        sources = sources.with_store(store.clone());
+        Some(store)
+    } else {
+        None
+    };
+    let backfiller = match &store {
+        Some(store) => Some(Arc::new(Backfiller::new(hermes_client.clone(), store.clone(), &config.backfill)?)),
+        None => None,
+    };
+
+    if let Some(Command::Backfill(request)) = &args.command {
+        let backfiller = backfiller
+            .as_ref()
+            .ok_or("Backfill needs observation storage: enable [storage] or set DATABASE_URL")?;
+        let status = backfiller.run(backfiller.plan(request, &registry)?).await?;
+        info!(
+            "Backfilled {} feeds: {} requests, {} observations, {} new",
+            status.feeds, status.requests, status.observations, status.inserted
+        );
+        return Ok(());
+    }
+    let fetch_deadline = Duration::from_secs(args.fetch_deadline_secs.unwrap_or(config.fetch.deadline_secs));
+    let cache = PriceCache::new(
+        Duration::from_secs(args.cache_ttl_secs.unwrap_or(config.cache.ttl_secs)),
+        Duration::from_secs(config.cache.stale_while_revalidate_secs),
+        config.stream.buffer,
+        config.stream.replay_buffer,
+    )
+    .with_metrics(metrics.clone());
+
+    info!("Priority: {}", sources.describe());
+
+    let sources = Arc::new(sources);
+    let registry = Arc::new(registry);
+    let cache = Arc::new(cache);
+
+    let poller = if args.poller.unwrap_or(config.poller.enabled) {
+        let schedule = PollSchedule {
+            interval: Duration::from_secs(args.poll_interval_secs.unwrap_or(config.poller.interval_secs).max(1)),
+            jitter: Duration::from_secs(config.poller.jitter_secs),
+            max_backoff: Duration::from_secs(config.poller.max_backoff_secs),
+            deadline: fetch_deadline,
+        };
+        info!("Polling {} feeds every {:?} (jitter up to {:?})", registry.feeds().len(), schedule.interval, schedule.jitter);
+        let poller = Poller::new(sources.clone(), registry.clone(), cache.clone(), schedule);
+        let status = poller.status();
+        poller.spawn();
+        Some(status)
+    } else {
+        None
+    };
+
+    let subscriber = if args.hermes_subscriber.unwrap_or(config.subscriber.enabled) {
+        info!("Following Pyth Hermes price stream for {} feeds", registry.feeds().len());
+        let subscriber = Subscriber::new(
+            hermes_client,
+            hermes_source,
+            sources.clone(),
+            registry.clone(),
+            cache.clone(),
+            &config.subscriber,
+        );
+        let status = subscriber.status();
+        subscriber.spawn();
+        Some(status)
+    } else {
+        None
+    };
+
+    let watcher = if args.account_watcher.unwrap_or(config.watcher.enabled) {
+        let watcher = Watcher::new(
+            vec![sonic_source, solana_source],
+            sources.clone(),
+            registry.clone(),
+            cache.clone(),
+            upstreams.clone(),
+            &config.watcher,
+        )?;
+        info!("Watching feed accounts on {} over PubSub", config.watcher.sources.join(", "));
+        info!("Sonic SVM PubSub: {}", config::redact_url(&upstreams.sonic_ws_url));
+        info!("Solana PubSub: {}", upstreams.solana_ws_url_redacted());
+        let status = watcher.status();
+        watcher.spawn();
+        Some(status)
+    } else {
+        None
+    };
+
     let state = AppState {
-        sonic_rpc_client: Arc::new(RpcClient::new("https://rpc.mainnet-alpha.sonic.game".to_string())),
-        solana_fallback_client: Arc::new(RpcClient::new("https://mainnet.helius-rpc.com/?api-key=22abefb4-e86a-482d-9a62-452fcd4f2cb0".to_string())),
-        http_client: Client::new(),
+        sonic_rpc_client,
+        sources,
+        registry,
+        upstreams,
+        cache,
+        fetch_deadline,
+        poller,
+        subscriber,
+        watcher,
+        store,
+        backfiller,
+        admin_token: args.admin_token.map(Arc::from),
+        stream_limits: websocket::Limits::from(&config.stream),
+        max_replay: config.stream.max_replay,
+        metrics: metrics.clone(),
     };
 
-    let app = Router::new()
-        .route("/", get(serve_dashboard))
-        .route("/dashboard", get(serve_dashboard))
-        .route("/health", get(health_check))
-        .route("/sonic/status", get(sonic_status))
-        .route("/sonic/programs", get(sonic_pyth_programs))
-        .route("/gdp", get(us_gdp_data))
-        .route("/gdp/all", get(all_gdp_feeds))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+    let feed_count = state.registry.feeds().len();
+
+    let app = router(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
     info!("Server starting on {}", addr);
     info!("Dashboard UI: http://localhost:{}/", args.port);
     info!("GDP API: http://localhost:{}/gdp", args.port);
     info!("All GDP Feeds: http://localhost:{}/gdp/all", args.port);
+    info!("Live updates: ws://localhost:{}/ws and http://localhost:{}/gdp/stream", args.port, args.port);
     info!("Sonic Status: http://localhost:{}/sonic/status", args.port);
+    info!("Metrics: http://localhost:{}/metrics", args.port);
     info!("Pyth Programs: http://localhost:{}/sonic/programs", args.port);
-    info!("GDP DASHBOARD READY WITH {} FEEDS!", 23);
+    info!("GDP DASHBOARD READY WITH {} FEEDS!", feed_count);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
     axum::serve(listener, app).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{Outcome, StubSource};
+
+    async fn get(url: &str) -> (StatusCode, serde_json::Value) {
+        let response = reqwest::get(url).await.unwrap();
+        (response.status(), response.json().await.unwrap())
+    }
+
+    fn symbols(body: &serde_json::Value) -> Vec<&str> {
+        body["data"].as_array().unwrap().iter().map(|data| data["symbol"].as_str().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn serves_a_feed_by_symbol_or_id() {
+        let source = Arc::new(StubSource::new("stub", Outcome::Price(280_000)).with("ECO.US.GDPQ225", Outcome::Fail));
+        let url = testing::serve(router(testing::app_state(source))).await;
+        let feed = testing::feed("ECO.US.GDPQ125");
+
+        let bare_id = feed.id.trim_start_matches("0x").to_uppercase();
+        for key in ["ECO.US.GDPQ125", feed.id.as_str(), bare_id.as_str()] {
+            let (status, body) = get(&format!("{}/gdp/{}", url, key)).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!((&body["success"], &body["data"]["symbol"]), (&true.into(), &"ECO.US.GDPQ125".into()), "{}", key);
+        }
+
+        // A known feed that cannot be fetched is not a 404.
+        let (status, body) = get(&format!("{}/gdp/ECO.US.GDPQ225", url)).await;
+        assert_eq!((status, &body["success"]), (StatusCode::OK, &false.into()));
+        assert_eq!(
+            body["error"],
+            "Failed to fetch ECO.US.GDPQ225: Failed to fetch ECO.US.GDPQ225 from all sources: stub: stub is down"
+        );
+
+        let (status, body) = get(&format!("{}/gdp/ECO.US.CPI", url)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!((&body["success"], &body["error"]), (&false.into(), &"Unknown feed ECO.US.CPI".into()));
+        let known: Vec<String> = serde_json::from_value(body["known_symbols"].clone()).unwrap();
+        assert_eq!(known, testing::registry().symbols());
+    }
+
+    #[tokio::test]
+    async fn filters_all_feeds_by_ids() {
+        let source = Arc::new(StubSource::new("stub", Outcome::Price(280_000)));
+        let url = testing::serve(router(testing::app_state(source.clone()))).await;
+        let feed = testing::feed("ECO.US.GDPQ125");
+
+        // Symbols and IDs mix, blanks are ignored and repeats collapse.
+        let (status, body) = get(&format!("{}/gdp/all?ids=ECO.US.GDPQ225, {},,ECO.US.GDPQ125", url, feed.id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(symbols(&body), ["ECO.US.GDPQ225", "ECO.US.GDPQ125"]);
+        assert_eq!(source.batches(), [["ECO.US.GDPQ225", "ECO.US.GDPQ125"]]);
+
+        // Filters apply to the listed feeds.
+        let (_, body) = get(&format!("{}/gdp/all?ids=ECO.US.GDPQ225,ECO.US.GDPQ125&quarter=1", url)).await;
+        assert_eq!(symbols(&body), ["ECO.US.GDPQ125"]);
+
+        let (_, body) = get(&format!("{}/gdp/all?ids=", url)).await;
+        assert_eq!(body["data"].as_array().unwrap().len(), testing::registry().feeds().len());
+
+        let (status, body) = get(&format!("{}/gdp/all?ids=ECO.US.GDPQ125,0xabc", url)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Unknown feed 0xabc");
+    }
+}
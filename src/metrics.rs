@@ -0,0 +1,346 @@
+//! Prometheus metrics, served by `/metrics` in the text exposition format.
+//!
+//! Counters and histograms are recorded as requests are served and feeds are
+//! fetched; the per-feed price gauges are read from the cache when scraped.
+
+use crate::{config::Feed, PriceData};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::Mutex,
+    time::Duration,
+};
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative.
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+/// How a cache lookup was answered.
+#[derive(Clone, Copy)]
+pub enum CacheResult {
+    Hit,
+    /// Served stale while revalidating.
+    Stale,
+    Miss,
+}
+
+#[derive(Default)]
+struct Recorded {
+    /// By method, route and status.
+    requests: BTreeMap<(String, String, u16), Histogram>,
+    /// By source and feed symbol.
+    fetches: BTreeMap<(&'static str, String), Histogram>,
+    fetch_errors: BTreeMap<(&'static str, String), u64>,
+    /// By feed symbol and the source that served it after earlier ones failed.
+    fallbacks: BTreeMap<(String, &'static str), u64>,
+    cache_hits: u64,
+    cache_stale: u64,
+    cache_misses: u64,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    recorded: Mutex<Recorded>,
+}
+
+impl Metrics {
+    pub fn record_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let mut recorded = self.recorded.lock().unwrap();
+        recorded
+            .requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default()
+            .observe(elapsed);
+    }
+
+    /// One attempt of `source` at `feed`, including the quality checks.
+    pub fn record_fetch(&self, source: &'static str, feed: &Feed, elapsed: Duration, ok: bool) {
+        let mut recorded = self.recorded.lock().unwrap();
+        let key = (source, feed.symbol.clone());
+        if !ok {
+            *recorded.fetch_errors.entry(key.clone()).or_default() += 1;
+        }
+        recorded.fetches.entry(key).or_default().observe(elapsed);
+    }
+
+    /// `feed` was served by `source`, not the first source of its chain.
+    pub fn record_fallback(&self, feed: &Feed, source: &'static str) {
+        *self.recorded.lock().unwrap().fallbacks.entry((feed.symbol.clone(), source)).or_default() += 1;
+    }
+
+    pub fn record_cache(&self, result: CacheResult) {
+        let mut recorded = self.recorded.lock().unwrap();
+        match result {
+            CacheResult::Hit => recorded.cache_hits += 1,
+            CacheResult::Stale => recorded.cache_stale += 1,
+            CacheResult::Miss => recorded.cache_misses += 1,
+        }
+    }
+
+    /// Everything recorded so far plus gauges for the latest price of each
+    /// feed, in the Prometheus text format.
+    pub fn render(&self, latest: &[PriceData]) -> String {
+        let recorded = self.recorded.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out, "http_requests_total", "counter", "HTTP requests served, by route and status.");
+        for ((method, route, status), histogram) in &recorded.requests {
+            let labels = labels(&[("method", method), ("route", route), ("status", &status.to_string())]);
+            sample(&mut out, "http_requests_total", &labels, histogram.count as f64);
+        }
+        header(&mut out, "http_request_duration_seconds", "histogram", "Time to respond to HTTP requests.");
+        for ((method, route, status), histogram) in &recorded.requests {
+            let labels = [("method", method.as_str()), ("route", route.as_str()), ("status", &status.to_string())];
+            write_histogram(&mut out, "http_request_duration_seconds", &labels, histogram);
+        }
+
+        header(&mut out, "gdp_source_fetch_duration_seconds", "histogram", "Upstream fetch latency per source and feed.");
+        for ((source, symbol), histogram) in &recorded.fetches {
+            write_histogram(&mut out, "gdp_source_fetch_duration_seconds", &[("source", source), ("feed", symbol)], histogram);
+        }
+        header(&mut out, "gdp_source_errors_total", "counter", "Failed upstream fetches, including rejected prices.");
+        for ((source, symbol), errors) in &recorded.fetch_errors {
+            sample(&mut out, "gdp_source_errors_total", &labels(&[("source", source), ("feed", symbol)]), *errors as f64);
+        }
+        header(&mut out, "gdp_source_fallbacks_total", "counter", "Feeds served by a source after earlier sources in their chain failed.");
+        for ((symbol, source), fallbacks) in &recorded.fallbacks {
+            sample(&mut out, "gdp_source_fallbacks_total", &labels(&[("feed", symbol), ("source", source)]), *fallbacks as f64);
+        }
+
+        header(&mut out, "gdp_cache_requests_total", "counter", "Price cache lookups, by result.");
+        for (result, count) in [("hit", recorded.cache_hits), ("stale", recorded.cache_stale), ("miss", recorded.cache_misses)] {
+            sample(&mut out, "gdp_cache_requests_total", &labels(&[("result", result)]), count as f64);
+        }
+        let lookups = recorded.cache_hits + recorded.cache_stale + recorded.cache_misses;
+        if lookups > 0 {
+            header(&mut out, "gdp_cache_hit_ratio", "gauge", "Share of cache lookups answered from memory, fresh or stale.");
+            let ratio = (recorded.cache_hits + recorded.cache_stale) as f64 / lookups as f64;
+            sample(&mut out, "gdp_cache_hit_ratio", "", ratio);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        header(&mut out, "gdp_feed_price", "gauge", "Latest price of each feed.");
+        for data in latest {
+            sample(&mut out, "gdp_feed_price", &feed_labels(data), data.price);
+        }
+        header(&mut out, "gdp_feed_confidence", "gauge", "Confidence interval of each feed's latest price.");
+        for data in latest {
+            sample(&mut out, "gdp_feed_confidence", &feed_labels(data), data.confidence);
+        }
+        header(&mut out, "gdp_feed_age_seconds", "gauge", "Seconds since each feed's latest price was published.");
+        for data in latest {
+            sample(&mut out, "gdp_feed_age_seconds", &feed_labels(data), (now - data.publish_time) as f64);
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &str, value: f64) {
+    let _ = writeln!(out, "{}{} {}", name, labels, value);
+}
+
+fn write_histogram(out: &mut String, name: &str, base: &[(&str, &str)], histogram: &Histogram) {
+    let mut cumulative = 0;
+    for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+        cumulative += count;
+        let le = bound.to_string();
+        let mut bucket_labels = base.to_vec();
+        bucket_labels.push(("le", &le));
+        sample(out, &format!("{}_bucket", name), &labels(&bucket_labels), cumulative as f64);
+    }
+    let mut bucket_labels = base.to_vec();
+    bucket_labels.push(("le", "+Inf"));
+    sample(out, &format!("{}_bucket", name), &labels(&bucket_labels), histogram.count as f64);
+    sample(out, &format!("{}_sum", name), &labels(base), histogram.sum);
+    sample(out, &format!("{}_count", name), &labels(base), histogram.count as f64);
+}
+
+fn feed_labels(data: &PriceData) -> String {
+    labels(&[("feed", &data.symbol), ("feed_id", &data.price_feed_id), ("unit", &data.unit)])
+}
+
+/// `{name="value",...}` with values escaped as the text format requires.
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// One sample of the text format: name, labels and value.
+    type Sample = (String, BTreeMap<String, String>, f64);
+
+    /// Parses `render`'s output, checking that every family is declared once
+    /// before its samples.
+    fn parse(text: &str) -> Vec<Sample> {
+        let mut declared: Vec<String> = Vec::new();
+        let mut samples = Vec::new();
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let name = rest.split(' ').next().unwrap().to_string();
+                assert!(!declared.contains(&name), "{} declared twice", name);
+                declared.push(name);
+                continue;
+            }
+            if line.starts_with("# HELP ") {
+                continue;
+            }
+
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            let (name, mut rest) = series.split_once('{').map_or((series, ""), |(name, rest)| (name, rest));
+            let family = ["_bucket", "_sum", "_count"]
+                .iter()
+                .find_map(|suffix| name.strip_suffix(suffix).filter(|family| declared.iter().any(|d| d == family)))
+                .unwrap_or(name);
+            assert_eq!(declared.last().map(String::as_str), Some(family), "{} outside its family", line);
+
+            let mut labels = BTreeMap::new();
+            while let Some((label, after)) = rest.split_once("=\"") {
+                let mut value = String::new();
+                let mut chars = after.char_indices();
+                let end = loop {
+                    match chars.next().unwrap() {
+                        (_, '\\') => value.push(match chars.next().unwrap().1 {
+                            'n' => '\n',
+                            escaped => escaped,
+                        }),
+                        (index, '"') => break index,
+                        (_, c) => value.push(c),
+                    }
+                };
+                labels.insert(label.trim_start_matches(',').to_string(), value);
+                rest = &after[end + 1..];
+            }
+            assert!(rest.is_empty() || rest == "}", "{}", line);
+            samples.push((name.to_string(), labels, value.parse().unwrap()));
+        }
+        samples
+    }
+
+    fn values<'a>(samples: &'a [Sample], name: &str, filter: &[(&str, &str)]) -> Vec<(&'a BTreeMap<String, String>, f64)> {
+        samples
+            .iter()
+            .filter(|(n, labels, _)| n == name && filter.iter().all(|(k, v)| labels.get(*k).map(String::as_str) == Some(v)))
+            .map(|(_, labels, value)| (labels, *value))
+            .collect()
+    }
+
+    #[test]
+    fn renders_parseable_histograms_and_counters() {
+        let metrics = Metrics::default();
+        let route = "/gdp/\"odd\"\\route\nnext";
+        for millis in [3, 20, 300, 20_000] {
+            metrics.record_request("GET", route, 200, Duration::from_millis(millis));
+        }
+        metrics.record_request("GET", "/gdp", 500, Duration::from_millis(1));
+        let feed = testing::feed("ECO.US.GDP");
+        metrics.record_fetch("sonic", &feed, Duration::from_millis(40), false);
+        metrics.record_fetch("hermes", &feed, Duration::from_millis(60), true);
+        metrics.record_fallback(&feed, "hermes");
+        for result in [CacheResult::Hit, CacheResult::Hit, CacheResult::Hit, CacheResult::Stale, CacheResult::Miss, CacheResult::Miss] {
+            metrics.record_cache(result);
+        }
+
+        let latest = [testing::price(&feed, 280_000, 1_000, -5, chrono::Utc::now().timestamp() - 60)];
+        let samples = parse(&metrics.render(&latest));
+
+        let odd = [("method", "GET"), ("route", route), ("status", "200")];
+        assert_eq!(values(&samples, "http_requests_total", &odd)[0].1, 4.0);
+        assert_eq!(values(&samples, "http_requests_total", &[("status", "500")])[0].1, 1.0);
+
+        // Buckets are cumulative and end with `+Inf`, which equals `_count`.
+        let buckets = values(&samples, "http_request_duration_seconds_bucket", &odd);
+        let counts: Vec<(&str, f64)> = buckets.iter().map(|(labels, value)| (labels["le"].as_str(), *value)).collect();
+        assert_eq!(
+            counts,
+            [
+                ("0.005", 1.0),
+                ("0.01", 1.0),
+                ("0.025", 2.0),
+                ("0.05", 2.0),
+                ("0.1", 2.0),
+                ("0.25", 2.0),
+                ("0.5", 3.0),
+                ("1", 3.0),
+                ("2.5", 3.0),
+                ("5", 3.0),
+                ("10", 3.0),
+                ("+Inf", 4.0),
+            ]
+        );
+        assert_eq!(values(&samples, "http_request_duration_seconds_count", &odd)[0].1, 4.0);
+        let sum = values(&samples, "http_request_duration_seconds_sum", &odd)[0].1;
+        assert!((sum - 20.323).abs() < 1e-9, "{}", sum);
+        for (name, labels, _) in samples.iter().filter(|(name, ..)| name.ends_with("_bucket")) {
+            let mut series = labels.clone();
+            series.remove("le");
+            let family = name.strip_suffix("_bucket").unwrap();
+            let counts: Vec<f64> = samples
+                .iter()
+                .filter(|(n, l, _)| n == name && l.iter().filter(|(k, _)| *k != "le").eq(series.iter()))
+                .map(|(.., value)| *value)
+                .collect();
+            assert!(counts.windows(2).all(|pair| pair[0] <= pair[1]), "{} {:?}", name, counts);
+            let count = samples.iter().find(|(n, l, _)| *n == format!("{}_count", family) && *l == series).unwrap().2;
+            assert_eq!(counts.last(), Some(&count));
+        }
+
+        let gdp = [("feed", "ECO.US.GDP")];
+        assert_eq!(values(&samples, "gdp_source_errors_total", &gdp).len(), 1);
+        assert_eq!(values(&samples, "gdp_source_errors_total", &[("source", "sonic")])[0].1, 1.0);
+        assert_eq!(values(&samples, "gdp_source_fallbacks_total", &[("source", "hermes")])[0].1, 1.0);
+        assert_eq!(values(&samples, "gdp_source_fetch_duration_seconds_count", &gdp).len(), 2);
+
+        let cache: Vec<(&str, f64)> =
+            values(&samples, "gdp_cache_requests_total", &[]).iter().map(|(labels, value)| (labels["result"].as_str(), *value)).collect();
+        assert_eq!(cache, [("hit", 3.0), ("stale", 1.0), ("miss", 2.0)]);
+        assert_eq!(values(&samples, "gdp_cache_hit_ratio", &[])[0].1, 4.0 / 6.0);
+
+        assert_eq!(values(&samples, "gdp_feed_price", &gdp)[0].1, 2.8);
+        assert_eq!(values(&samples, "gdp_feed_price", &gdp)[0].0["feed_id"], feed.id);
+        assert_eq!(values(&samples, "gdp_feed_confidence", &gdp)[0].1, 0.01);
+        let age = values(&samples, "gdp_feed_age_seconds", &gdp)[0].1;
+        assert!((60.0..65.0).contains(&age), "{}", age);
+    }
+
+    #[test]
+    fn leaves_out_the_hit_ratio_before_any_lookup() {
+        let samples = parse(&Metrics::default().render(&[]));
+        let cache: Vec<f64> = values(&samples, "gdp_cache_requests_total", &[]).iter().map(|(_, value)| *value).collect();
+        assert_eq!(cache, [0.0, 0.0, 0.0]);
+        assert!(values(&samples, "gdp_cache_hit_ratio", &[]).is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+//! Layouts of Pyth price accounts read directly from Sonic or Solana.
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{hash::hashv, pubkey::Pubkey};
+use std::str::FromStr;
+
+/// Pyth Solana receiver program, owner of `PriceUpdateV2` accounts.
+pub const PYTH_RECEIVER_PROGRAM: &str = "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ";
+
+/// Pyth push oracle program, from which the price-feed PDAs are derived. The
+/// `PriceUpdateV2` accounts at those addresses are owned by the receiver.
+pub const PYTH_PUSH_ORACLE_PROGRAM: &str = "pythWSnswVUd12oZpeFP8e9CVaEqJg25g1Vtc2biRsT";
+
+/// Pyth oracle program on Solana, owner of legacy price accounts.
+pub const PYTH_ORACLE_PROGRAM: &str = "FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH";
+
+/// Derives the push-oracle price-feed account for `feed_id` on `shard`.
+pub fn price_feed_address(shard: u16, feed_id: &[u8; 32]) -> Pubkey {
+    let program = Pubkey::from_str(PYTH_PUSH_ORACLE_PROGRAM).expect("PYTH_PUSH_ORACLE_PROGRAM is a valid pubkey");
+    Pubkey::find_program_address(&[&shard.to_le_bytes(), feed_id], &program).0
+}
+
+/// How thoroughly the receiver checked the Wormhole VAA behind an update.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationLevel {
+    /// Only `num_signatures` guardian signatures were checked.
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+/// Price message stored in a `PriceUpdateV2` account (Borsh, little endian).
+#[derive(Clone, Debug)]
+pub struct PriceMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+/// A decoded pull-oracle `PriceUpdateV2` account.
+#[derive(Clone, Debug)]
+pub struct PriceUpdateV2 {
+    pub write_authority: Pubkey,
+    pub verification_level: VerificationLevel,
+    pub price_message: PriceMessage,
+    pub posted_slot: u64,
+}
+
+impl PriceUpdateV2 {
+    /// Anchor discriminator: first 8 bytes of `sha256("account:PriceUpdateV2")`.
+    pub fn discriminator() -> [u8; 8] {
+        let hash = hashv(&[b"account:PriceUpdateV2"]).to_bytes();
+        let mut discriminator = [0u8; 8];
+        discriminator.copy_from_slice(&hash[..8]);
+        discriminator
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, anyhow::Error> {
+        let mut reader = LeReader::new(data);
+
+        if reader.array::<8>()? != Self::discriminator() {
+            return Err(anyhow::anyhow!("Account is not a PriceUpdateV2 (discriminator mismatch)"));
+        }
+
+        let write_authority = Pubkey::new_from_array(reader.array::<32>()?);
+        let verification_level = match reader.u8()? {
+            0 => VerificationLevel::Partial { num_signatures: reader.u8()? },
+            1 => VerificationLevel::Full,
+            other => return Err(anyhow::anyhow!("Unknown verification level {}", other)),
+        };
+        let price_message = PriceMessage {
+            feed_id: reader.array::<32>()?,
+            price: reader.i64()?,
+            conf: reader.u64()?,
+            exponent: reader.i32()?,
+            publish_time: reader.i64()?,
+            prev_publish_time: reader.i64()?,
+            ema_price: reader.i64()?,
+            ema_conf: reader.u64()?,
+        };
+        let posted_slot = reader.u64()?;
+
+        Ok(Self { write_authority, verification_level, price_message, posted_slot })
+    }
+}
+
+/// Magic number at the start of every legacy Pyth oracle account.
+pub const LEGACY_MAGIC: u32 = 0xa1b2c3d4;
+
+/// Legacy account layout version handled by [`LegacyPriceAccount`].
+pub const LEGACY_VERSION_2: u32 = 2;
+
+/// Legacy account type of a price account.
+pub const LEGACY_ACCOUNT_TYPE_PRICE: u32 = 3;
+
+/// Trading status of a legacy price account's aggregate price.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceStatus {
+    Unknown,
+    Trading,
+    Halted,
+    Auction,
+    Ignored,
+}
+
+impl PriceStatus {
+    fn from_u32(status: u32) -> Self {
+        match status {
+            1 => Self::Trading,
+            2 => Self::Halted,
+            3 => Self::Auction,
+            4 => Self::Ignored,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Aggregate or previous price of a legacy price account.
+#[derive(Clone, Debug)]
+pub struct LegacyPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub publish_time: i64,
+    pub slot: u64,
+}
+
+/// A decoded legacy (push oracle, layout V2) Pyth price account.
+#[derive(Clone, Debug)]
+pub struct LegacyPriceAccount {
+    pub exponent: i32,
+    pub aggregate: LegacyPrice,
+    pub status: PriceStatus,
+    pub previous: LegacyPrice,
+    pub ema_price: i64,
+    pub ema_conf: i64,
+    pub product: Pubkey,
+}
+
+impl LegacyPriceAccount {
+    pub fn parse(data: &[u8]) -> Result<Self, anyhow::Error> {
+        let mut reader = LeReader::new(data);
+
+        let magic = reader.u32()?;
+        if magic != LEGACY_MAGIC {
+            return Err(anyhow::anyhow!("Bad magic number {:#x}", magic));
+        }
+        let version = reader.u32()?;
+        if version != LEGACY_VERSION_2 {
+            return Err(anyhow::anyhow!("Unsupported account version {}", version));
+        }
+        let account_type = reader.u32()?;
+        if account_type != LEGACY_ACCOUNT_TYPE_PRICE {
+            return Err(anyhow::anyhow!("Account type {} is not a price account", account_type));
+        }
+
+        reader.skip(8)?; // size, price type
+        let exponent = reader.i32()?;
+        reader.skip(8)?; // number of components, number of quoters
+        reader.skip(16)?; // last slot, valid slot
+        let ema_price = reader.i64()?;
+        reader.skip(16)?; // ema price numerator, denominator
+        let ema_conf = reader.i64()?;
+        reader.skip(16)?; // ema confidence numerator, denominator
+        let timestamp = reader.i64()?;
+        reader.skip(8)?; // min publishers, padding
+        let product = Pubkey::new_from_array(reader.array::<32>()?);
+        reader.skip(32)?; // next price account
+        let previous = LegacyPrice {
+            slot: reader.u64()?,
+            price: reader.i64()?,
+            conf: reader.u64()?,
+            publish_time: reader.i64()?,
+        };
+        let price = reader.i64()?;
+        let conf = reader.u64()?;
+        let status = PriceStatus::from_u32(reader.u32()?);
+        reader.skip(4)?; // corporate action
+        let pub_slot = reader.u64()?;
+
+        Ok(Self {
+            exponent,
+            aggregate: LegacyPrice { price, conf, publish_time: timestamp, slot: pub_slot },
+            status,
+            previous,
+            ema_price,
+            ema_conf,
+            product,
+        })
+    }
+
+    /// The aggregate price while trading, otherwise the last price that was.
+    pub fn current_price(&self) -> &LegacyPrice {
+        if self.status == PriceStatus::Trading {
+            &self.aggregate
+        } else {
+            &self.previous
+        }
+    }
+}
+
+/// Little-endian cursor over account data.
+pub(crate) struct LeReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> LeReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], anyhow::Error> {
+        if self.bytes.len() < N {
+            return Err(anyhow::anyhow!("Account data too short: wanted {} bytes, {} left", N, self.bytes.len()));
+        }
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+        let mut out = [0u8; N];
+        out.copy_from_slice(head);
+        Ok(out)
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) -> Result<(), anyhow::Error> {
+        if self.bytes.len() < len {
+            return Err(anyhow::anyhow!("Account data too short: wanted {} bytes, {} left", len, self.bytes.len()));
+        }
+        self.bytes = &self.bytes[len..];
+        Ok(())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, anyhow::Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, anyhow::Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn i32(&mut self) -> Result<i32, anyhow::Error> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, anyhow::Error> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn i64(&mut self) -> Result<i64, anyhow::Error> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, legacy_price_account};
+
+    const GDP_ID: &str = "01a2d2aa5728850767d67e2f82ddc9c8e4c3bbace231461386ef9cbb16d0d36b";
+
+    fn feed_id(hex_id: &str) -> [u8; 32] {
+        hex::decode(hex_id).unwrap().try_into().unwrap()
+    }
+
+    fn price_update(discriminator: [u8; 8], level: &[u8]) -> Vec<u8> {
+        testing::price_update_account(discriminator, level, &feed_id(GDP_ID))
+    }
+
+    fn legacy_trading_account() -> Vec<u8> {
+        legacy_price_account(LEGACY_MAGIC, LEGACY_VERSION_2, LEGACY_ACCOUNT_TYPE_PRICE, 1)
+    }
+
+    #[test]
+    fn derives_published_price_feed_addresses() {
+        for (hex_id, address) in [
+            (GDP_ID, "48mYDzV1JWZo93cheTbg9ikvp3PScDvoTAkWFMWtmmc9"), // ECO.US.GDP
+            ("e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43", "4cSM2e6rvbGQUFiJbqytoVMi5GgghSMr8LwVrT9VPSPo"), // BTC/USD
+            ("ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace", "42amVS4KgzR9rA28tkVYqVXjq9Qa8dcZQMbH5EYFX6XC"), // ETH/USD
+        ] {
+            assert_eq!(price_feed_address(0, &feed_id(hex_id)).to_string(), address);
+        }
+        assert_ne!(price_feed_address(1, &feed_id(GDP_ID)), price_feed_address(0, &feed_id(GDP_ID)));
+    }
+
+    #[test]
+    fn parses_fully_verified_update() {
+        let update = PriceUpdateV2::parse(&price_update(PriceUpdateV2::discriminator(), &[1])).unwrap();
+        assert_eq!(update.write_authority, Pubkey::new_from_array([7u8; 32]));
+        assert_eq!(update.verification_level, VerificationLevel::Full);
+        let message = &update.price_message;
+        assert_eq!(message.feed_id, feed_id(GDP_ID));
+        assert_eq!(message.price, 280_000);
+        assert_eq!(message.conf, 1_000);
+        assert_eq!(message.exponent, -5);
+        assert_eq!(message.publish_time, 1_753_920_000);
+        assert_eq!(message.prev_publish_time, 1_745_971_200);
+        assert_eq!(message.ema_price, 279_000);
+        assert_eq!(message.ema_conf, 1_100);
+        assert_eq!(update.posted_slot, 301_234_567);
+    }
+
+    #[test]
+    fn parses_partially_verified_update() {
+        let update = PriceUpdateV2::parse(&price_update(PriceUpdateV2::discriminator(), &[0, 5])).unwrap();
+        assert_eq!(update.verification_level, VerificationLevel::Partial { num_signatures: 5 });
+        assert_eq!(update.price_message.price, 280_000);
+        assert_eq!(update.posted_slot, 301_234_567);
+    }
+
+    #[test]
+    fn rejects_other_accounts() {
+        let mut discriminator = PriceUpdateV2::discriminator();
+        discriminator[0] ^= 1;
+        let error = PriceUpdateV2::parse(&price_update(discriminator, &[1])).unwrap_err();
+        assert!(error.to_string().contains("discriminator"), "{}", error);
+
+        let error = PriceUpdateV2::parse(&price_update(PriceUpdateV2::discriminator(), &[2])).unwrap_err();
+        assert!(error.to_string().contains("verification level 2"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_truncated_update() {
+        let data = price_update(PriceUpdateV2::discriminator(), &[0, 5]);
+        for len in 0..data.len() {
+            assert!(PriceUpdateV2::parse(&data[..len]).is_err(), "parsed {} of {} bytes", len, data.len());
+        }
+    }
+
+    #[test]
+    fn parses_legacy_price_account() {
+        let account = LegacyPriceAccount::parse(&legacy_trading_account()).unwrap();
+        assert_eq!(account.exponent, -5);
+        assert_eq!(account.aggregate.price, 280_000);
+        assert_eq!(account.aggregate.conf, 1_000);
+        assert_eq!(account.aggregate.publish_time, 1_753_920_005);
+        assert_eq!(account.aggregate.slot, 301_000_000);
+        assert_eq!(account.status, PriceStatus::Trading);
+        assert_eq!(account.previous.price, 275_000);
+        assert_eq!(account.previous.conf, 900);
+        assert_eq!(account.previous.publish_time, 1_745_971_200);
+        assert_eq!(account.previous.slot, 300_999_990);
+        assert_eq!(account.ema_price, 279_500);
+        assert_eq!(account.ema_conf, 1_150);
+        assert_eq!(account.product, Pubkey::new_from_array([11u8; 32]));
+        assert_eq!(account.current_price().price, 280_000);
+    }
+
+    #[test]
+    fn legacy_price_falls_back_to_previous_unless_trading() {
+        for (status, expected) in [
+            (0, PriceStatus::Unknown),
+            (2, PriceStatus::Halted),
+            (3, PriceStatus::Auction),
+            (4, PriceStatus::Ignored),
+            (9, PriceStatus::Unknown),
+        ] {
+            let data = legacy_price_account(LEGACY_MAGIC, LEGACY_VERSION_2, LEGACY_ACCOUNT_TYPE_PRICE, status);
+            let account = LegacyPriceAccount::parse(&data).unwrap();
+            assert_eq!(account.status, expected);
+            assert_eq!(account.current_price().price, 275_000);
+            assert_eq!(account.current_price().publish_time, 1_745_971_200);
+        }
+    }
+
+    #[test]
+    fn rejects_other_legacy_accounts() {
+        for (data, expected) in [
+            (legacy_price_account(0xa1b2c3d5, LEGACY_VERSION_2, LEGACY_ACCOUNT_TYPE_PRICE, 1), "Bad magic number"),
+            (legacy_price_account(LEGACY_MAGIC, 1, LEGACY_ACCOUNT_TYPE_PRICE, 1), "Unsupported account version 1"),
+            (legacy_price_account(LEGACY_MAGIC, LEGACY_VERSION_2, 2, 1), "Account type 2 is not a price account"),
+            (legacy_trading_account()[..239].to_vec(), "Account data too short"),
+        ] {
+            let error = LegacyPriceAccount::parse(&data).unwrap_err();
+            assert!(error.to_string().contains(expected), "{}", error);
+        }
+        // The aggregate ends at byte 240; the publisher components are not read.
+        assert!(LegacyPriceAccount::parse(&legacy_trading_account()[..240]).is_ok());
+    }
+}
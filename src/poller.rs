@@ -0,0 +1,212 @@
+use crate::{cache::PriceCache, config::FeedRegistry, retry, sources::SourceChain, FeedError};
+use serde::Serialize;
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Schedule of the background poller.
+#[derive(Clone, Debug)]
+pub struct PollSchedule {
+    pub interval: Duration,
+    /// Up to this much random delay is added to every wait.
+    pub jitter: Duration,
+    /// Upper bound of the wait after consecutive failed runs.
+    pub max_backoff: Duration,
+    /// Deadline for fetching every feed in one run.
+    pub deadline: Duration,
+}
+
+/// Outcome of the poller's runs, served by `/poller/status`.
+#[derive(Clone, Default, Serialize)]
+pub struct PollerStatus {
+    pub runs: u64,
+    pub last_started: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_finished: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub feeds_ok: usize,
+    pub feeds_failed: usize,
+    pub failed: Vec<FeedError>,
+    /// Runs in a row in which no feed could be fetched.
+    pub consecutive_failures: u32,
+    pub next_run: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Background task that refreshes every registered feed into the cache, so
+/// requests can be answered from memory.
+pub struct Poller {
+    sources: Arc<SourceChain>,
+    registry: Arc<FeedRegistry>,
+    cache: Arc<PriceCache>,
+    schedule: PollSchedule,
+    status: Arc<RwLock<PollerStatus>>,
+}
+
+impl Poller {
+    pub fn new(
+        sources: Arc<SourceChain>,
+        registry: Arc<FeedRegistry>,
+        cache: Arc<PriceCache>,
+        schedule: PollSchedule,
+    ) -> Self {
+        Self { sources, registry, cache, schedule, status: Arc::new(RwLock::new(PollerStatus::default())) }
+    }
+
+    pub fn status(&self) -> Arc<RwLock<PollerStatus>> {
+        self.status.clone()
+    }
+
+    /// Starts polling immediately, then after every interval.
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let failures = self.run_once().await;
+                let wait = self.next_wait(failures);
+                self.status.write().unwrap().next_run =
+                    chrono::Duration::from_std(wait).ok().map(|wait| chrono::Utc::now() + wait);
+                tokio::time::sleep(wait).await;
+            }
+        })
+    }
+
+    /// Fetches every feed once and returns the number of consecutive failed runs.
+    async fn run_once(&self) -> u32 {
+        let started = Instant::now();
+        let started_at = chrono::Utc::now();
+        self.status.write().unwrap().last_started = Some(started_at);
+
+        let feeds: Vec<_> = self.registry.feeds().iter().collect();
+        let results = self.sources.fetch_many(&feeds, started + self.schedule.deadline).await;
+
+        let mut feeds_ok = 0;
+        let mut failed = Vec::new();
+        for (feed, result) in feeds.iter().zip(results) {
+            match result {
+                Ok(data) => {
+                    self.cache.insert(feed, data);
+                    feeds_ok += 1;
+                }
+                Err(e) => failed.push(FeedError {
+                    symbol: feed.symbol.clone(),
+                    price_feed_id: feed.id.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        let mut status = self.status.write().unwrap();
+        status.runs += 1;
+        status.last_finished = Some(chrono::Utc::now());
+        status.last_duration_ms = Some(started.elapsed().as_millis() as u64);
+        status.feeds_ok = feeds_ok;
+        status.feeds_failed = failed.len();
+        status.failed = failed;
+        if feeds_ok == 0 {
+            status.consecutive_failures += 1;
+            warn!("Poller run {} fetched no feeds ({} in a row)", status.runs, status.consecutive_failures);
+        } else {
+            status.consecutive_failures = 0;
+            info!("Poller run {} refreshed {} feeds ({} failed)", status.runs, feeds_ok, status.feeds_failed);
+        }
+        status.consecutive_failures
+    }
+
+    /// The interval, doubled for every consecutive failed run up to
+    /// `max_backoff`, plus random jitter.
+    fn next_wait(&self, failures: u32) -> Duration {
+        retry::backoff(self.schedule.interval, self.schedule.max_backoff, failures) + retry::jitter(self.schedule.jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sources::PriceSource,
+        testing::{self, Outcome, StubSource},
+    };
+
+    fn schedule(jitter_secs: u64) -> PollSchedule {
+        PollSchedule {
+            interval: Duration::from_secs(10),
+            jitter: Duration::from_secs(jitter_secs),
+            max_backoff: Duration::from_secs(60),
+            deadline: Duration::from_secs(5),
+        }
+    }
+
+    fn poller(source: &Arc<StubSource>, schedule: PollSchedule) -> (Poller, Arc<FeedRegistry>, Arc<PriceCache>) {
+        let registry = Arc::new(testing::feed_registry(&[("GDP", None), ("CPI", None)]));
+        let available: Vec<Arc<dyn PriceSource>> = vec![source.clone()];
+        let chain = SourceChain::new(available, &["a".to_string()], &[], &registry).unwrap();
+        let cache = Arc::new(PriceCache::new(Duration::from_secs(60), Duration::ZERO, 16, 0));
+        (Poller::new(Arc::new(chain), registry.clone(), cache.clone(), schedule), registry, cache)
+    }
+
+    #[tokio::test]
+    async fn fills_the_cache_and_reports_failed_feeds() {
+        let source = Arc::new(StubSource::new("a", Outcome::Price(280_000)).with("CPI", Outcome::Fail));
+        let (poller, registry, cache) = poller(&source, schedule(0));
+
+        assert_eq!(poller.run_once().await, 0);
+        let status = poller.status().read().unwrap().clone();
+        assert_eq!((status.runs, status.feeds_ok, status.feeds_failed), (1, 1, 1));
+        assert_eq!(status.failed.len(), 1);
+        assert_eq!(status.failed[0].symbol, "CPI");
+        assert_eq!(status.failed[0].error, "Failed to fetch CPI from all sources: a: a is down");
+        assert!(status.last_started.is_some() && status.last_finished.is_some());
+
+        let gdp = cache.latest(registry.resolve("GDP").unwrap()).unwrap();
+        assert_eq!((gdp.source.as_str(), gdp.exact.price), ("a (cached)", 280_000));
+        assert!(cache.latest(registry.resolve("CPI").unwrap()).is_none());
+    }
+
+    #[tokio::test]
+    async fn counts_consecutive_failed_runs() {
+        let source = Arc::new(StubSource::new("a", Outcome::Fail));
+        let (poller, _, _) = poller(&source, schedule(0));
+
+        assert_eq!(poller.run_once().await, 1);
+        assert_eq!(poller.run_once().await, 2);
+        let status = poller.status().read().unwrap().clone();
+        assert_eq!((status.consecutive_failures, status.feeds_ok, status.failed.len()), (2, 0, 2));
+
+        // One fetched feed is enough to reset the count.
+        source.set_default(Outcome::Price(1));
+        assert_eq!(poller.run_once().await, 0);
+        let status = poller.status().read().unwrap().clone();
+        assert_eq!((status.runs, status.consecutive_failures, status.feeds_ok), (3, 0, 2));
+        assert!(status.failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn backs_off_after_failed_runs() {
+        let source = Arc::new(StubSource::new("a", Outcome::Fail));
+        let (poller, _, _) = poller(&source, schedule(0));
+        let waits: Vec<u64> = (0..5).map(|failures| poller.next_wait(failures).as_secs()).collect();
+        assert_eq!(waits, [10, 20, 40, 60, 60]);
+
+        let (jittered, _, _) = self::poller(&source, schedule(2));
+        for _ in 0..20 {
+            let wait = jittered.next_wait(0);
+            assert!(wait >= Duration::from_secs(10) && wait <= Duration::from_secs(12), "{:?}", wait);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polls_on_its_schedule() {
+        let source = Arc::new(StubSource::new("a", Outcome::Price(1)));
+        let (poller, _, _) = poller(&source, schedule(0));
+        let status = poller.status();
+        let task = poller.spawn();
+
+        // Runs at once, then every 10 seconds.
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert_eq!(status.read().unwrap().runs, 3);
+        assert!(status.read().unwrap().next_run.is_some());
+        assert_eq!(source.batches().len(), 3);
+        task.abort();
+    }
+}
@@ -0,0 +1,173 @@
+//! Exact fixed-point prices as published by Pyth: `mantissa × 10^expo`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Largest exponent magnitude accepted from upstream. Pyth feeds use a few
+/// digits at most; the decimal rendering grows with the exponent, so a corrupt
+/// account or payload must not get that far.
+pub const MAX_EXPONENT: i32 = 18;
+
+/// A Pyth price and confidence interval as raw integers sharing one exponent.
+///
+/// Serialized as the exact decimal strings (`price_exact`,
+/// `confidence_exact`) next to the raw fields (`price_mantissa`,
+/// `confidence_mantissa`, `exponent`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "ExactPriceJson", try_from = "ExactPriceJson")]
+pub struct ExactPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+}
+
+impl ExactPrice {
+    /// Fails if `expo` is outside `±MAX_EXPONENT`.
+    pub fn new(price: i64, conf: u64, expo: i32) -> Result<Self, anyhow::Error> {
+        if !(-MAX_EXPONENT..=MAX_EXPONENT).contains(&expo) {
+            return Err(anyhow::anyhow!("Exponent {} is outside ±{}", expo, MAX_EXPONENT));
+        }
+        Ok(Self { price, conf, expo })
+    }
+
+    pub fn price(&self) -> Decimal {
+        Decimal::new(self.price.into(), self.expo)
+    }
+
+    pub fn conf(&self) -> Decimal {
+        Decimal::new(self.conf.into(), self.expo)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExactPriceJson {
+    price_exact: String,
+    confidence_exact: String,
+    price_mantissa: i64,
+    confidence_mantissa: u64,
+    exponent: i32,
+}
+
+impl From<ExactPrice> for ExactPriceJson {
+    fn from(exact: ExactPrice) -> Self {
+        Self {
+            price_exact: exact.price().to_string(),
+            confidence_exact: exact.conf().to_string(),
+            price_mantissa: exact.price,
+            confidence_mantissa: exact.conf,
+            exponent: exact.expo,
+        }
+    }
+}
+
+impl TryFrom<ExactPriceJson> for ExactPrice {
+    type Error = anyhow::Error;
+
+    fn try_from(json: ExactPriceJson) -> Result<Self, Self::Error> {
+        Self::new(json.price_mantissa, json.confidence_mantissa, json.exponent)
+    }
+}
+
+/// A decimal number `mantissa × 10^expo`, rendered without loss of precision.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decimal {
+    mantissa: i128,
+    expo: i32,
+}
+
+impl Decimal {
+    pub fn new(mantissa: i128, expo: i32) -> Self {
+        Self { mantissa, expo }
+    }
+
+    /// Nearest `f64`, rounded once from the exact decimal.
+    pub fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+
+        if self.expo >= 0 {
+            let zeros = if self.mantissa == 0 { 0 } else { self.expo as usize };
+            return write!(f, "{}{}{}", sign, digits, "0".repeat(zeros));
+        }
+
+        let scale = self.expo.unsigned_abs() as usize;
+        if digits.len() > scale {
+            let (int, frac) = digits.split_at(digits.len() - scale);
+            write!(f, "{}{}.{}", sign, int, frac)
+        } else {
+            write!(f, "{}0.{}{}", sign, "0".repeat(scale - digits.len()), digits)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_exact_decimals() {
+        for (mantissa, expo, expected) in [
+            (280_000, -5, "2.80000"),
+            (-280_000, -5, "-2.80000"),
+            (-5, -8, "-0.00000005"),
+            (5, -8, "0.00000005"),
+            (123, -3, "0.123"),
+            (-123, -3, "-0.123"),
+            (1234, -3, "1.234"),
+            (0, -3, "0.000"),
+            (42, 0, "42"),
+            (-42, 0, "-42"),
+            (0, 0, "0"),
+            (42, 3, "42000"),
+            (-42, 3, "-42000"),
+            (0, 3, "0"),
+            (i64::MIN as i128, -2, "-92233720368547758.08"),
+        ] {
+            assert_eq!(Decimal::new(mantissa, expo).to_string(), expected, "{} × 10^{}", mantissa, expo);
+        }
+    }
+
+    #[test]
+    fn converts_to_nearest_f64() {
+        assert_eq!(Decimal::new(280_000, -5).to_f64(), 2.8);
+        assert_eq!(Decimal::new(-5, -8).to_f64(), -5e-8);
+        assert_eq!(Decimal::new(-42, 0).to_f64(), -42.0);
+        assert_eq!(Decimal::new(42, 3).to_f64(), 42_000.0);
+        // `3.0 * 0.1` is 0.30000000000000004; rounding the exact decimal once is not.
+        assert_eq!(Decimal::new(3, -1).to_f64(), 0.3);
+    }
+
+    #[test]
+    fn rejects_out_of_range_exponents() {
+        assert!(ExactPrice::new(1, 1, MAX_EXPONENT).is_ok());
+        assert!(ExactPrice::new(1, 1, -MAX_EXPONENT).is_ok());
+        for expo in [MAX_EXPONENT + 1, -MAX_EXPONENT - 1, i32::MAX, i32::MIN] {
+            let error = ExactPrice::new(1, 1, expo).unwrap_err();
+            assert!(error.to_string().contains("outside"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let exact = ExactPrice::new(-5, 2, -8).unwrap();
+        let json = serde_json::to_value(exact).unwrap();
+        assert_eq!(json["price_exact"], "-0.00000005");
+        assert_eq!(json["confidence_exact"], "0.00000002");
+        assert_eq!(serde_json::from_value::<ExactPrice>(json).unwrap(), exact);
+
+        let json = serde_json::json!({
+            "price_exact": "1",
+            "confidence_exact": "1",
+            "price_mantissa": 1,
+            "confidence_mantissa": 1,
+            "exponent": 1_000_000_000,
+        });
+        assert!(serde_json::from_value::<ExactPrice>(json).is_err());
+    }
+}
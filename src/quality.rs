@@ -0,0 +1,243 @@
+use crate::{
+    config::{Feed, QualityAction, QualitySettings},
+    PriceData,
+};
+use std::time::Duration;
+use tracing::warn;
+
+/// Staleness and confidence limits applied to every price a source returns.
+#[derive(Clone, Debug, Default)]
+pub struct QualityPolicy {
+    action: QualityAction,
+    max_age: Option<Duration>,
+    max_confidence_ratio: Option<f64>,
+}
+
+impl QualityPolicy {
+    pub fn new(settings: &QualitySettings) -> Result<Self, anyhow::Error> {
+        if let Some(ratio) = settings.max_confidence_ratio
+            && (ratio.is_nan() || ratio < 0.0)
+        {
+            return Err(anyhow::anyhow!("[quality].max_confidence_ratio must be non-negative"));
+        }
+
+        Ok(Self {
+            action: settings.action,
+            max_age: settings.max_age_secs.map(Duration::from_secs),
+            max_confidence_ratio: settings.max_confidence_ratio,
+        })
+    }
+
+    /// Sets `stale` and `low_confidence` on `data` against the feed's limits.
+    /// With [`QualityAction::Reject`] a price that fails either check is an
+    /// error instead, so the next source is tried.
+    pub fn apply(&self, feed: &Feed, data: &mut PriceData) -> Result<(), anyhow::Error> {
+        let problems = self.check(feed, data);
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        match self.action {
+            QualityAction::Flag => {
+                warn!("{} price from {} flagged: {}", feed.symbol, data.source, problems.join(", "));
+                Ok(())
+            }
+            QualityAction::Reject => Err(anyhow::anyhow!("price rejected: {}", problems.join(", "))),
+        }
+    }
+
+    /// [`apply`](Self::apply) again on a price that was accepted earlier,
+    /// e.g. the last one the poller stored: its age has grown since. Flagging
+    /// is not logged, as this runs on every request.
+    pub fn recheck(&self, feed: &Feed, data: &mut PriceData) -> Result<(), anyhow::Error> {
+        let problems = self.check(feed, data);
+        if problems.is_empty() || self.action == QualityAction::Flag {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!("price rejected: {}", problems.join(", ")))
+    }
+
+    /// Sets the flags on `data` and describes each failed check.
+    fn check(&self, feed: &Feed, data: &mut PriceData) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Some(max_age) = feed.max_age.or(self.max_age) {
+            let age = chrono::Utc::now().timestamp().saturating_sub(data.publish_time);
+            data.stale = age > max_age.as_secs() as i64;
+            if data.stale {
+                problems.push(format!("published {}s ago (max {}s)", age, max_age.as_secs()));
+            }
+        }
+
+        if let Some(max_ratio) = feed.max_confidence_ratio.or(self.max_confidence_ratio) {
+            let ratio = confidence_ratio(data);
+            data.low_confidence = ratio > max_ratio;
+            if data.low_confidence {
+                problems.push(format!("confidence ratio {:.4} (max {})", ratio, max_ratio));
+            }
+        }
+
+        problems
+    }
+}
+
+/// `confidence / |price|`, computed on the raw integers; infinite for a zero
+/// price with a non-zero confidence.
+fn confidence_ratio(data: &PriceData) -> f64 {
+    let price = data.exact.price.unsigned_abs();
+    let conf = data.exact.conf;
+    match (price, conf) {
+        (_, 0) => 0.0,
+        (0, _) => f64::INFINITY,
+        (price, conf) => conf as f64 / price as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn policy(action: QualityAction, max_age_secs: Option<u64>, max_confidence_ratio: Option<f64>) -> QualityPolicy {
+        QualityPolicy::new(&QualitySettings { action, max_age_secs, max_confidence_ratio }).unwrap()
+    }
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+
+    #[test]
+    fn flags_stale_and_uncertain_prices() {
+        let feed = testing::feed("ECO.US.GDPQ125");
+        let policy = policy(QualityAction::Flag, Some(60), Some(0.01));
+
+        let mut fresh = testing::price(&feed, 280_000, 1_000, -5, now() - 10);
+        policy.apply(&feed, &mut fresh).unwrap();
+        assert!(!fresh.stale && !fresh.low_confidence);
+
+        let mut flagged = testing::price(&feed, 280_000, 5_000, -5, now() - 600);
+        policy.apply(&feed, &mut flagged).unwrap();
+        assert!(flagged.stale && flagged.low_confidence);
+    }
+
+    #[test]
+    fn rejects_stale_and_uncertain_prices() {
+        let feed = testing::feed("ECO.US.GDPQ125");
+        let policy = policy(QualityAction::Reject, Some(60), Some(0.01));
+
+        let mut fresh = testing::price(&feed, 280_000, 1_000, -5, now() - 10);
+        policy.apply(&feed, &mut fresh).unwrap();
+
+        let error = policy.apply(&feed, &mut testing::price(&feed, 280_000, 1_000, -5, now() - 600)).unwrap_err();
+        assert!(error.to_string().contains("(max 60s)"), "{}", error);
+
+        let error = policy.apply(&feed, &mut testing::price(&feed, 280_000, 5_000, -5, now())).unwrap_err();
+        assert!(error.to_string().contains("confidence ratio 0.0179 (max 0.01)"), "{}", error);
+    }
+
+    #[test]
+    fn per_feed_limits_override_global_ones() {
+        let mut feed = testing::feed("ECO.US.GDPQ125");
+        feed.max_age = Some(Duration::from_secs(3_600));
+        feed.max_confidence_ratio = Some(0.1);
+        let policy = policy(QualityAction::Reject, Some(60), Some(0.01));
+
+        // Over the global limits, within the feed's.
+        let mut data = testing::price(&feed, 280_000, 5_000, -5, now() - 600);
+        policy.apply(&feed, &mut data).unwrap();
+        assert!(!data.stale && !data.low_confidence);
+
+        // A stricter per-feed limit wins too.
+        feed.max_age = Some(Duration::from_secs(5));
+        assert!(policy.apply(&feed, &mut testing::price(&feed, 280_000, 1_000, -5, now() - 30)).is_err());
+    }
+
+    #[test]
+    fn without_limits_nothing_is_checked() {
+        let feed = testing::feed("ECO.US.GDPQ125");
+        let mut data = testing::price(&feed, 0, 5_000, -5, 0);
+        policy(QualityAction::Reject, None, None).apply(&feed, &mut data).unwrap();
+        assert!(!data.stale && !data.low_confidence);
+    }
+
+    #[test]
+    fn confidence_ratio_handles_extreme_prices() {
+        let feed = testing::feed("ECO.US.GDPQ125");
+        assert_eq!(confidence_ratio(&testing::price(&feed, 0, 0, -5, 0)), 0.0);
+        assert_eq!(confidence_ratio(&testing::price(&feed, 0, 1, -5, 0)), f64::INFINITY);
+        assert_eq!(confidence_ratio(&testing::price(&feed, -200, 50, -5, 0)), 0.25);
+        assert_eq!(confidence_ratio(&testing::price(&feed, i64::MIN, 1 << 62, -5, 0)), 0.5);
+
+        // A zero price with any confidence is maximally uncertain.
+        let policy = policy(QualityAction::Flag, None, Some(1_000.0));
+        let mut data = testing::price(&feed, 0, 1, -5, now());
+        policy.apply(&feed, &mut data).unwrap();
+        assert!(data.low_confidence);
+
+        let mut data = testing::price(&feed, i64::MIN, u64::MAX, -5, now());
+        policy.apply(&feed, &mut data).unwrap();
+        assert!(!data.low_confidence);
+    }
+
+    #[test]
+    fn future_publish_time_is_not_stale() {
+        let feed = testing::feed("ECO.US.GDPQ125");
+        let mut data = testing::price(&feed, 280_000, 1_000, -5, now() + 3_600);
+        policy(QualityAction::Reject, Some(60), None).apply(&feed, &mut data).unwrap();
+        assert!(!data.stale);
+
+        let mut data = testing::price(&feed, 280_000, 1_000, -5, i64::MAX);
+        policy(QualityAction::Reject, Some(60), None).apply(&feed, &mut data).unwrap();
+        let mut data = testing::price(&feed, 280_000, 1_000, -5, i64::MIN);
+        assert!(policy(QualityAction::Reject, Some(60), None).apply(&feed, &mut data).is_err());
+    }
+
+    #[test]
+    fn recheck_catches_prices_that_aged() {
+        let feed = testing::feed("ECO.US.GDPQ125");
+        let mut data = testing::price(&feed, 280_000, 1_000, -5, now() - 50);
+        policy(QualityAction::Flag, Some(60), None).apply(&feed, &mut data).unwrap();
+        assert!(!data.stale);
+
+        // The same price, 30 seconds later.
+        data.publish_time -= 30;
+        let mut flagged = data.clone();
+        policy(QualityAction::Flag, Some(60), None).recheck(&feed, &mut flagged).unwrap();
+        assert!(flagged.stale);
+
+        let error = policy(QualityAction::Reject, Some(60), None).recheck(&feed, &mut data).unwrap_err();
+        assert!(error.to_string().contains("(max 60s)"), "{}", error);
+    }
+
+    #[test]
+    fn uses_the_configured_action() {
+        let settings: QualitySettings = toml::from_str("action = \"reject\"\nmax_age_secs = 60\n").unwrap();
+        let policy = QualityPolicy::new(&settings).unwrap();
+        let feed = testing::feed("ECO.US.GDPQ125");
+
+        let error = policy.apply(&feed, &mut testing::price(&feed, 280_000, 1_000, -5, now() - 600)).unwrap_err();
+        assert!(error.to_string().starts_with("price rejected:"), "{}", error);
+    }
+
+    #[test]
+    fn default_limits_fit_the_release_cadence() {
+        let config = crate::config::Config::from_toml(include_str!("../config/default.toml")).unwrap();
+        let policy = QualityPolicy::new(&config.quality).unwrap();
+        let day = 86_400;
+
+        // The headline moves with every release, at most a quarter apart.
+        let headline = config.registry.headline();
+        let mut recent = testing::price(headline, 280_000, 1_000, -5, now() - 60 * day);
+        policy.apply(headline, &mut recent).unwrap();
+        assert!(!recent.stale);
+        let mut missed = testing::price(headline, 280_000, 1_000, -5, now() - 120 * day);
+        policy.apply(headline, &mut missed).unwrap();
+        assert!(missed.stale);
+
+        // A past quarter's print is final and never goes stale.
+        let quarter = config.registry.resolve("ECO.US.GDPQ120").unwrap();
+        let mut settled = testing::price(quarter, 280_000, 1_000, -5, now() - 5 * 365 * day);
+        policy.apply(quarter, &mut settled).unwrap();
+        assert!(!settled.stale);
+    }
+}
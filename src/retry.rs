@@ -0,0 +1,44 @@
+//! Backoff between retries of the poller, the Hermes subscriber and the
+//! account watcher.
+
+use rand::Rng;
+use std::time::Duration;
+
+/// `min` doubled for every consecutive failure, up to `max` (or `min` when
+/// `max` is smaller).
+pub fn backoff(min: Duration, max: Duration, failures: u32) -> Duration {
+    min.saturating_mul(2u32.saturating_pow(failures)).min(max.max(min))
+}
+
+/// A random wait of up to `max`, spreading out retries that would otherwise
+/// fire together.
+pub fn jitter(max: Duration) -> Duration {
+    let max_ms = max.as_millis() as u64;
+    Duration::from_millis(if max_ms == 0 { 0 } else { rand::thread_rng().gen_range(0..=max_ms) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_up_to_max() {
+        let (min, max) = (Duration::from_secs(1), Duration::from_secs(60));
+        let waits: Vec<u64> = (0..8).map(|failures| backoff(min, max, failures).as_secs()).collect();
+        assert_eq!(waits, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff(min, max, u32::MAX), max);
+    }
+
+    #[test]
+    fn max_below_min_keeps_min() {
+        assert_eq!(backoff(Duration::from_secs(30), Duration::from_secs(5), 3), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn jitter_stays_in_range() {
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(jitter(Duration::from_millis(500)) <= Duration::from_millis(500));
+        }
+    }
+}
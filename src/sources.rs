@@ -0,0 +1,1179 @@
+use crate::{
+    accumulator::{PriceFeedMessage, UpdateVerifier},
+    config::{self, Feed, FeedRegistry, Upstreams, VerificationMode},
+    hermes::{HermesClient, ParsedPriceUpdate, PriceUpdates, RpcPrice},
+    metrics::Metrics,
+    oracle::{self, LegacyPriceAccount, PriceStatus, PriceUpdateV2, VerificationLevel},
+    price::{Decimal, ExactPrice},
+    quality::QualityPolicy,
+    storage::{Observation, ObservationStore},
+    PriceData,
+};
+use async_trait::async_trait;
+use futures::{future, stream, StreamExt};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+/// A backend that can produce a price for a registered feed.
+///
+/// Sources are tried in the order configured under `[sources]`; implement this
+/// trait and register the source in `main` to add a new backend to the chain.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Name used to refer to the source in the config file, e.g. `"hermes"`.
+    fn name(&self) -> &'static str;
+
+    /// Human-readable name used in logs.
+    fn label(&self) -> &'static str;
+
+    async fn fetch(&self, feed: &Feed) -> Result<PriceData, anyhow::Error>;
+
+    /// Fetches several feeds at once, returning one result per feed in the
+    /// same order, with at most `concurrency` upstream requests in flight.
+    /// Sources that support batched reads override this.
+    async fn fetch_many(&self, feeds: &[&Feed], concurrency: usize) -> Vec<Result<PriceData, anyhow::Error>> {
+        let fetches: Vec<_> = feeds.iter().map(|feed| self.fetch(feed)).collect();
+        stream::iter(fetches).buffered(concurrency.max(1)).collect().await
+    }
+}
+
+/// Maximum number of accounts per `getMultipleAccounts` call.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Reads accounts with `getMultipleAccounts` in chunks of
+/// [`MAX_MULTIPLE_ACCOUNTS`], up to `concurrency` chunks at a time. A failed
+/// chunk fails only its own accounts.
+async fn get_accounts_chunked(
+    client: &RpcClient,
+    upstreams: &Upstreams,
+    pubkeys: &[Pubkey],
+    concurrency: usize,
+) -> Vec<Result<Option<Account>, anyhow::Error>> {
+    let reads: Vec<_> = pubkeys
+        .chunks(MAX_MULTIPLE_ACCOUNTS)
+        .map(|chunk| async move {
+            match client.get_multiple_accounts(chunk).await {
+                Ok(accounts) => accounts.into_iter().map(Ok).collect(),
+                Err(e) => {
+                    let e = upstreams.scrub(&e.to_string());
+                    warn!("getMultipleAccounts failed for {} accounts: {}", chunk.len(), e);
+                    chunk.iter().map(|_| Err(anyhow::anyhow!("RPC request failed: {}", e))).collect()
+                }
+            }
+        })
+        .collect();
+    let chunks: Vec<Vec<Result<Option<Account>, anyhow::Error>>> =
+        stream::iter(reads).buffered(concurrency.max(1)).collect().await;
+
+    chunks.into_iter().flatten().collect()
+}
+
+/// Decodes a fetched account into a price for the feed.
+type AccountDecoder = fn(&Feed, &Pubkey, &Account, &str) -> Result<PriceData, anyhow::Error>;
+
+/// Reads the account of each feed (`None` where the feed has none) and
+/// decodes it with `decode`.
+async fn fetch_accounts(
+    client: &RpcClient,
+    upstreams: &Upstreams,
+    feeds: &[&Feed],
+    pubkeys: Vec<Option<Pubkey>>,
+    decode: AccountDecoder,
+    source: &str,
+    concurrency: usize,
+) -> Vec<Result<PriceData, anyhow::Error>> {
+    let lookups: Vec<Pubkey> = pubkeys.iter().flatten().copied().collect();
+    let mut accounts = get_accounts_chunked(client, upstreams, &lookups, concurrency).await.into_iter();
+
+    feeds
+        .iter()
+        .zip(pubkeys)
+        .map(|(feed, pubkey)| {
+            let pubkey = pubkey
+                .ok_or_else(|| anyhow::anyhow!("No on-chain account configured for {}", feed.symbol))?;
+            match accounts.next() {
+                Some(Ok(Some(account))) => {
+                    info!("Found {} feed account {} ({} bytes, owner: {})", feed.symbol, pubkey, account.data.len(), account.owner);
+                    decode(feed, &pubkey, &account, source)
+                }
+                Some(Ok(None)) => {
+                    info!("{} feed account {} not found", feed.symbol, pubkey);
+                    Err(anyhow::anyhow!("{} feed account {} not found", feed.symbol, pubkey))
+                }
+                Some(Err(e)) => Err(e),
+                None => Err(anyhow::anyhow!("Missing RPC result for {}", pubkey)),
+            }
+        })
+        .collect()
+}
+
+/// Decodes a legacy V2 Pyth price account. While the feed is not trading the
+/// last trading price is reported, along with the current status.
+fn decode_legacy_price_account(feed: &Feed, pubkey: &Pubkey, account: &Account, source: &str) -> Result<PriceData, anyhow::Error> {
+    let price_account = LegacyPriceAccount::parse(&account.data).map_err(|e| {
+        warn!("Account exists but not a valid Pyth price account: {}", e);
+        anyhow::anyhow!("Account {} is not a valid Pyth price account: {}", pubkey, e)
+    })?;
+    if price_account.status != PriceStatus::Trading {
+        warn!("{} price account {} is {:?}, using the last trading price", feed.symbol, pubkey, price_account.status);
+    }
+
+    let price = price_account.current_price();
+    let exact = ExactPrice::new(price.price, price.conf, price_account.exponent)
+        .map_err(|e| anyhow::anyhow!("Account {} has an invalid price: {}", pubkey, e))?;
+    let price_value = exact.price().to_f64();
+    let confidence = exact.conf().to_f64();
+
+    info!("Successfully parsed REAL data for {} from {}: {:.2} (±{:.2}), product {}", feed.symbol, source, price_value, confidence, price_account.product);
+
+    Ok(PriceData {
+        symbol: feed.symbol.clone(),
+        price: price_value,
+        confidence,
+        exact,
+        publish_time: price.publish_time,
+        price_feed_id: feed.id.clone(),
+        last_updated: chrono::Utc::now(),
+        source: source.to_string(),
+        description: feed.description.clone(),
+        unit: feed.unit.clone(),
+        ema_price: Some(Decimal::new(price_account.ema_price.into(), price_account.exponent).to_f64()),
+        ema_confidence: Some(Decimal::new(price_account.ema_conf.into(), price_account.exponent).to_f64()),
+        slot: Some(price.slot),
+        prev_publish_time: Some(price_account.previous.publish_time),
+        update_data: None,
+        verified: false,
+        status: Some(price_account.status),
+        stale: false,
+        low_confidence: false,
+        metadata: feed.metadata.clone(),
+    })
+}
+
+/// Decodes a pull-oracle `PriceUpdateV2` account owned by the Pyth receiver.
+/// Only updates whose VAA the receiver fully verified are marked `verified`.
+fn decode_price_update(feed: &Feed, pubkey: &Pubkey, account: &Account, source: &str) -> Result<PriceData, anyhow::Error> {
+    if account.owner.to_string() != oracle::PYTH_RECEIVER_PROGRAM {
+        return Err(anyhow::anyhow!("Account {} is owned by {}, not the Pyth receiver", pubkey, account.owner));
+    }
+
+    let update = PriceUpdateV2::parse(&account.data)
+        .map_err(|e| anyhow::anyhow!("Account {} is not a valid PriceUpdateV2: {}", pubkey, e))?;
+    let message = &update.price_message;
+    if message.feed_id != feed.id_bytes {
+        return Err(anyhow::anyhow!("Account {} holds feed 0x{}, expected {}", pubkey, hex::encode(message.feed_id), feed.id));
+    }
+    if let VerificationLevel::Partial { num_signatures } = update.verification_level {
+        warn!("{} update in {} is only partially verified ({} signatures)", feed.symbol, pubkey, num_signatures);
+    }
+
+    let exact = ExactPrice::new(message.price, message.conf, message.exponent)
+        .map_err(|e| anyhow::anyhow!("Account {} has an invalid price: {}", pubkey, e))?;
+    let price_value = exact.price().to_f64();
+    let confidence = exact.conf().to_f64();
+
+    info!("Successfully parsed REAL data for {} from {}: {:.2} (±{:.2}), posted at slot {} by {}", feed.symbol, source, price_value, confidence, update.posted_slot, update.write_authority);
+
+    Ok(PriceData {
+        symbol: feed.symbol.clone(),
+        price: price_value,
+        confidence,
+        exact,
+        publish_time: message.publish_time,
+        price_feed_id: feed.id.clone(),
+        last_updated: chrono::Utc::now(),
+        source: source.to_string(),
+        description: feed.description.clone(),
+        unit: feed.unit.clone(),
+        ema_price: Some(Decimal::new(message.ema_price.into(), message.exponent).to_f64()),
+        ema_confidence: Some(Decimal::new(message.ema_conf.into(), message.exponent).to_f64()),
+        slot: Some(update.posted_slot),
+        prev_publish_time: Some(message.prev_publish_time),
+        update_data: None,
+        verified: update.verification_level == VerificationLevel::Full,
+        status: None,
+        stale: false,
+        low_confidence: false,
+        metadata: feed.metadata.clone(),
+    })
+}
+
+/// Decodes a feed account read on Solana by its owner: a `PriceUpdateV2`
+/// owned by the receiver, such as a push-oracle price-feed PDA, or a legacy
+/// price account owned by the oracle program.
+fn decode_solana_account(feed: &Feed, pubkey: &Pubkey, account: &Account, source: &str) -> Result<PriceData, anyhow::Error> {
+    match account.owner.to_string().as_str() {
+        oracle::PYTH_RECEIVER_PROGRAM => decode_price_update(feed, pubkey, account, source),
+        oracle::PYTH_ORACLE_PROGRAM => decode_legacy_price_account(feed, pubkey, account, source),
+        _ => Err(anyhow::anyhow!(
+            "Account {} is owned by {}, neither the Pyth receiver nor the Pyth oracle",
+            pubkey,
+            account.owner
+        )),
+    }
+}
+
+/// An on-chain source whose feed accounts can be watched with
+/// `accountSubscribe` instead of being read on request.
+pub trait AccountSource: PriceSource {
+    /// PubSub endpoint of the source's RPC node.
+    fn ws_endpoint(&self) -> String;
+
+    /// The account holding `feed`'s price, if the source has one for it.
+    fn account(&self, feed: &Feed) -> Option<Pubkey>;
+
+    fn decode(&self, feed: &Feed, pubkey: &Pubkey, account: &Account) -> Result<PriceData, anyhow::Error>;
+}
+
+pub struct SonicSource {
+    client: Arc<RpcClient>,
+    upstreams: Arc<Upstreams>,
+}
+
+impl SonicSource {
+    pub fn new(client: Arc<RpcClient>, upstreams: Arc<Upstreams>) -> Self {
+        Self { client, upstreams }
+    }
+}
+
+#[async_trait]
+impl PriceSource for SonicSource {
+    fn name(&self) -> &'static str {
+        "sonic"
+    }
+
+    fn label(&self) -> &'static str {
+        "Sonic SVM"
+    }
+
+    async fn fetch(&self, feed: &Feed) -> Result<PriceData, anyhow::Error> {
+        self.fetch_many(&[feed], 1).await.remove(0)
+    }
+
+    async fn fetch_many(&self, feeds: &[&Feed], concurrency: usize) -> Vec<Result<PriceData, anyhow::Error>> {
+        info!("Fetching {} feeds through Sonic SVM Pyth Programs", feeds.len());
+        info!("Using Pyth Receiver: {}", oracle::PYTH_RECEIVER_PROGRAM);
+        info!("Using Pyth Price Feed: {} (shard {})", oracle::PYTH_PUSH_ORACLE_PROGRAM, self.upstreams.price_feed_shard);
+
+        let pubkeys = feeds.iter().map(|feed| self.account(feed)).collect();
+
+        fetch_accounts(&self.client, &self.upstreams, feeds, pubkeys, decode_price_update, "Sonic SVM Direct Account", concurrency).await
+    }
+}
+
+impl AccountSource for SonicSource {
+    fn ws_endpoint(&self) -> String {
+        self.upstreams.sonic_ws_url.clone()
+    }
+
+    fn account(&self, feed: &Feed) -> Option<Pubkey> {
+        Some(oracle::price_feed_address(self.upstreams.price_feed_shard, &feed.id_bytes))
+    }
+
+    fn decode(&self, feed: &Feed, pubkey: &Pubkey, account: &Account) -> Result<PriceData, anyhow::Error> {
+        decode_price_update(feed, pubkey, account, "Sonic SVM Direct Account")
+    }
+}
+
+pub struct SolanaSource {
+    client: Arc<RpcClient>,
+    upstreams: Arc<Upstreams>,
+}
+
+impl SolanaSource {
+    pub fn new(client: Arc<RpcClient>, upstreams: Arc<Upstreams>) -> Self {
+        Self { client, upstreams }
+    }
+}
+
+#[async_trait]
+impl PriceSource for SolanaSource {
+    fn name(&self) -> &'static str {
+        "solana"
+    }
+
+    fn label(&self) -> &'static str {
+        "Solana RPC"
+    }
+
+    async fn fetch(&self, feed: &Feed) -> Result<PriceData, anyhow::Error> {
+        self.fetch_many(&[feed], 1).await.remove(0)
+    }
+
+    async fn fetch_many(&self, feeds: &[&Feed], concurrency: usize) -> Vec<Result<PriceData, anyhow::Error>> {
+        info!("Fetching {} feeds from Solana RPC (Helius)", feeds.len());
+
+        let pubkeys = feeds.iter().map(|feed| self.account(feed)).collect();
+
+        fetch_accounts(&self.client, &self.upstreams, feeds, pubkeys, decode_solana_account, "Solana RPC (Helius)", concurrency).await
+    }
+}
+
+impl AccountSource for SolanaSource {
+    fn ws_endpoint(&self) -> String {
+        self.upstreams.solana_ws_endpoint()
+    }
+
+    fn account(&self, feed: &Feed) -> Option<Pubkey> {
+        feed.account
+    }
+
+    fn decode(&self, feed: &Feed, pubkey: &Pubkey, account: &Account) -> Result<PriceData, anyhow::Error> {
+        decode_solana_account(feed, pubkey, account, "Solana RPC (Helius)")
+    }
+}
+
+/// Maximum number of `ids[]` per Hermes request, to keep URLs reasonably short.
+pub(crate) const MAX_HERMES_IDS: usize = 50;
+
+pub struct HermesSource {
+    client: HermesClient,
+    retain_updates: bool,
+    verifier: Option<Arc<UpdateVerifier>>,
+    verification: VerificationMode,
+}
+
+impl HermesSource {
+    pub fn new(client: HermesClient, retain_updates: bool) -> Self {
+        Self { client, retain_updates, verifier: None, verification: VerificationMode::Off }
+    }
+
+    /// Verifies the accumulator update behind every price. In
+    /// [`VerificationMode::Flag`] unverified prices are returned with
+    /// `verified: false`; in [`VerificationMode::Enforce`] they are rejected.
+    pub fn with_verifier(mut self, verifier: Arc<UpdateVerifier>, mode: VerificationMode) -> Self {
+        self.verifier = (mode != VerificationMode::Off).then_some(verifier);
+        self.verification = mode;
+        self
+    }
+
+    /// Verifies every update and collects the price messages they commit to.
+    /// An update that fails verification contributes nothing.
+    fn verified_messages(&self, verifier: &UpdateVerifier, data: &[Vec<u8>]) -> HashMap<[u8; 32], PriceFeedMessage> {
+        let mut messages = HashMap::new();
+        for update in data {
+            match verifier.verify(update) {
+                Ok(verified) => messages.extend(verified),
+                Err(e) => warn!("Pyth Hermes update failed verification: {}", e),
+            }
+        }
+        messages
+    }
+
+    /// Fetches one chunk of feeds in a single request and maps the parsed
+    /// prices back to them by ID; feeds absent from the response fail
+    /// individually.
+    async fn fetch_chunk(&self, feeds: &[&Feed]) -> Vec<Result<PriceData, anyhow::Error>> {
+        info!("Fetching {} feeds from Pyth Hermes API", feeds.len());
+
+        let ids: Vec<&str> = feeds.iter().map(|feed| feed.id.as_str()).collect();
+        let updates = match self.client.latest_price_updates(&ids).await {
+            Ok(updates) => updates,
+            Err(e) => return feeds.iter().map(|_| Err(anyhow::anyhow!("{}", e))).collect(),
+        };
+
+        self.parse_updates(feeds, updates)
+            .into_iter()
+            .zip(feeds)
+            .map(|(result, feed)| {
+                result.unwrap_or_else(|| Err(anyhow::anyhow!("Feed {} missing from Pyth Hermes response", feed.id)))
+            })
+            .collect()
+    }
+
+    /// Prices in `updates` for `feeds`, in order, with `None` for feeds the
+    /// updates do not cover. Shared by requests and the Hermes subscriber.
+    pub(crate) fn parse_updates(&self, feeds: &[&Feed], updates: PriceUpdates) -> Vec<Option<Result<PriceData, anyhow::Error>>> {
+        let binary = if self.retain_updates || self.verifier.is_some() {
+            match updates.binary.decode() {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Discarding Pyth Hermes update data: {}", e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        let verified = self
+            .verifier
+            .as_ref()
+            .map(|verifier| self.verified_messages(verifier, &binary))
+            .unwrap_or_default();
+
+        let update_data = if self.retain_updates && !binary.is_empty() {
+            Some(binary.iter().map(hex::encode).collect::<Vec<_>>())
+        } else {
+            None
+        };
+
+        let mut by_id: HashMap<[u8; 32], ParsedPriceUpdate> = HashMap::new();
+        for parsed in updates.parsed.unwrap_or_default() {
+            match config::parse_feed_id(&parsed.id) {
+                Ok(id) => {
+                    by_id.insert(id, parsed);
+                }
+                Err(e) => warn!("Ignoring Pyth Hermes entry with bad ID: {}", e),
+            }
+        }
+
+        feeds
+            .iter()
+            .map(|feed| {
+                let parsed = by_id.remove(&feed.id_bytes)?;
+
+                let is_verified = verified
+                    .get(&feed.id_bytes)
+                    .is_some_and(|message| matches_parsed(message, &parsed));
+                if self.verification == VerificationMode::Enforce && !is_verified {
+                    return Some(Err(anyhow::anyhow!("Pyth Hermes price for {} could not be verified", feed.symbol)));
+                }
+
+                Some(to_price_data(feed, parsed, update_data.clone()).map(|mut data| {
+                    data.verified = is_verified;
+                    data
+                }))
+            })
+            .collect()
+    }
+}
+
+/// Whether the JSON price Hermes returned is the one in the verified message.
+fn matches_parsed(message: &PriceFeedMessage, parsed: &ParsedPriceUpdate) -> bool {
+    parsed.price.price.parse() == Ok(message.price)
+        && parsed.price.conf.parse() == Ok(message.conf)
+        && parsed.price.expo == message.exponent
+        && parsed.price.publish_time == message.publish_time
+}
+
+pub(crate) fn parse_rpc_price(price: &RpcPrice) -> Result<ExactPrice, anyhow::Error> {
+    let price_val: i64 = price.price.parse()
+        .map_err(|e| anyhow::anyhow!("Failed to parse price: {}", e))?;
+    let conf_val: u64 = price.conf.parse()
+        .map_err(|e| anyhow::anyhow!("Failed to parse confidence: {}", e))?;
+
+    ExactPrice::new(price_val, conf_val, price.expo)
+}
+
+fn to_price_data(feed: &Feed, parsed: ParsedPriceUpdate, update_data: Option<Vec<String>>) -> Result<PriceData, anyhow::Error> {
+    let exact = parse_rpc_price(&parsed.price)?;
+    let ema = parse_rpc_price(&parsed.ema_price)?;
+    let price_value = exact.price().to_f64();
+    let confidence = exact.conf().to_f64();
+
+    info!("Successfully parsed REAL data for {}: {:.2}% (±{:.2}%)", feed.symbol, price_value, confidence);
+
+    Ok(PriceData {
+        symbol: feed.symbol.clone(),
+        price: price_value,
+        confidence,
+        exact,
+        publish_time: parsed.price.publish_time,
+        price_feed_id: feed.id.clone(),
+        last_updated: chrono::Utc::now(),
+        source: "Pyth Hermes API".to_string(),
+        description: feed.description.clone(),
+        unit: feed.unit.clone(),
+        ema_price: Some(ema.price().to_f64()),
+        ema_confidence: Some(ema.conf().to_f64()),
+        slot: parsed.metadata.slot,
+        prev_publish_time: parsed.metadata.prev_publish_time,
+        update_data,
+        verified: false,
+        status: None,
+        stale: false,
+        low_confidence: false,
+        metadata: feed.metadata.clone(),
+    })
+}
+
+#[async_trait]
+impl PriceSource for HermesSource {
+    fn name(&self) -> &'static str {
+        "hermes"
+    }
+
+    fn label(&self) -> &'static str {
+        "Pyth Hermes API"
+    }
+
+    async fn fetch(&self, feed: &Feed) -> Result<PriceData, anyhow::Error> {
+        self.fetch_many(&[feed], 1).await.remove(0)
+    }
+
+    async fn fetch_many(&self, feeds: &[&Feed], concurrency: usize) -> Vec<Result<PriceData, anyhow::Error>> {
+        let requests: Vec<_> = feeds.chunks(MAX_HERMES_IDS).map(|chunk| self.fetch_chunk(chunk)).collect();
+        let chunks: Vec<Vec<Result<PriceData, anyhow::Error>>> =
+            stream::iter(requests).buffered(concurrency.max(1)).collect().await;
+
+        chunks.into_iter().flatten().collect()
+    }
+}
+
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Ordered fallback chain of price sources, with optional per-feed overrides.
+pub struct SourceChain {
+    default: Vec<Arc<dyn PriceSource>>,
+    overrides: HashMap<String, Vec<Arc<dyn PriceSource>>>,
+    concurrency: usize,
+    quality: QualityPolicy,
+    store: Option<Arc<dyn ObservationStore>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl SourceChain {
+    /// Builds the chain from the available sources. `order` and each feed's
+    /// `sources` list name sources by [`PriceSource::name`]; names listed in
+    /// `disabled` are dropped everywhere.
+    pub fn new(
+        available: Vec<Arc<dyn PriceSource>>,
+        order: &[String],
+        disabled: &[String],
+        registry: &FeedRegistry,
+    ) -> Result<Self, anyhow::Error> {
+        for name in order.iter().chain(disabled) {
+            if !available.iter().any(|source| source.name() == name) {
+                return Err(anyhow::anyhow!("Unknown price source {}", name));
+            }
+        }
+
+        let resolve = |names: &[String]| -> Result<Vec<Arc<dyn PriceSource>>, anyhow::Error> {
+            let mut chain = Vec::new();
+            for name in names {
+                let source = available
+                    .iter()
+                    .find(|source| source.name() == name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown price source {}", name))?;
+                if !disabled.contains(name) && !chain.iter().any(|s: &Arc<dyn PriceSource>| s.name() == name) {
+                    chain.push(source.clone());
+                }
+            }
+            Ok(chain)
+        };
+
+        let default = resolve(order)?;
+        if default.is_empty() {
+            return Err(anyhow::anyhow!("No price sources enabled"));
+        }
+
+        let mut overrides = HashMap::new();
+        for feed in registry.feeds() {
+            if let Some(names) = &feed.sources {
+                let chain = resolve(names).map_err(|e| anyhow::anyhow!("Feed {}: {}", feed.symbol, e))?;
+                if chain.is_empty() {
+                    return Err(anyhow::anyhow!("Feed {}: all of its sources are disabled", feed.symbol));
+                }
+                overrides.insert(feed.symbol.clone(), chain);
+            }
+        }
+
+        Ok(Self { default, overrides, concurrency: DEFAULT_CONCURRENCY, quality: QualityPolicy::default(), store: None, metrics: None })
+    }
+
+    /// Limits how many upstream requests each source may have in flight
+    /// during [`SourceChain::fetch_many`].
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Checks every price against `quality`; a rejected price counts as a
+    /// failure of its source.
+    pub fn with_quality(mut self, quality: QualityPolicy) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Records every accepted price in `store`.
+    pub fn with_store(mut self, store: Arc<dyn ObservationStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Records the latency and outcome of every source attempt, and every
+    /// fallback, in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn for_feed(&self, feed: &Feed) -> &[Arc<dyn PriceSource>] {
+        self.overrides.get(&feed.symbol).unwrap_or(&self.default)
+    }
+
+    /// Human-readable default order, e.g. `Sonic SVM → Solana RPC → Pyth Hermes API`.
+    pub fn describe(&self) -> String {
+        self.default.iter().map(|source| source.label()).collect::<Vec<_>>().join(" → ")
+    }
+
+    /// Tries each source for the feed in order and returns the first success.
+    pub async fn fetch(&self, feed: &Feed) -> Result<PriceData, anyhow::Error> {
+        let symbol = &feed.symbol;
+        let mut errors = Vec::new();
+
+        for (step, source) in self.for_feed(feed).iter().enumerate() {
+            info!("Trying {} for {}", source.label(), symbol);
+            let started = Instant::now();
+            let result = source.fetch(feed).await.and_then(|mut data| {
+                self.quality.apply(feed, &mut data)?;
+                Ok(data)
+            });
+            self.observe(source.as_ref(), feed, step, started.elapsed(), result.is_ok());
+            match result {
+                Ok(data) => {
+                    info!("Successfully fetched {} from {}!", symbol, source.label());
+                    self.record(vec![Observation::new(feed, &data)]);
+                    return Ok(data);
+                }
+                Err(e) => {
+                    warn!("{} failed for {}: {}", source.label(), symbol, e);
+                    errors.push(format!("{}: {}", source.name(), e));
+                }
+            }
+        }
+
+        Err(all_sources_failed(symbol, &errors))
+    }
+
+    /// Accepts a price pushed by a subscriber rather than fetched: it is
+    /// checked against the quality policy and recorded like a fetched one.
+    pub fn ingest(&self, feed: &Feed, mut data: PriceData) -> Result<PriceData, anyhow::Error> {
+        self.quality.apply(feed, &mut data)?;
+        self.record(vec![Observation::new(feed, &data)]);
+        Ok(data)
+    }
+
+    /// Re-checks a price accepted earlier against the quality policy, which
+    /// may flag or reject it now that it is older.
+    pub fn recheck(&self, feed: &Feed, mut data: PriceData) -> Result<PriceData, anyhow::Error> {
+        self.quality.recheck(feed, &mut data)?;
+        Ok(data)
+    }
+
+    /// Fetches several feeds, batching each step of the fallback chain: all
+    /// feeds whose next source is the same are handed to it in one
+    /// [`PriceSource::fetch_many`] call, and different sources run
+    /// concurrently. Feeds still unresolved at `deadline` fail with the errors
+    /// collected so far. Results are in the order of `feeds`.
+    pub async fn fetch_many(&self, feeds: &[&Feed], deadline: Instant) -> Vec<Result<PriceData, anyhow::Error>> {
+        let mut results: Vec<Option<Result<PriceData, anyhow::Error>>> = feeds.iter().map(|_| None).collect();
+        let mut errors: Vec<Vec<String>> = feeds.iter().map(|_| Vec::new()).collect();
+        let mut pending: Vec<usize> = (0..feeds.len()).collect();
+        let mut step = 0;
+
+        while !pending.is_empty() {
+            if Instant::now() >= deadline {
+                for index in pending.drain(..) {
+                    errors[index].push("deadline exceeded".to_string());
+                    results[index] = Some(Err(all_sources_failed(&feeds[index].symbol, &errors[index])));
+                }
+                break;
+            }
+
+            // Group pending feeds by the source at this step of their chain.
+            let mut groups: Vec<(Arc<dyn PriceSource>, Vec<usize>)> = Vec::new();
+            for &index in &pending {
+                let Some(source) = self.for_feed(feeds[index]).get(step) else {
+                    results[index] = Some(Err(all_sources_failed(&feeds[index].symbol, &errors[index])));
+                    continue;
+                };
+                match groups.iter_mut().find(|(s, _)| s.name() == source.name()) {
+                    Some((_, indices)) => indices.push(index),
+                    None => groups.push((source.clone(), vec![index])),
+                }
+            }
+
+            let outcomes = future::join_all(groups.into_iter().map(|(source, indices)| async move {
+                let batch: Vec<&Feed> = indices.iter().map(|&index| feeds[index]).collect();
+                info!("Trying {} for {} feeds", source.label(), batch.len());
+                let started = Instant::now();
+                let outcome = tokio::time::timeout_at(deadline, source.fetch_many(&batch, self.concurrency)).await;
+                (source, indices, outcome, started.elapsed())
+            }))
+            .await;
+
+            pending.clear();
+            for (source, indices, outcome, elapsed) in outcomes {
+                let Ok(batch_results) = outcome else {
+                    warn!("{} did not answer before the deadline for {} feeds", source.label(), indices.len());
+                    for index in indices {
+                        self.observe(source.as_ref(), feeds[index], step, elapsed, false);
+                        errors[index].push(format!("{}: deadline exceeded", source.name()));
+                        results[index] = Some(Err(all_sources_failed(&feeds[index].symbol, &errors[index])));
+                    }
+                    continue;
+                };
+
+                for (index, result) in indices.into_iter().zip(batch_results) {
+                    let result = result.and_then(|mut data| {
+                        self.quality.apply(feeds[index], &mut data)?;
+                        Ok(data)
+                    });
+                    self.observe(source.as_ref(), feeds[index], step, elapsed, result.is_ok());
+                    match result {
+                        Ok(data) => results[index] = Some(Ok(data)),
+                        Err(e) => {
+                            warn!("{} failed for {}: {}", source.label(), feeds[index].symbol, e);
+                            errors[index].push(format!("{}: {}", source.name(), e));
+                            pending.push(index);
+                        }
+                    }
+                }
+            }
+            step += 1;
+        }
+
+        self.record(
+            feeds
+                .iter()
+                .zip(&results)
+                .filter_map(|(feed, result)| match result {
+                    Some(Ok(data)) => Some(Observation::new(feed, data)),
+                    _ => None,
+                })
+                .collect(),
+        );
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(anyhow::anyhow!("Feed was not fetched"))))
+            .collect()
+    }
+
+    /// Records one attempt of `source`, the `step`th of `feed`'s chain.
+    fn observe(&self, source: &dyn PriceSource, feed: &Feed, step: usize, elapsed: Duration, ok: bool) {
+        let Some(metrics) = &self.metrics else { return };
+        metrics.record_fetch(source.name(), feed, elapsed, ok);
+        if ok && step > 0 {
+            metrics.record_fallback(feed, source.name());
+        }
+    }
+
+    /// Writes observations in the background; a storage failure is logged
+    /// and never fails the fetch.
+    fn record(&self, observations: Vec<Observation>) {
+        let Some(store) = self.store.clone() else { return };
+        if observations.is_empty() {
+            return;
+        }
+        tokio::spawn(async move {
+            match store.record(&observations).await {
+                Ok(0) => {}
+                Ok(inserted) => info!("Recorded {} new observations in {}", inserted, store.backend()),
+                Err(e) => warn!("Failed to record {} observations in {}: {}", observations.len(), store.backend(), e),
+            }
+        });
+    }
+}
+
+fn all_sources_failed(symbol: &str, errors: &[String]) -> anyhow::Error {
+    error!("All sources failed for {}: {}", symbol, errors.join("; "));
+    anyhow::anyhow!("Failed to fetch {} from all sources: {}", symbol, errors.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::str::FromStr;
+
+    fn solana_source() -> SolanaSource {
+        // Never contacted: only `decode` is exercised.
+        let client = Arc::new(RpcClient::new("http://127.0.0.1:1".to_string()));
+        SolanaSource::new(client, Arc::new(testing::upstreams()))
+    }
+
+    fn account(owner: &str, data: Vec<u8>) -> Account {
+        Account {
+            lamports: 1_000_000,
+            data,
+            owner: Pubkey::from_str(owner).unwrap(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn decodes_registry_accounts_on_solana() {
+        let source = solana_source();
+        let registry = testing::registry();
+        let mut decoded = 0;
+
+        for feed in registry.feeds() {
+            let Some(pubkey) = source.account(feed) else { continue };
+            let data = testing::price_update_account(PriceUpdateV2::discriminator(), &[1], &feed.id_bytes);
+            let price = source.decode(feed, &pubkey, &account(oracle::PYTH_RECEIVER_PROGRAM, data)).unwrap();
+
+            assert_eq!(price.symbol, feed.symbol);
+            assert_eq!(price.exact.price().to_string(), "2.80000");
+            assert_eq!(price.publish_time, 1_753_920_000);
+            assert!(price.verified);
+            assert_eq!(price.status, None);
+            decoded += 1;
+        }
+
+        // The headline's account is its shard-0 push-oracle PDA, a `PriceUpdateV2`.
+        let headline = registry.headline();
+        assert_eq!(headline.account, Some(oracle::price_feed_address(0, &headline.id_bytes)));
+        assert!(decoded > 0);
+    }
+
+    #[test]
+    fn decodes_legacy_accounts_by_owner() {
+        let source = solana_source();
+        let feed = testing::feed("ECO.US.GDP");
+        let pubkey = feed.account.unwrap();
+        let data = testing::legacy_price_account(oracle::LEGACY_MAGIC, oracle::LEGACY_VERSION_2, oracle::LEGACY_ACCOUNT_TYPE_PRICE, 1);
+
+        let price = source.decode(&feed, &pubkey, &account(oracle::PYTH_ORACLE_PROGRAM, data)).unwrap();
+        assert_eq!(price.exact.price().to_string(), "2.80000");
+        assert_eq!(price.publish_time, 1_753_920_005);
+        assert_eq!(price.status, Some(PriceStatus::Trading));
+        assert!(!price.verified);
+    }
+
+    #[test]
+    fn rejects_accounts_of_other_owners() {
+        let source = solana_source();
+        let feed = testing::feed("ECO.US.GDP");
+        let pubkey = feed.account.unwrap();
+        let data = testing::price_update_account(PriceUpdateV2::discriminator(), &[1], &feed.id_bytes);
+
+        let error = source
+            .decode(&feed, &pubkey, &account("11111111111111111111111111111111", data.clone()))
+            .err()
+            .expect("account should be rejected");
+        assert!(error.to_string().contains("neither the Pyth receiver nor the Pyth oracle"), "{}", error);
+
+        // A receiver account for another feed is not silently served.
+        let other = testing::feed("ECO.US.GDPQ125");
+        let error = source
+            .decode(&other, &pubkey, &account(oracle::PYTH_RECEIVER_PROGRAM, data))
+            .err()
+            .expect("account should be rejected");
+        assert!(error.to_string().contains(&format!("expected {}", other.id)), "{}", error);
+    }
+
+    /// Answers `getMultipleAccounts` with a receiver-owned `PriceUpdateV2`
+    /// for every account in `accounts`, `null` for the others, and an error
+    /// for any chunk that includes `broken`. Records the size of each call.
+    async fn mock_rpc(accounts: HashMap<Pubkey, [u8; 32]>, broken: Option<Pubkey>) -> (Arc<RpcClient>, Arc<std::sync::Mutex<Vec<usize>>>) {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+        let url = testing::serve_rpc(move |method, params| {
+            assert_eq!(method, "getMultipleAccounts");
+            let pubkeys: Vec<Pubkey> = params[0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|pubkey| Pubkey::from_str(pubkey.as_str().unwrap()).unwrap())
+                .collect();
+            recorded.lock().unwrap().push(pubkeys.len());
+            if broken.is_some_and(|broken| pubkeys.contains(&broken)) {
+                return Err("node is behind".to_string());
+            }
+            let value: Vec<serde_json::Value> = pubkeys
+                .iter()
+                .map(|pubkey| match accounts.get(pubkey) {
+                    Some(feed_id) => {
+                        let data = testing::price_update_account(PriceUpdateV2::discriminator(), &[1], feed_id);
+                        testing::ui_account(pubkey, &account(oracle::PYTH_RECEIVER_PROGRAM, data))
+                    }
+                    None => serde_json::Value::Null,
+                })
+                .collect();
+            Ok(serde_json::json!({ "context": { "slot": 1 }, "value": value }))
+        })
+        .await;
+        (Arc::new(RpcClient::new(url)), calls)
+    }
+
+    #[tokio::test]
+    async fn reads_accounts_in_chunks() {
+        let symbols: Vec<String> = (0..MAX_MULTIPLE_ACCOUNTS * 2 + 30).map(|index| format!("FEED{}", index)).collect();
+        let entries: Vec<(&str, Option<&[&str]>)> = symbols.iter().map(|symbol| (symbol.as_str(), None)).collect();
+        let registry = testing::feed_registry(&entries);
+        let feeds: Vec<&Feed> = registry.feeds().iter().collect();
+        let upstreams = Arc::new(testing::upstreams());
+        let address = |feed: &Feed| oracle::price_feed_address(upstreams.price_feed_shard, &feed.id_bytes);
+
+        // The first chunk fails as a whole; one account of the second is missing.
+        let mut accounts: HashMap<Pubkey, [u8; 32]> = feeds.iter().map(|feed| (address(feed), feed.id_bytes)).collect();
+        let missing = address(feeds[MAX_MULTIPLE_ACCOUNTS + 1]);
+        accounts.remove(&missing);
+        let (client, calls) = mock_rpc(accounts, Some(address(feeds[3]))).await;
+
+        let results = SonicSource::new(client, upstreams.clone()).fetch_many(&feeds, 2).await;
+        assert_eq!(results.len(), feeds.len());
+        for (index, (feed, result)) in feeds.iter().zip(&results).enumerate() {
+            match index {
+                index if index < MAX_MULTIPLE_ACCOUNTS => {
+                    let error = result.as_ref().err().unwrap().to_string();
+                    assert!(error.starts_with("RPC request failed:") && error.contains("node is behind"), "{}", error);
+                }
+                index if index == MAX_MULTIPLE_ACCOUNTS + 1 => {
+                    let error = result.as_ref().err().unwrap().to_string();
+                    assert_eq!(error, format!("{} feed account {} not found", feed.symbol, missing));
+                }
+                _ => {
+                    let price = result.as_ref().unwrap();
+                    assert_eq!(price.symbol, feed.symbol);
+                    assert_eq!(price.source, "Sonic SVM Direct Account");
+                }
+            }
+        }
+
+        let mut sizes = calls.lock().unwrap().clone();
+        sizes.sort();
+        assert_eq!(sizes, [30, MAX_MULTIPLE_ACCOUNTS, MAX_MULTIPLE_ACCOUNTS]);
+    }
+
+    #[tokio::test]
+    async fn reads_only_configured_solana_accounts() {
+        let registry = testing::registry();
+        let feeds: Vec<&Feed> = registry.feeds().iter().collect();
+        let accounts: HashMap<Pubkey, [u8; 32]> = feeds
+            .iter()
+            .filter_map(|feed| Some((feed.account?, feed.id_bytes)))
+            .collect();
+        let (client, calls) = mock_rpc(accounts.clone(), None).await;
+
+        let results = SolanaSource::new(client, Arc::new(testing::upstreams())).fetch_many(&feeds, 4).await;
+        for (feed, result) in feeds.iter().zip(&results) {
+            match feed.account {
+                Some(_) => assert_eq!(result.as_ref().unwrap().source, "Solana RPC (Helius)"),
+                None => assert_eq!(
+                    result.as_ref().err().unwrap().to_string(),
+                    format!("No on-chain account configured for {}", feed.symbol)
+                ),
+            }
+        }
+        assert_eq!(*calls.lock().unwrap(), [accounts.len()]);
+    }
+
+    fn chain(sources: &[&Arc<testing::StubSource>], order: &[&str], disabled: &[&str], registry: &FeedRegistry) -> Result<SourceChain, anyhow::Error> {
+        let available = sources.iter().map(|source| Arc::clone(source) as Arc<dyn PriceSource>).collect();
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        SourceChain::new(available, &names(order), &names(disabled), registry)
+    }
+
+    fn names(sources: &[Arc<dyn PriceSource>]) -> Vec<&'static str> {
+        sources.iter().map(|source| source.name()).collect()
+    }
+
+    fn batches(batches: &[&[&str]]) -> Vec<Vec<String>> {
+        batches.iter().map(|batch| batch.iter().map(|symbol| symbol.to_string()).collect()).collect()
+    }
+
+    fn stub(name: &'static str, default: testing::Outcome) -> Arc<testing::StubSource> {
+        Arc::new(testing::StubSource::new(name, default))
+    }
+
+    #[tokio::test]
+    async fn tries_sources_in_order() {
+        let registry = testing::feed_registry(&[("GDP", None)]);
+        let feed = registry.headline();
+        let (a, b, c) = (stub("a", testing::Outcome::Fail), stub("b", testing::Outcome::Price(280_000)), stub("c", testing::Outcome::Price(1)));
+        let chain = chain(&[&a, &b, &c], &["a", "b", "c"], &[], &registry).unwrap();
+
+        let price = chain.fetch(feed).await.unwrap();
+        assert_eq!(price.source, "b");
+        assert_eq!(price.exact.price().to_string(), "2.80000");
+        assert_eq!(a.batches(), batches(&[&["GDP"]]));
+        assert_eq!(b.batches(), batches(&[&["GDP"]]));
+        assert!(c.batches().is_empty());
+        assert_eq!(chain.describe(), "a → b → c");
+
+        let failing = stub("b", testing::Outcome::Fail);
+        let chain = self::chain(&[&a, &failing], &["a", "b"], &[], &registry).unwrap();
+        let error = chain.fetch(feed).await.err().expect("every source fails");
+        assert_eq!(error.to_string(), "Failed to fetch GDP from all sources: a: a is down; b: b is down");
+    }
+
+    #[tokio::test]
+    async fn skips_disabled_sources() {
+        let registry = testing::feed_registry(&[("GDP", None)]);
+        let (a, b) = (stub("a", testing::Outcome::Price(1)), stub("b", testing::Outcome::Price(2)));
+        let chain = chain(&[&a, &b], &["a", "b"], &["a"], &registry).unwrap();
+
+        assert_eq!(chain.fetch(registry.headline()).await.unwrap().source, "b");
+        assert!(a.batches().is_empty());
+        assert_eq!(chain.describe(), "b");
+
+        let error = |result: Result<SourceChain, anyhow::Error>| result.err().expect("chain is invalid").to_string();
+        assert_eq!(error(self::chain(&[&a, &b], &["a", "z"], &[], &registry)), "Unknown price source z");
+        assert_eq!(error(self::chain(&[&a, &b], &["a"], &["z"], &registry)), "Unknown price source z");
+        assert_eq!(error(self::chain(&[&a, &b], &["a", "b"], &["a", "b"], &registry)), "No price sources enabled");
+
+        let overridden = testing::feed_registry(&[("GDP", Some(&["a"]))]);
+        assert_eq!(error(self::chain(&[&a, &b], &["a", "b"], &["a"], &overridden)), "Feed GDP: all of its sources are disabled");
+        let unknown = testing::feed_registry(&[("GDP", Some(&["z"]))]);
+        assert_eq!(error(self::chain(&[&a, &b], &["a", "b"], &[], &unknown)), "Feed GDP: Unknown price source z");
+    }
+
+    #[tokio::test]
+    async fn applies_per_feed_overrides() {
+        let registry = testing::feed_registry(&[("GDP", Some(&["c", "a", "c"])), ("CPI", None)]);
+        let (a, b) = (stub("a", testing::Outcome::Price(1)), stub("b", testing::Outcome::Price(2)));
+        let c = Arc::new(testing::StubSource::new("c", testing::Outcome::Price(3)).with("GDP", testing::Outcome::Fail));
+        let chain = chain(&[&a, &b, &c], &["b", "a", "b"], &["b"], &registry).unwrap();
+
+        let gdp = registry.resolve("GDP").unwrap();
+        let cpi = registry.resolve("CPI").unwrap();
+        assert_eq!(names(chain.for_feed(gdp)), ["c", "a"]);
+        assert_eq!(names(chain.for_feed(cpi)), ["a"]);
+
+        assert_eq!(chain.fetch(gdp).await.unwrap().source, "a");
+        assert_eq!(c.batches(), batches(&[&["GDP"]]));
+        assert_eq!(chain.fetch(cpi).await.unwrap().source, "a");
+        assert_eq!(c.batches().len(), 1);
+        assert!(b.batches().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn returns_partial_results_at_the_deadline() {
+        let registry = testing::feed_registry(&[("GDP", Some(&["b"])), ("CPI", None), ("PPI", Some(&["c", "b"]))]);
+        let a = stub("a", testing::Outcome::Hang);
+        let (b, c) = (stub("b", testing::Outcome::Price(1)), stub("c", testing::Outcome::Fail));
+        let chain = chain(&[&a, &b, &c], &["a", "b"], &[], &registry).unwrap();
+        let feeds: Vec<&Feed> = registry.feeds().iter().collect();
+
+        let started = Instant::now();
+        let results = chain.fetch_many(&feeds, started + Duration::from_secs(5)).await;
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+
+        assert_eq!(results[0].as_ref().unwrap().source, "b");
+        let errors: Vec<String> = results[1..].iter().map(|result| result.as_ref().err().unwrap().to_string()).collect();
+        assert_eq!(errors[0], "Failed to fetch CPI from all sources: a: deadline exceeded");
+        // PPI failed on c at once, but its next step only starts once a
+        // has given up on CPI, which is at the deadline.
+        assert_eq!(errors[1], "Failed to fetch PPI from all sources: c: c is down; deadline exceeded");
+        assert_eq!(b.batches(), batches(&[&["GDP"]]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn times_out_a_fallback_step() {
+        let registry = testing::feed_registry(&[("GDP", None), ("CPI", None)]);
+        let a = Arc::new(testing::StubSource::new("a", testing::Outcome::Price(1)).with("CPI", testing::Outcome::Fail));
+        let b = stub("b", testing::Outcome::Hang);
+        let chain = chain(&[&a, &b], &["a", "b"], &[], &registry).unwrap();
+        let feeds: Vec<&Feed> = registry.feeds().iter().collect();
+
+        let started = Instant::now();
+        let results = chain.fetch_many(&feeds, started + Duration::from_secs(3)).await;
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+        assert_eq!(results[0].as_ref().unwrap().source, "a");
+        let error = results[1].as_ref().err().unwrap().to_string();
+        assert_eq!(error, "Failed to fetch CPI from all sources: a: a is down; b: deadline exceeded");
+        assert_eq!(b.batches(), batches(&[&["CPI"]]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fails_pending_feeds_once_the_deadline_has_passed() {
+        let registry = testing::feed_registry(&[("GDP", None)]);
+        let (a, b) = (stub("a", testing::Outcome::Fail), stub("b", testing::Outcome::Price(1)));
+        let chain = chain(&[&a, &b], &["a", "b"], &[], &registry).unwrap();
+
+        let results = chain.fetch_many(&[registry.headline()], Instant::now()).await;
+        let error = results[0].as_ref().err().unwrap().to_string();
+        assert_eq!(error, "Failed to fetch GDP from all sources: deadline exceeded");
+        assert!(a.batches().is_empty());
+        assert!(b.batches().is_empty());
+    }
+
+    #[tokio::test]
+    async fn batches_each_step_by_source() {
+        let registry = testing::feed_registry(&[("GDP", None), ("CPI", None), ("PPI", Some(&["b"])), ("NFP", None)]);
+        let a = Arc::new(testing::StubSource::new("a", testing::Outcome::Price(1)).with("CPI", testing::Outcome::Fail).with("NFP", testing::Outcome::Fail));
+        let b = Arc::new(testing::StubSource::new("b", testing::Outcome::Price(2)).with("NFP", testing::Outcome::Fail));
+        let chain = chain(&[&a, &b], &["a", "b"], &[], &registry).unwrap();
+        let feeds: Vec<&Feed> = registry.feeds().iter().collect();
+
+        let results = chain.fetch_many(&feeds, Instant::now() + Duration::from_secs(5)).await;
+        let sources: Vec<String> = results
+            .iter()
+            .map(|result| match result {
+                Ok(data) => data.source.clone(),
+                Err(e) => e.to_string(),
+            })
+            .collect();
+        assert_eq!(sources, ["a", "b", "b", "Failed to fetch NFP from all sources: a: a is down; b: b is down"]);
+
+        // One call per source and step: PPI starts on b alongside the first
+        // step of a, and the feeds a failed share one call to b.
+        assert_eq!(a.batches(), batches(&[&["GDP", "CPI", "NFP"]]));
+        assert_eq!(b.batches(), batches(&[&["PPI"], &["CPI", "NFP"]]));
+    }
+
+    /// Serves `/v2/updates/price/latest` for `registry`, answering every
+    /// requested ID except `omitted`, and records the size of each request.
+    async fn mock_hermes(registry: &FeedRegistry, omitted: Option<String>) -> (HermesSource, Arc<std::sync::Mutex<Vec<usize>>>) {
+        use axum::{extract::RawQuery, routing::get, Json, Router};
+
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let ids: Vec<String> = registry.feeds().iter().map(|feed| feed.id.trim_start_matches("0x").to_string()).collect();
+        let router = Router::new().route(
+            "/v2/updates/price/latest",
+            get(move |RawQuery(query): RawQuery| async move {
+                let requested = testing::hermes_ids(&query.unwrap_or_default());
+                recorded.lock().unwrap().push(requested.len());
+                let parsed: Vec<serde_json::Value> = requested
+                    .iter()
+                    .map(|id| id.trim_start_matches("0x").to_string())
+                    .filter(|id| ids.contains(id) && Some(id) != omitted.as_ref())
+                    .map(|id| {
+                        let price = serde_json::json!({ "price": "280000", "conf": "1000", "expo": -5, "publish_time": 1_753_920_000 });
+                        serde_json::json!({ "id": id, "price": price, "ema_price": price, "metadata": { "slot": 1 } })
+                    })
+                    .collect();
+                Json(serde_json::json!({ "binary": { "encoding": "hex", "data": [] }, "parsed": parsed }))
+            }),
+        );
+        let url = testing::serve(router).await;
+        let client = HermesClient::new(reqwest::Client::new(), &url, Duration::from_secs(5));
+        (HermesSource::new(client, false), requests)
+    }
+
+    #[tokio::test]
+    async fn reports_feeds_missing_from_hermes() {
+        let registry = testing::feed_registry(&[("GDP", None), ("CPI", None), ("PPI", None)]);
+        let feeds: Vec<&Feed> = registry.feeds().iter().collect();
+        let missing = feeds[1].id.trim_start_matches("0x").to_string();
+        let (hermes, requests) = mock_hermes(&registry, Some(missing)).await;
+
+        let results = hermes.fetch_many(&feeds, 4).await;
+        assert_eq!(results[0].as_ref().unwrap().exact.price().to_string(), "2.80000");
+        assert_eq!(results[2].as_ref().unwrap().symbol, "PPI");
+        let error = results[1].as_ref().err().unwrap().to_string();
+        assert_eq!(error, format!("Feed {} missing from Pyth Hermes response", feeds[1].id));
+        assert_eq!(*requests.lock().unwrap(), [3]);
+    }
+
+    #[tokio::test]
+    async fn chunks_hermes_requests() {
+        let symbols: Vec<String> = (0..MAX_HERMES_IDS * 2 + 7).map(|index| format!("FEED{}", index)).collect();
+        let entries: Vec<(&str, Option<&[&str]>)> = symbols.iter().map(|symbol| (symbol.as_str(), None)).collect();
+        let registry = testing::feed_registry(&entries);
+        let feeds: Vec<&Feed> = registry.feeds().iter().collect();
+        let (hermes, requests) = mock_hermes(&registry, None).await;
+
+        let results = hermes.fetch_many(&feeds, 2).await;
+        assert_eq!(results.len(), feeds.len());
+        for (feed, result) in feeds.iter().zip(&results) {
+            assert_eq!(result.as_ref().unwrap().symbol, feed.symbol);
+        }
+        let mut sizes = requests.lock().unwrap().clone();
+        sizes.sort();
+        assert_eq!(sizes, [7, MAX_HERMES_IDS, MAX_HERMES_IDS]);
+    }
+}
@@ -0,0 +1,403 @@
+//! `/gdp/stream`: price updates as Server-Sent Events.
+//!
+//! Each changed price of a selected feed is sent as a `price` event whose data
+//! is the `PriceData` JSON and whose ID is `<last_updated in unix ms>:<symbol>`.
+//! A client reconnecting with `Last-Event-ID` is first sent the prices changed
+//! since that event, from memory while they are still kept there and from the
+//! observation store otherwise.
+
+use crate::{config::Feed, storage::Observation, AppState, PriceData};
+use axum::response::sse::Event;
+use futures::Stream;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    convert::Infallible,
+    fmt,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+/// Sent event IDs are remembered this long past the newest one, so that a
+/// price arriving both by replay and broadcast is sent once.
+const DEDUPE_WINDOW_MS: i64 = 60_000;
+
+/// Position in the stream. Prices fetched in the same millisecond are told
+/// apart by symbol.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EventId {
+    fetched_ms: i64,
+    symbol: String,
+}
+
+impl EventId {
+    fn of(data: &PriceData) -> Self {
+        Self { fetched_ms: data.last_updated.timestamp_millis(), symbol: data.symbol.clone() }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (fetched_ms, symbol) = raw.split_once(':')?;
+        Some(Self { fetched_ms: fetched_ms.parse().ok()?, symbol: symbol.to_string() })
+    }
+}
+
+impl fmt::Display for EventId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.fetched_ms, self.symbol)
+    }
+}
+
+/// One `/gdp/stream` client.
+struct SseClient {
+    state: AppState,
+    /// Selected feeds by ID.
+    feeds: HashMap<String, Feed>,
+    updates: broadcast::Receiver<PriceData>,
+    max_replay: usize,
+    /// Where to start: resume after an event, or a snapshot when `None`.
+    start: Option<Option<EventId>>,
+    pending: VecDeque<PriceData>,
+    sent: HashSet<EventId>,
+    newest_ms: i64,
+    /// When the client subscribed to `updates`.
+    connected_ms: i64,
+}
+
+/// Events for `feeds`: a snapshot of their current prices, or everything
+/// since `resume`, followed by every change.
+pub fn events(
+    state: AppState,
+    feeds: Vec<Feed>,
+    resume: Option<EventId>,
+    max_replay: usize,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let client = SseClient::new(state, feeds, resume, max_replay);
+    info!("SSE client connected for {} feeds", client.feeds.len());
+
+    futures::stream::unfold(client, |mut client| async move {
+        let event = client.next().await?;
+        Some((Ok(event), client))
+    })
+}
+
+impl SseClient {
+    fn new(state: AppState, feeds: Vec<Feed>, resume: Option<EventId>, max_replay: usize) -> Self {
+        // The client has the event it resumes from.
+        let sent = resume.iter().cloned().collect();
+        Self {
+            updates: state.cache.subscribe(),
+            state,
+            feeds: feeds.into_iter().map(|feed| (feed.id.clone(), feed)).collect(),
+            max_replay,
+            start: Some(resume),
+            pending: VecDeque::new(),
+            sent,
+            newest_ms: i64::MIN,
+            connected_ms: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+
+    async fn next(&mut self) -> Option<Event> {
+        loop {
+            if let Some(start) = self.start.take() {
+                let backlog = match start {
+                    Some(resume) => self.replay(resume.fetched_ms).await,
+                    None => self.snapshot().await,
+                };
+                self.pending.extend(backlog);
+            }
+
+            if let Some(data) = self.pending.pop_front() {
+                match self.event(data) {
+                    Some(event) => return Some(event),
+                    None => continue,
+                }
+            }
+
+            match self.updates.recv().await {
+                Ok(data) => {
+                    if let Some(event) = self.event(data) {
+                        return Some(event);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("SSE client fell {} updates behind, replaying", skipped);
+                    // Nothing sent yet: everything missed came after connecting.
+                    let since = if self.newest_ms == i64::MIN { self.connected_ms } else { self.newest_ms };
+                    let backlog = self.replay(since).await;
+                    self.pending.extend(backlog);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The event for `data`, unless its feed is not selected or it was sent already.
+    fn event(&mut self, data: PriceData) -> Option<Event> {
+        if !self.feeds.contains_key(&data.price_feed_id) {
+            return None;
+        }
+        let id = EventId::of(&data);
+        if !self.sent.insert(id.clone()) {
+            return None;
+        }
+        if id.fetched_ms > self.newest_ms {
+            self.newest_ms = id.fetched_ms;
+            let cutoff = self.newest_ms.saturating_sub(DEDUPE_WINDOW_MS);
+            self.sent.retain(|sent| sent.fetched_ms >= cutoff);
+        }
+
+        match Event::default().event("price").id(id.to_string()).json_data(&data) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                warn!("Failed to encode {} for SSE: {}", data.symbol, e);
+                None
+            }
+        }
+    }
+
+    /// Current prices, fetched like `/gdp/all`.
+    async fn snapshot(&self) -> Vec<PriceData> {
+        let results = self.state.get_feeds(self.feeds.values().collect()).await;
+        for failed in &results.failed {
+            warn!("SSE snapshot of {} failed: {}", failed.symbol, failed.error);
+        }
+        results.feeds
+    }
+
+    /// Changes fetched at or after `since_ms` and not sent yet, oldest first
+    /// and at most `max_replay` of them.
+    async fn replay(&self, since_ms: i64) -> Vec<PriceData> {
+        let Some(since) = chrono::DateTime::from_timestamp_millis(since_ms) else {
+            return Vec::new();
+        };
+
+        let mut changes = match self.state.cache.changes_since(since) {
+            Some(changes) => changes.into_iter().filter(|data| self.feeds.contains_key(&data.price_feed_id)).collect(),
+            None => self.recorded_since(since).await,
+        };
+        changes.retain(|data| !self.sent.contains(&EventId::of(data)));
+        changes.sort_by_key(|data| (data.last_updated, data.symbol.clone()));
+        if changes.len() > self.max_replay {
+            warn!("Replaying the first {} of {} changes to an SSE client", self.max_replay, changes.len());
+            changes.truncate(self.max_replay);
+        }
+        changes
+    }
+
+    async fn recorded_since(&self, since: chrono::DateTime<chrono::Utc>) -> Vec<PriceData> {
+        let Some(store) = &self.state.store else {
+            warn!("Cannot replay SSE events from before {}: storage is disabled", since);
+            return Vec::new();
+        };
+        // `recorded_since` is exclusive; step back to include `since` itself,
+        // and take one more than `max_replay` for the event there, already sent.
+        let after = since - chrono::Duration::nanoseconds(1);
+        let limit = self.max_replay.saturating_add(1);
+
+        let mut changes = Vec::new();
+        for feed in self.feeds.values() {
+            match store.recorded_since(&feed.id, after, limit).await {
+                Ok(observations) => {
+                    for observation in observations {
+                        match replayed(feed, observation) {
+                            Ok(data) => changes.push(data),
+                            Err(e) => warn!("Skipping stored observation of {}: {}", feed.symbol, e),
+                        }
+                    }
+                }
+                Err(e) => warn!("Failed to replay {} from storage: {}", feed.symbol, e),
+            }
+        }
+        changes
+    }
+}
+
+/// A stored observation as `PriceData`. Only what is stored is filled in:
+/// EMA, slot and on-chain status are absent, and quality flags are unset.
+fn replayed(feed: &Feed, observation: Observation) -> Result<PriceData, anyhow::Error> {
+    let exact = crate::price::ExactPrice::new(
+        observation.price_mantissa,
+        observation.confidence_mantissa,
+        observation.exponent,
+    )?;
+    Ok(PriceData {
+        symbol: feed.symbol.clone(),
+        price: exact.price().to_f64(),
+        confidence: exact.conf().to_f64(),
+        exact,
+        publish_time: observation.publish_time,
+        price_feed_id: feed.id.clone(),
+        last_updated: observation.fetched_at,
+        source: observation.source,
+        description: feed.description.clone(),
+        unit: feed.unit.clone(),
+        ema_price: None,
+        ema_confidence: None,
+        slot: None,
+        prev_publish_time: None,
+        update_data: None,
+        verified: false,
+        status: None,
+        stale: false,
+        low_confidence: false,
+        metadata: feed.metadata.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::PriceCache,
+        storage::{self, ObservationStore},
+        testing::{self, Outcome, StubSource},
+    };
+    use axum::{
+        body::BodyDataStream,
+        response::{sse::Sse, IntoResponse},
+    };
+    use futures::StreamExt;
+    use std::{sync::Arc, time::Duration};
+
+    /// Server state whose cache broadcasts to `capacity` and keeps `replay` changes.
+    fn state(capacity: usize, replay: usize, store: Option<Arc<dyn ObservationStore>>) -> AppState {
+        let mut state = testing::app_state(Arc::new(StubSource::new("stub", Outcome::Fail)));
+        state.cache = Arc::new(PriceCache::new(Duration::from_secs(60), Duration::ZERO, capacity, replay));
+        state.store = store;
+        state
+    }
+
+    /// Fetch times `n` seconds into 2100, after any cache or client was created.
+    fn at(n: i64) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp(4_102_444_800 + n, 0).unwrap()
+    }
+
+    /// A price of `mantissa`, also its publish time, fetched at `at(n)`.
+    fn change(feed: &Feed, mantissa: i64, n: i64) -> PriceData {
+        let mut data = testing::price(feed, mantissa, 1_000, -5, mantissa);
+        data.last_updated = at(n);
+        data
+    }
+
+    fn body(state: &AppState, feeds: &[&Feed], resume: Option<EventId>, max_replay: usize) -> BodyDataStream {
+        let feeds = feeds.iter().map(|feed| (*feed).clone()).collect();
+        Sse::new(events(state.clone(), feeds, resume, max_replay)).into_response().into_body().into_data_stream()
+    }
+
+    /// The ID, mantissa and source of the next event, or `None` when none
+    /// comes within 200ms.
+    async fn next(body: &mut BodyDataStream) -> Option<(String, i64, String)> {
+        let frame = tokio::time::timeout(Duration::from_millis(200), body.next()).await.ok()??.unwrap();
+        let text = String::from_utf8(frame.to_vec()).unwrap();
+        assert!(text.starts_with("event: price\n"), "{}", text);
+        let id = text.lines().find_map(|line| line.strip_prefix("id: ")).unwrap();
+        let data: PriceData = serde_json::from_str(text.lines().find_map(|line| line.strip_prefix("data: ")).unwrap()).unwrap();
+        Some((id.to_string(), data.exact.price, data.source))
+    }
+
+    fn id(symbol: &str, n: i64) -> String {
+        format!("{}:{}", at(n).timestamp_millis(), symbol)
+    }
+
+    #[tokio::test]
+    async fn resumes_after_the_last_event_id() {
+        let state = state(16, 16, None);
+        let gdp = testing::feed("ECO.US.GDP");
+        let quarterly = testing::feed("ECO.US.GDPQ125");
+        state.cache.insert(&gdp, change(&gdp, 1, 1));
+
+        let resume = EventId::parse(&id("ECO.US.GDP", 1)).unwrap();
+        let mut body = body(&state, &[&gdp], Some(resume), 100);
+        // Both replayed and broadcast, but sent once; other feeds are not sent.
+        state.cache.insert(&gdp, change(&gdp, 2, 2));
+        state.cache.insert(&quarterly, change(&quarterly, 9, 2));
+        state.cache.insert(&gdp, change(&gdp, 3, 3));
+        assert_eq!(next(&mut body).await, Some((id("ECO.US.GDP", 2), 2, "test".to_string())));
+        assert_eq!(next(&mut body).await, Some((id("ECO.US.GDP", 3), 3, "test".to_string())));
+
+        state.cache.insert(&gdp, change(&gdp, 4, 4));
+        assert_eq!(next(&mut body).await, Some((id("ECO.US.GDP", 4), 4, "test".to_string())));
+        assert_eq!(next(&mut body).await, None);
+    }
+
+    #[test]
+    fn forgets_event_ids_outside_the_dedupe_window() {
+        let gdp = testing::feed("ECO.US.GDP");
+        let mut client = SseClient::new(state(16, 16, None), vec![gdp.clone()], None, 100);
+
+        let first = change(&gdp, 1, 0);
+        assert!(client.event(first.clone()).is_some());
+        assert!(client.event(first.clone()).is_none());
+
+        // Still remembered a full window after it.
+        let mut later = change(&gdp, 2, 0);
+        later.last_updated += chrono::Duration::milliseconds(DEDUPE_WINDOW_MS);
+        assert!(client.event(later).is_some());
+        assert!(client.event(first.clone()).is_none());
+
+        let mut newest = change(&gdp, 3, 0);
+        newest.last_updated += chrono::Duration::milliseconds(DEDUPE_WINDOW_MS + 1);
+        assert!(client.event(newest).is_some());
+        assert_eq!(client.sent.len(), 2);
+        assert!(client.event(first).is_some());
+    }
+
+    #[tokio::test]
+    async fn catches_up_after_lagging() {
+        let gdp = testing::feed("ECO.US.GDP");
+
+        // After an event: replayed from that event on.
+        let state = state(2, 16, None);
+        state.cache.insert(&gdp, change(&gdp, 1, 1));
+        let mut body = body(&state, &[&gdp], EventId::parse(&id("ECO.US.GDP", 0)), 100);
+        assert_eq!(next(&mut body).await.unwrap().0, id("ECO.US.GDP", 1));
+        for n in 2..=6 {
+            state.cache.insert(&gdp, change(&gdp, n, n));
+        }
+        for n in 2..=6 {
+            assert_eq!(next(&mut body).await.unwrap().0, id("ECO.US.GDP", n));
+        }
+        assert_eq!(next(&mut body).await, None);
+
+        // Before any event: replayed from when the client connected.
+        let state = self::state(2, 16, None);
+        let mut body = self::body(&state, &[&gdp], EventId::parse(&id("ECO.US.GDP", 0)), 100);
+        for n in 1..=5 {
+            state.cache.insert(&gdp, change(&gdp, n, n));
+        }
+        for n in 1..=5 {
+            assert_eq!(next(&mut body).await.unwrap().0, id("ECO.US.GDP", n));
+        }
+        assert_eq!(next(&mut body).await, None);
+    }
+
+    #[tokio::test]
+    async fn replays_from_the_store_beyond_the_replay_buffer() {
+        let store = storage::open("sqlite::memory:").await.unwrap();
+        let state = state(16, 2, Some(store.clone()));
+        let gdp = testing::feed("ECO.US.GDP");
+        for n in 1..=5 {
+            let data = change(&gdp, n, n);
+            let mut observation = Observation::new(&gdp, &data);
+            observation.source = "stored".to_string();
+            store.record(&[observation]).await.unwrap();
+            state.cache.insert(&gdp, data);
+        }
+
+        // Changes 1 to 3 are no longer in memory.
+        let mut body = body(&state, &[&gdp], EventId::parse(&id("ECO.US.GDP", 1)), 100);
+        for n in 2..=5 {
+            assert_eq!(next(&mut body).await, Some((id("ECO.US.GDP", n), n, "stored".to_string())));
+        }
+        assert_eq!(next(&mut body).await, None);
+
+        let mut body = self::body(&state, &[&gdp], EventId::parse(&id("ECO.US.GDP", 1)), 2);
+        assert_eq!(next(&mut body).await.unwrap().1, 2);
+        assert_eq!(next(&mut body).await.unwrap().1, 3);
+        assert_eq!(next(&mut body).await, None);
+
+        let mut body = self::body(&state, &[&gdp], EventId::parse(&id("ECO.US.GDP", 4)), 100);
+        assert_eq!(next(&mut body).await, Some((id("ECO.US.GDP", 5), 5, "test".to_string())));
+        assert_eq!(next(&mut body).await, None);
+    }
+}
@@ -0,0 +1,579 @@
+use crate::{config::Feed, PriceData};
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tracing::{info, warn};
+
+/// Schema migrations, applied in order and recorded in `schema_migrations`.
+const SQLITE_MIGRATIONS: &[(i64, &str)] = &[
+    (1, include_str!("../migrations/sqlite/0001_observations.sql")),
+    (2, include_str!("../migrations/sqlite/0002_backfill_progress.sql")),
+    (3, include_str!("../migrations/sqlite/0003_observations_fetched_at.sql")),
+];
+
+#[cfg(feature = "postgres")]
+const POSTGRES_MIGRATIONS: &[(i64, &str)] = &[
+    (1, include_str!("../migrations/postgres/0001_observations.sql")),
+    (2, include_str!("../migrations/postgres/0002_backfill_progress.sql")),
+    (3, include_str!("../migrations/postgres/0003_observations_fetched_at.sql")),
+];
+
+/// A price observed for a feed, stored once per `(feed_id, publish_time)`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Observation {
+    pub feed_id: String,
+    pub symbol: String,
+    pub publish_time: i64,
+    pub price_mantissa: i64,
+    pub confidence_mantissa: u64,
+    pub exponent: i32,
+    pub source: String,
+    pub fetched_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Observation {
+    pub fn new(feed: &Feed, data: &PriceData) -> Self {
+        Self {
+            feed_id: feed.id.clone(),
+            symbol: feed.symbol.clone(),
+            publish_time: data.publish_time,
+            price_mantissa: data.exact.price,
+            confidence_mantissa: data.exact.conf,
+            exponent: data.exact.expo,
+            source: data.source.clone(),
+            fetched_at: data.last_updated,
+        }
+    }
+
+    fn from_row(row: ObservationRow) -> Result<Self, anyhow::Error> {
+        let (feed_id, symbol, publish_time, price_mantissa, confidence_mantissa, exponent, source, fetched_at) = row;
+        let confidence_mantissa = u64::try_from(confidence_mantissa)
+            .map_err(|_| anyhow::anyhow!("Stored confidence {} of {} is negative", confidence_mantissa, symbol))?;
+        Ok(Self { feed_id, symbol, publish_time, price_mantissa, confidence_mantissa, exponent, source, fetched_at })
+    }
+
+    fn confidence_i64(&self) -> Result<i64, anyhow::Error> {
+        i64::try_from(self.confidence_mantissa)
+            .map_err(|_| anyhow::anyhow!("Confidence {} of {} does not fit the store", self.confidence_mantissa, self.symbol))
+    }
+}
+
+/// Columns of `observations`, in `HISTORY_SQL` order.
+type ObservationRow = (String, String, i64, i64, i64, i32, String, chrono::DateTime<chrono::Utc>);
+
+/// Observations of one feed, most recent `limit` first. With `bucket_secs`
+/// above 1 only the last observation in each bucket of that many seconds is
+/// returned.
+#[derive(Clone, Debug)]
+pub struct HistoryQuery {
+    pub feed_id: String,
+    pub from: i64,
+    pub to: i64,
+    pub bucket_secs: i64,
+    pub limit: usize,
+}
+
+/// Keeps the last observation per `publish_time / bucket` in range.
+const HISTORY_SQL: &str = "SELECT o.feed_id, o.symbol, o.publish_time, o.price_mantissa, o.confidence_mantissa,
+        o.exponent, o.source, o.fetched_at
+     FROM observations o
+     JOIN (SELECT MAX(publish_time) AS publish_time
+           FROM observations
+           WHERE feed_id = $1 AND publish_time >= $2 AND publish_time <= $3
+           GROUP BY publish_time / $4) b ON o.publish_time = b.publish_time
+     WHERE o.feed_id = $1
+     ORDER BY o.publish_time DESC
+     LIMIT $5";
+
+const RECORDED_SINCE_SQL: &str = "SELECT feed_id, symbol, publish_time, price_mantissa, confidence_mantissa,
+        exponent, source, fetched_at
+     FROM observations
+     WHERE feed_id = $1 AND fetched_at > $2
+     ORDER BY fetched_at ASC
+     LIMIT $3";
+
+const SAVE_CHECKPOINT_SQL: &str = "INSERT INTO backfill_progress (job, next_time, updated_at) VALUES ($1, $2, $3)
+     ON CONFLICT (job) DO UPDATE SET next_time = excluded.next_time, updated_at = excluded.updated_at";
+
+/// Persistent time series of observed prices.
+#[async_trait]
+pub trait ObservationStore: Send + Sync {
+    /// Backend name for logs, e.g. `"sqlite"`.
+    fn backend(&self) -> &'static str;
+
+    /// Stores the observations, skipping any `(feed_id, publish_time)`
+    /// already present or whose confidence does not fit. Returns how many
+    /// were new.
+    async fn record(&self, observations: &[Observation]) -> Result<usize, anyhow::Error>;
+
+    /// Observations matching `query`, newest first.
+    async fn history(&self, query: &HistoryQuery) -> Result<Vec<Observation>, anyhow::Error>;
+
+    /// The first `limit` observations of a feed recorded after `since`, oldest first.
+    async fn recorded_since(
+        &self,
+        feed_id: &str,
+        since: chrono::DateTime<chrono::Utc>,
+        limit: usize,
+    ) -> Result<Vec<Observation>, anyhow::Error>;
+
+    /// Next publish time saved for the backfill `job`, if it has run before.
+    async fn backfill_checkpoint(&self, job: &str) -> Result<Option<i64>, anyhow::Error>;
+
+    async fn save_backfill_checkpoint(&self, job: &str, next_time: i64) -> Result<(), anyhow::Error>;
+}
+
+/// Opens the store for `url` and runs pending migrations. `sqlite://<path>`
+/// (or `sqlite::memory:`) uses SQLite; `postgres://...` needs the `postgres`
+/// cargo feature.
+pub async fn open(url: &str) -> Result<Arc<dyn ObservationStore>, anyhow::Error> {
+    if let Some(path) = url.strip_prefix("sqlite://").or_else(|| url.strip_prefix("sqlite:")) {
+        let path = path.to_string();
+        let store = tokio::task::spawn_blocking(move || SqliteStore::open(&path)).await??;
+        return Ok(Arc::new(store));
+    }
+
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return Ok(Arc::new(postgres::PostgresStore::connect(url).await?));
+        #[cfg(not(feature = "postgres"))]
+        return Err(anyhow::anyhow!("Postgres storage requires building with `--features postgres`"));
+    }
+
+    Err(anyhow::anyhow!("Unsupported database URL {}", crate::config::redact_url(url)))
+}
+
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    fn open(path: &str) -> Result<Self, anyhow::Error> {
+        let mut connection = if path == ":memory:" {
+            Connection::open_in_memory()?
+        } else {
+            if let Some(dir) = Path::new(path).parent()
+                && !dir.as_os_str().is_empty()
+            {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+            }
+            let connection = Connection::open(path)
+                .map_err(|e| anyhow::anyhow!("Failed to open SQLite database {}: {}", path, e))?;
+            connection.pragma_update(None, "journal_mode", "WAL")?;
+            connection
+        };
+
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY, applied_at TEXT NOT NULL)",
+        )?;
+        for (version, sql) in SQLITE_MIGRATIONS {
+            let applied: bool = connection.query_row(
+                "SELECT EXISTS(SELECT 1 FROM schema_migrations WHERE version = ?1)",
+                [version],
+                |row| row.get(0),
+            )?;
+            if applied {
+                continue;
+            }
+            let tx = connection.transaction()?;
+            tx.execute_batch(sql)
+                .map_err(|e| anyhow::anyhow!("SQLite migration {} failed: {}", version, e))?;
+            tx.execute(
+                "INSERT INTO schema_migrations (version, applied_at) VALUES (?1, ?2)",
+                params![version, chrono::Utc::now()],
+            )?;
+            tx.commit()?;
+            info!("Applied SQLite migration {}", version);
+        }
+
+        Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+    }
+}
+
+#[async_trait]
+impl ObservationStore for SqliteStore {
+    fn backend(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn record(&self, observations: &[Observation]) -> Result<usize, anyhow::Error> {
+        let connection = self.connection.clone();
+        let observations = observations.to_vec();
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap();
+            let tx = connection.transaction()?;
+            let mut inserted = 0;
+            {
+                let mut insert = tx.prepare_cached(
+                    "INSERT INTO observations
+                        (feed_id, symbol, publish_time, price_mantissa, confidence_mantissa, exponent, source, fetched_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                     ON CONFLICT (feed_id, publish_time) DO NOTHING",
+                )?;
+                for observation in &observations {
+                    let confidence = match observation.confidence_i64() {
+                        Ok(confidence) => confidence,
+                        Err(e) => {
+                            warn!("Skipping observation published at {}: {}", observation.publish_time, e);
+                            continue;
+                        }
+                    };
+                    inserted += insert.execute(params![
+                        observation.feed_id,
+                        observation.symbol,
+                        observation.publish_time,
+                        observation.price_mantissa,
+                        confidence,
+                        observation.exponent,
+                        observation.source,
+                        observation.fetched_at,
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            Ok(inserted)
+        })
+        .await?
+    }
+
+    async fn history(&self, query: &HistoryQuery) -> Result<Vec<Observation>, anyhow::Error> {
+        let connection = self.connection.clone();
+        let query = query.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection.lock().unwrap();
+            let mut select = connection.prepare_cached(HISTORY_SQL)?;
+            let rows = select.query_map(
+                params![query.feed_id, query.from, query.to, query.bucket_secs.max(1), query.limit as i64],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?)),
+            )?;
+            rows.map(|row| Observation::from_row(row?)).collect()
+        })
+        .await?
+    }
+
+    async fn recorded_since(
+        &self,
+        feed_id: &str,
+        since: chrono::DateTime<chrono::Utc>,
+        limit: usize,
+    ) -> Result<Vec<Observation>, anyhow::Error> {
+        let connection = self.connection.clone();
+        let feed_id = feed_id.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection.lock().unwrap();
+            let mut select = connection.prepare_cached(RECORDED_SINCE_SQL)?;
+            let rows = select.query_map(params![feed_id, since, limit as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?))
+            })?;
+            rows.map(|row| Observation::from_row(row?)).collect()
+        })
+        .await?
+    }
+
+    async fn backfill_checkpoint(&self, job: &str) -> Result<Option<i64>, anyhow::Error> {
+        let connection = self.connection.clone();
+        let job = job.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection.lock().unwrap();
+            let next_time = connection
+                .query_row("SELECT next_time FROM backfill_progress WHERE job = ?1", [job], |row| row.get(0))
+                .optional()?;
+            Ok(next_time)
+        })
+        .await?
+    }
+
+    async fn save_backfill_checkpoint(&self, job: &str, next_time: i64) -> Result<(), anyhow::Error> {
+        let connection = self.connection.clone();
+        let job = job.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection.lock().unwrap();
+            connection.execute(SAVE_CHECKPOINT_SQL, params![job, next_time, chrono::Utc::now()])?;
+            Ok(())
+        })
+        .await?
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use super::{
+        HistoryQuery, Observation, ObservationStore, HISTORY_SQL, POSTGRES_MIGRATIONS, RECORDED_SINCE_SQL,
+        SAVE_CHECKPOINT_SQL,
+    };
+    use async_trait::async_trait;
+    use tokio::sync::Mutex;
+    use tokio_postgres::{Client, NoTls};
+    use tracing::{error, info, warn};
+
+    pub struct PostgresStore {
+        client: Mutex<Client>,
+    }
+
+    impl PostgresStore {
+        pub async fn connect(url: &str) -> Result<Self, anyhow::Error> {
+            let (mut client, connection) = tokio_postgres::connect(url, NoTls)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to connect to Postgres: {}", e))?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    error!("Postgres connection closed: {}", e);
+                }
+            });
+
+            client
+                .batch_execute(
+                    "CREATE TABLE IF NOT EXISTS schema_migrations (version BIGINT PRIMARY KEY, applied_at TIMESTAMPTZ NOT NULL)",
+                )
+                .await?;
+            for (version, sql) in POSTGRES_MIGRATIONS {
+                let applied: bool = client
+                    .query_one("SELECT EXISTS(SELECT 1 FROM schema_migrations WHERE version = $1)", &[version])
+                    .await?
+                    .get(0);
+                if applied {
+                    continue;
+                }
+                let tx = client.transaction().await?;
+                tx.batch_execute(sql)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Postgres migration {} failed: {}", version, e))?;
+                tx.execute(
+                    "INSERT INTO schema_migrations (version, applied_at) VALUES ($1, $2)",
+                    &[version, &chrono::Utc::now()],
+                )
+                .await?;
+                tx.commit().await?;
+                info!("Applied Postgres migration {}", version);
+            }
+
+            Ok(Self { client: Mutex::new(client) })
+        }
+    }
+
+    #[async_trait]
+    impl ObservationStore for PostgresStore {
+        fn backend(&self) -> &'static str {
+            "postgres"
+        }
+
+        async fn record(&self, observations: &[Observation]) -> Result<usize, anyhow::Error> {
+            let mut client = self.client.lock().await;
+            let tx = client.transaction().await?;
+            let insert = tx
+                .prepare(
+                    "INSERT INTO observations
+                        (feed_id, symbol, publish_time, price_mantissa, confidence_mantissa, exponent, source, fetched_at)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                     ON CONFLICT (feed_id, publish_time) DO NOTHING",
+                )
+                .await?;
+            let mut inserted = 0;
+            for observation in observations {
+                let confidence = match observation.confidence_i64() {
+                    Ok(confidence) => confidence,
+                    Err(e) => {
+                        warn!("Skipping observation published at {}: {}", observation.publish_time, e);
+                        continue;
+                    }
+                };
+                inserted += tx
+                    .execute(
+                        &insert,
+                        &[
+                            &observation.feed_id,
+                            &observation.symbol,
+                            &observation.publish_time,
+                            &observation.price_mantissa,
+                            &confidence,
+                            &observation.exponent,
+                            &observation.source,
+                            &observation.fetched_at,
+                        ],
+                    )
+                    .await? as usize;
+            }
+            tx.commit().await?;
+            Ok(inserted)
+        }
+
+        async fn history(&self, query: &HistoryQuery) -> Result<Vec<Observation>, anyhow::Error> {
+            let client = self.client.lock().await;
+            let rows = client
+                .query(
+                    HISTORY_SQL,
+                    &[&query.feed_id, &query.from, &query.to, &query.bucket_secs.max(1), &(query.limit as i64)],
+                )
+                .await?;
+            rows.into_iter()
+                .map(|row| {
+                    Observation::from_row((
+                        row.get(0),
+                        row.get(1),
+                        row.get(2),
+                        row.get(3),
+                        row.get(4),
+                        row.get(5),
+                        row.get(6),
+                        row.get(7),
+                    ))
+                })
+                .collect()
+        }
+
+        async fn recorded_since(
+            &self,
+            feed_id: &str,
+            since: chrono::DateTime<chrono::Utc>,
+            limit: usize,
+        ) -> Result<Vec<Observation>, anyhow::Error> {
+            let client = self.client.lock().await;
+            let rows = client.query(RECORDED_SINCE_SQL, &[&feed_id, &since, &(limit as i64)]).await?;
+            rows.into_iter()
+                .map(|row| {
+                    Observation::from_row((
+                        row.get(0),
+                        row.get(1),
+                        row.get(2),
+                        row.get(3),
+                        row.get(4),
+                        row.get(5),
+                        row.get(6),
+                        row.get(7),
+                    ))
+                })
+                .collect()
+        }
+
+        async fn backfill_checkpoint(&self, job: &str) -> Result<Option<i64>, anyhow::Error> {
+            let client = self.client.lock().await;
+            let row = client.query_opt("SELECT next_time FROM backfill_progress WHERE job = $1", &[&job]).await?;
+            Ok(row.map(|row| row.get(0)))
+        }
+
+        async fn save_backfill_checkpoint(&self, job: &str, next_time: i64) -> Result<(), anyhow::Error> {
+            let client = self.client.lock().await;
+            client.execute(SAVE_CHECKPOINT_SQL, &[&job, &next_time, &chrono::Utc::now()]).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn observation(feed: &Feed, publish_time: i64, fetched_at: i64) -> Observation {
+        let mut observation = Observation::new(feed, &testing::price(feed, 280_000, 1_000, -5, publish_time));
+        observation.fetched_at = chrono::DateTime::from_timestamp(fetched_at, 0).unwrap();
+        observation
+    }
+
+    fn versions(store: &SqliteStore) -> Vec<i64> {
+        let connection = store.connection.lock().unwrap();
+        let mut select = connection.prepare("SELECT version FROM schema_migrations ORDER BY version").unwrap();
+        select.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect()
+    }
+
+    #[tokio::test]
+    async fn applies_migrations_once() {
+        let path = std::env::temp_dir().join(format!("pyth_gdp_fetcher-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let feed = testing::feed("ECO.US.GDP");
+
+        let store = SqliteStore::open(path).unwrap();
+        assert_eq!(versions(&store), [1, 2, 3]);
+        assert_eq!(store.record(&[observation(&feed, 100, 1_000)]).await.unwrap(), 1);
+        drop(store);
+
+        // Reopening skips applied migrations and keeps the data.
+        let store = SqliteStore::open(path).unwrap();
+        assert_eq!(versions(&store), [1, 2, 3]);
+        let query = HistoryQuery { feed_id: feed.id.clone(), from: 0, to: i64::MAX, bucket_secs: 1, limit: 10 };
+        assert_eq!(store.history(&query).await.unwrap().len(), 1);
+        drop(store);
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+
+    #[tokio::test]
+    async fn records_each_publish_time_once() {
+        let store = SqliteStore::open(":memory:").unwrap();
+        let gdp = testing::feed("ECO.US.GDP");
+        let quarterly = testing::feed("ECO.US.GDPQ125");
+
+        let batch = [observation(&gdp, 100, 1_000), observation(&gdp, 200, 1_000), observation(&quarterly, 100, 1_000)];
+        assert_eq!(store.record(&batch).await.unwrap(), 3);
+        // The same publish times again, and one new one.
+        let batch = [observation(&gdp, 100, 2_000), observation(&gdp, 300, 2_000), observation(&quarterly, 100, 2_000)];
+        assert_eq!(store.record(&batch).await.unwrap(), 1);
+
+        let query = HistoryQuery { feed_id: gdp.id.clone(), from: 0, to: i64::MAX, bucket_secs: 1, limit: 10 };
+        let history = store.history(&query).await.unwrap();
+        let times: Vec<(i64, i64)> = history.iter().map(|o| (o.publish_time, o.fetched_at.timestamp())).collect();
+        assert_eq!(times, [(300, 2_000), (200, 1_000), (100, 1_000)]);
+    }
+
+    #[tokio::test]
+    async fn skips_observations_that_do_not_fit() {
+        let store = SqliteStore::open(":memory:").unwrap();
+        let feed = testing::feed("ECO.US.GDP");
+
+        let mut oversized = observation(&feed, 200, 1_000);
+        oversized.confidence_mantissa = u64::MAX;
+        let batch = [observation(&feed, 100, 1_000), oversized, observation(&feed, 300, 1_000)];
+        assert_eq!(store.record(&batch).await.unwrap(), 2);
+
+        let query = HistoryQuery { feed_id: feed.id.clone(), from: 0, to: i64::MAX, bucket_secs: 1, limit: 10 };
+        let times: Vec<i64> = store.history(&query).await.unwrap().iter().map(|o| o.publish_time).collect();
+        assert_eq!(times, [300, 100]);
+    }
+
+    #[tokio::test]
+    async fn lists_observations_recorded_since() {
+        let store = SqliteStore::open(":memory:").unwrap();
+        let gdp = testing::feed("ECO.US.GDP");
+        let quarterly = testing::feed("ECO.US.GDPQ125");
+        let batch = [
+            observation(&gdp, 400, 1_300),
+            observation(&gdp, 100, 1_000),
+            observation(&gdp, 300, 1_200),
+            observation(&gdp, 200, 1_100),
+            observation(&quarterly, 500, 1_150),
+        ];
+        store.record(&batch).await.unwrap();
+
+        let since = |secs| chrono::DateTime::from_timestamp(secs, 0).unwrap();
+        let times = |observations: Vec<Observation>| observations.iter().map(|o| o.publish_time).collect::<Vec<_>>();
+        // Exclusive of `since`, oldest first, one feed only.
+        assert_eq!(times(store.recorded_since(&gdp.id, since(1_100), 10).await.unwrap()), [300, 400]);
+        assert_eq!(times(store.recorded_since(&gdp.id, since(0), 2).await.unwrap()), [100, 200]);
+        assert_eq!(times(store.recorded_since(&quarterly.id, since(0), 10).await.unwrap()), [500]);
+        assert!(store.recorded_since(&gdp.id, since(1_300), 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn saves_backfill_checkpoints() {
+        let store = SqliteStore::open(":memory:").unwrap();
+        assert_eq!(store.backfill_checkpoint("gdp").await.unwrap(), None);
+
+        store.save_backfill_checkpoint("gdp", 100).await.unwrap();
+        store.save_backfill_checkpoint("gdp", 200).await.unwrap();
+        store.save_backfill_checkpoint("other", 50).await.unwrap();
+        assert_eq!(store.backfill_checkpoint("gdp").await.unwrap(), Some(200));
+        assert_eq!(store.backfill_checkpoint("other").await.unwrap(), Some(50));
+    }
+}
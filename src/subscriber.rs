@@ -0,0 +1,230 @@
+use crate::{
+    cache::PriceCache,
+    config::{Feed, FeedRegistry, SubscriberSettings},
+    hermes::HermesClient,
+    retry,
+    sources::{HermesSource, SourceChain, MAX_HERMES_IDS},
+};
+use serde::Serialize;
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// `source` of prices pushed by the subscriber.
+const STREAM_SOURCE: &str = "Pyth Hermes stream";
+
+/// State of the Hermes subscriber, served by `/subscriber/status`.
+#[derive(Clone, Default, Serialize)]
+pub struct SubscriberStatus {
+    /// Streams opened, one per chunk of feed IDs.
+    pub streams: usize,
+    /// Streams currently connected.
+    pub connected: usize,
+    pub connects: u64,
+    pub events: u64,
+    /// Prices pushed into the cache.
+    pub prices: u64,
+    /// Prices dropped by verification or the quality policy.
+    pub rejected: u64,
+    pub last_event: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// Background task that follows Hermes' `/v2/updates/price/stream` for every
+/// registered feed and pushes each new price into the cache, reconnecting
+/// with exponential backoff whenever a stream fails, ends or goes idle.
+pub struct Subscriber {
+    client: HermesClient,
+    hermes: Arc<HermesSource>,
+    sources: Arc<SourceChain>,
+    registry: Arc<FeedRegistry>,
+    cache: Arc<PriceCache>,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    idle_timeout: Option<Duration>,
+    status: Arc<RwLock<SubscriberStatus>>,
+}
+
+impl Subscriber {
+    pub fn new(
+        client: HermesClient,
+        hermes: Arc<HermesSource>,
+        sources: Arc<SourceChain>,
+        registry: Arc<FeedRegistry>,
+        cache: Arc<PriceCache>,
+        settings: &SubscriberSettings,
+    ) -> Self {
+        let min_backoff = Duration::from_secs(settings.min_backoff_secs.max(1));
+        Self {
+            client,
+            hermes,
+            sources,
+            registry,
+            cache,
+            min_backoff,
+            max_backoff: Duration::from_secs(settings.max_backoff_secs).max(min_backoff),
+            idle_timeout: (settings.idle_timeout_secs > 0).then(|| Duration::from_secs(settings.idle_timeout_secs)),
+            status: Arc::new(RwLock::new(SubscriberStatus::default())),
+        }
+    }
+
+    pub fn status(&self) -> Arc<RwLock<SubscriberStatus>> {
+        self.status.clone()
+    }
+
+    /// Opens one stream per `MAX_HERMES_IDS` feeds.
+    pub fn spawn(self) {
+        let subscriber = Arc::new(self);
+        let chunks: Vec<Vec<Feed>> = subscriber.registry.feeds().chunks(MAX_HERMES_IDS).map(<[Feed]>::to_vec).collect();
+        subscriber.status.write().unwrap().streams = chunks.len();
+        for feeds in chunks {
+            let subscriber = subscriber.clone();
+            tokio::spawn(async move { subscriber.follow(feeds).await });
+        }
+    }
+
+    /// Keeps a stream for `feeds` open forever.
+    async fn follow(&self, feeds: Vec<Feed>) {
+        let mut failures = 0;
+        loop {
+            let received = match self.run(&feeds).await {
+                Ok(received) => {
+                    info!("Pyth Hermes stream for {} feeds ended", feeds.len());
+                    received
+                }
+                Err((received, e)) => {
+                    warn!("Pyth Hermes stream for {} feeds failed: {}", feeds.len(), e);
+                    self.status.write().unwrap().last_error = Some(e.to_string());
+                    received
+                }
+            };
+            // A stream that delivered anything was healthy; start backing off afresh.
+            failures = if received { 0 } else { failures + 1 };
+
+            let wait = self.backoff(failures);
+            info!("Reconnecting to Pyth Hermes stream in {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Follows one connection until it ends. Returns whether any event was
+    /// received, with the error if it failed.
+    async fn run(&self, feeds: &[Feed]) -> Result<bool, (bool, anyhow::Error)> {
+        let ids: Vec<&str> = feeds.iter().map(|feed| feed.id.as_str()).collect();
+        let mut stream = self.client.stream_price_updates(&ids).await.map_err(|e| (false, e))?;
+        info!("Subscribed to Pyth Hermes stream for {} feeds", feeds.len());
+        {
+            let mut status = self.status.write().unwrap();
+            status.connects += 1;
+            status.connected += 1;
+        }
+
+        let feeds: Vec<&Feed> = feeds.iter().collect();
+        let mut received = false;
+        let result = loop {
+            let next = match self.idle_timeout {
+                Some(idle_timeout) => match tokio::time::timeout(idle_timeout, stream.next()).await {
+                    Ok(next) => next,
+                    Err(_) => break Err(anyhow::anyhow!("no event for {:?}", idle_timeout)),
+                },
+                None => stream.next().await,
+            };
+            match next {
+                Ok(Some(updates)) => {
+                    received = true;
+                    self.push(&feeds, updates);
+                }
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.status.write().unwrap().connected -= 1;
+        result.map(|()| received).map_err(|e| (received, e))
+    }
+
+    fn push(&self, feeds: &[&Feed], updates: crate::hermes::PriceUpdates) {
+        let mut prices = 0;
+        let mut rejected = 0;
+        for (feed, result) in feeds.iter().zip(self.hermes.parse_updates(feeds, updates)) {
+            let Some(result) = result else { continue };
+            let accepted = result.and_then(|mut data| {
+                data.source = STREAM_SOURCE.to_string();
+                self.sources.ingest(feed, data)
+            });
+            match accepted {
+                Ok(data) => {
+                    let (price, publish_time) = (data.price, data.publish_time);
+                    if self.cache.insert(feed, data) {
+                        info!("Streamed {}: {:.2} published at {}", feed.symbol, price, publish_time);
+                    }
+                    prices += 1;
+                }
+                Err(e) => {
+                    warn!("Dropping streamed price of {}: {}", feed.symbol, e);
+                    rejected += 1;
+                }
+            }
+        }
+
+        let mut status = self.status.write().unwrap();
+        status.events += 1;
+        status.prices += prices;
+        status.rejected += rejected;
+        status.last_event = Some(chrono::Utc::now());
+    }
+
+    /// The minimum backoff doubled for every consecutive failed attempt, up
+    /// to the maximum, plus up to half of it again as random jitter.
+    fn backoff(&self, failures: u32) -> Duration {
+        let base = retry::backoff(self.min_backoff, self.max_backoff, failures);
+        base + retry::jitter(base / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sources::PriceSource, testing};
+    use reqwest::Client;
+
+    #[tokio::test]
+    async fn reconnects_after_dropped_stream() {
+        let registry = Arc::new(testing::registry());
+        let feed = registry.resolve("ECO.US.GDP").unwrap().clone();
+        let now = chrono::Utc::now().timestamp();
+        let event = |mantissa, publish_time| format!("data: {}\n\n", testing::price_update_json(&feed, mantissa, publish_time)).into_bytes();
+        // The first connection drops after one event; the second carries the update.
+        let url = testing::serve_sse(vec![vec![event(280_000, now - 60)], vec![event(290_000, now)]]).await;
+
+        let client = HermesClient::new(Client::new(), &url, Duration::from_secs(5));
+        let hermes = Arc::new(HermesSource::new(client.clone(), false));
+        let sources = Arc::new(
+            SourceChain::new(vec![hermes.clone() as Arc<dyn PriceSource>], &["hermes".to_string()], &[], &registry).unwrap(),
+        );
+        let cache = Arc::new(PriceCache::new(Duration::from_secs(60), Duration::ZERO, 16, 0));
+        let settings = SubscriberSettings { enabled: true, min_backoff_secs: 1, max_backoff_secs: 1, idle_timeout_secs: 0 };
+        let subscriber = Subscriber::new(client, hermes, sources, registry, cache.clone(), &settings);
+        let status = subscriber.status();
+        subscriber.spawn();
+
+        let streamed = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Some(data) = cache.latest(&feed).filter(|data| data.publish_time == now) {
+                    return data;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("update from the reopened stream never reached the cache");
+        assert_eq!(streamed.exact.price().to_string(), "2.90000");
+        assert_eq!(streamed.source, format!("{} (cached)", STREAM_SOURCE));
+
+        let status = status.read().unwrap();
+        assert_eq!(status.connects, 2);
+        assert_eq!(status.prices, 2);
+    }
+}
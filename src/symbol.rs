@@ -0,0 +1,157 @@
+//! Structured metadata encoded in Pyth economic feed symbols.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// What a symbol such as `ECO.US.GDPQ321` describes: country `US`, indicator
+/// `GDP`, third quarter of 2021. Symbols without a period suffix, such as
+/// `ECO.US.GDP`, only carry country and indicator; unrecognised symbols carry
+/// nothing.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolInfo {
+    pub country: Option<String>,
+    pub indicator: Option<String>,
+    pub quarter: Option<u8>,
+    pub year: Option<i32>,
+    /// First day of the quarter.
+    pub period_start: Option<NaiveDate>,
+    /// Last day of the quarter.
+    pub period_end: Option<NaiveDate>,
+}
+
+impl SymbolInfo {
+    /// Parses `ECO.<country>.<indicator>[Q<quarter><yy>]`, with two-digit
+    /// years taken as 20yy.
+    pub fn parse(symbol: &str) -> Self {
+        let mut parts = symbol.split('.');
+        let (Some("ECO"), Some(country), Some(code), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Self::default();
+        };
+        if country.is_empty() || code.is_empty() {
+            return Self::default();
+        }
+
+        let mut info = Self {
+            country: Some(country.to_string()),
+            indicator: Some(code.to_string()),
+            ..Self::default()
+        };
+
+        if let Some((indicator, quarter, year)) = split_quarter(code)
+            && let Some((start, end)) = quarter_bounds(quarter, year)
+        {
+            info.indicator = Some(indicator.to_string());
+            info.quarter = Some(quarter);
+            info.year = Some(year);
+            info.period_start = Some(start);
+            info.period_end = Some(end);
+        }
+        info
+    }
+}
+
+/// Splits `GDPQ321` into (`GDP`, 3, 2021).
+fn split_quarter(code: &str) -> Option<(&str, u8, i32)> {
+    let split = code.len().checked_sub(4)?;
+    if !code.is_char_boundary(split) {
+        return None;
+    }
+    let (indicator, suffix) = code.split_at(split);
+    let suffix = suffix.as_bytes();
+    if indicator.is_empty() || suffix[0] != b'Q' || !suffix[1..].iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let quarter = suffix[1] - b'0';
+    let year = 2000 + i32::from(suffix[2] - b'0') * 10 + i32::from(suffix[3] - b'0');
+    Some((indicator, quarter, year))
+}
+
+fn quarter_bounds(quarter: u8, year: i32) -> Option<(NaiveDate, NaiveDate)> {
+    if !(1..=4).contains(&quarter) {
+        return None;
+    }
+    let start = NaiveDate::from_ymd_opt(year, u32::from(quarter - 1) * 3 + 1, 1)?;
+    let next = if quarter == 4 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, u32::from(quarter) * 3 + 1, 1)?
+    };
+    Some((start, next.pred_opt()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    #[test]
+    fn parses_registry_symbols() {
+        let registry = crate::testing::registry();
+        assert_eq!(registry.feeds().len(), 23);
+
+        for feed in registry.feeds() {
+            let info = SymbolInfo::parse(&feed.symbol);
+            assert_eq!(info.country.as_deref(), Some("US"), "{}", feed.symbol);
+            assert_eq!(info.indicator.as_deref(), Some("GDP"), "{}", feed.symbol);
+            assert_eq!(info, feed.metadata, "{}", feed.symbol);
+
+            if feed.symbol == "ECO.US.GDP" {
+                assert_eq!((info.quarter, info.year, info.period_start), (None, None, None));
+                continue;
+            }
+            let suffix = feed.symbol.strip_prefix("ECO.US.GDPQ").unwrap();
+            let quarter: u8 = suffix[..1].parse().unwrap();
+            let year = 2000 + suffix[1..].parse::<i32>().unwrap();
+            assert_eq!((info.quarter, info.year), (Some(quarter), Some(year)), "{}", feed.symbol);
+            assert!((2020..=2025).contains(&year), "{}", feed.symbol);
+        }
+    }
+
+    #[test]
+    fn computes_quarter_bounds() {
+        for (symbol, quarter, year, start, end) in [
+            ("ECO.US.GDPQ120", 1, 2020, date(2020, 1, 1), date(2020, 3, 31)),
+            ("ECO.US.GDPQ220", 2, 2020, date(2020, 4, 1), date(2020, 6, 30)),
+            ("ECO.US.GDPQ321", 3, 2021, date(2021, 7, 1), date(2021, 9, 30)),
+            ("ECO.US.GDPQ424", 4, 2024, date(2024, 10, 1), date(2024, 12, 31)),
+            ("ECO.US.GDPQ125", 1, 2025, date(2025, 1, 1), date(2025, 3, 31)),
+            ("ECO.DE.CPIQ199", 1, 2099, date(2099, 1, 1), date(2099, 3, 31)),
+        ] {
+            let info = SymbolInfo::parse(symbol);
+            assert_eq!((info.quarter, info.year), (Some(quarter), Some(year)), "{}", symbol);
+            assert_eq!((info.period_start, info.period_end), (start, end), "{}", symbol);
+        }
+    }
+
+    #[test]
+    fn malformed_quarters_keep_the_whole_code() {
+        for symbol in [
+            "ECO.US.GDPQ520",  // no fifth quarter
+            "ECO.US.GDPQ020",  // nor a zeroth
+            "ECO.US.GDPQ1",    // missing year
+            "ECO.US.GDPQ12",   // one-digit year
+            "ECO.US.GDPq121",  // lowercase marker
+            "ECO.US.GDPX121",  // not a quarter marker
+            "ECO.US.GDPQ1A1",  // non-digit year
+            "ECO.US.Q121",     // no indicator before the quarter
+            "ECO.US.GDPQ1210", // trailing digit
+        ] {
+            let info = SymbolInfo::parse(symbol);
+            let code = symbol.strip_prefix("ECO.US.").unwrap();
+            assert_eq!(info.indicator.as_deref(), Some(code), "{}", symbol);
+            assert_eq!((info.quarter, info.year, info.period_start, info.period_end), (None, None, None, None), "{}", symbol);
+        }
+    }
+
+    #[test]
+    fn unrecognised_symbols_carry_nothing() {
+        for symbol in ["", "ECO", "ECO.US", "eco.us.gdp", "eco.US.GDPQ121", "FX.EUR/USD", "ECO..GDP", "ECO.US.", "ECO.US.GDP.Q1", "Crypto.BTC/USD"] {
+            assert_eq!(SymbolInfo::parse(symbol), SymbolInfo::default(), "{}", symbol);
+        }
+        // Multi-byte characters must not split inside a code point.
+        assert_eq!(SymbolInfo::parse("ECO.US.GDPé").indicator.as_deref(), Some("GDPé"));
+    }
+}
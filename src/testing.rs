@@ -0,0 +1,335 @@
+//! Feeds and prices shared by the unit tests.
+
+use crate::{
+    cache::PriceCache,
+    config::{Config, Feed, FeedRegistry, UpstreamArgs, Upstreams},
+    metrics::Metrics,
+    price::ExactPrice,
+    sources::{PriceSource, SourceChain},
+    websocket, AppState, PriceData,
+};
+use async_trait::async_trait;
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The registry shipped in `config/default.toml`.
+pub fn registry() -> FeedRegistry {
+    Config::from_toml(include_str!("../config/default.toml")).unwrap().registry
+}
+
+/// A registry of `feeds`, with made-up IDs and optional per-feed source
+/// orders; the first feed is the headline.
+pub fn feed_registry(feeds: &[(&str, Option<&[&str]>)]) -> FeedRegistry {
+    let mut toml = format!("headline = \"{}\"\n", feeds[0].0);
+    for (index, (symbol, sources)) in feeds.iter().enumerate() {
+        toml.push_str(&format!("[[feeds]]\nsymbol = \"{}\"\nid = \"0x{:064x}\"\n", symbol, index + 1));
+        if let Some(sources) = sources {
+            toml.push_str(&format!("sources = {:?}\n", sources));
+        }
+    }
+    Config::from_toml(&toml).unwrap().registry
+}
+
+/// Server state over the default registry with `source` as the only
+/// source, no cache TTL and nothing running in the background.
+pub fn app_state(source: Arc<StubSource>) -> AppState {
+    let registry = registry();
+    let metrics = Arc::new(Metrics::default());
+    let sources = SourceChain::new(vec![source.clone()], &[source.name.to_string()], &[], &registry)
+        .unwrap()
+        .with_metrics(metrics.clone());
+    AppState {
+        sonic_rpc_client: Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
+            "http://127.0.0.1:1".to_string(),
+        )),
+        sources: Arc::new(sources),
+        registry: Arc::new(registry),
+        upstreams: Arc::new(upstreams()),
+        cache: Arc::new(PriceCache::new(Duration::ZERO, Duration::ZERO, 16, 16).with_metrics(metrics.clone())),
+        fetch_deadline: Duration::from_secs(5),
+        poller: None,
+        subscriber: None,
+        watcher: None,
+        store: None,
+        backfiller: None,
+        admin_token: None,
+        stream_limits: websocket::Limits { heartbeat: Duration::from_secs(15), send_timeout: Duration::from_secs(5) },
+        max_replay: 100,
+        metrics,
+    }
+}
+
+/// The upstreams shipped in `config/default.toml`, without any overrides.
+pub fn upstreams() -> Upstreams {
+    let config = Config::from_toml(include_str!("../config/default.toml")).unwrap();
+    Upstreams::resolve(config.upstreams, UpstreamArgs::default()).unwrap()
+}
+
+pub fn feed(symbol: &str) -> Feed {
+    registry().resolve(symbol).unwrap().clone()
+}
+
+/// A price for `feed` as a source would return it, before any quality checks.
+pub fn price(feed: &Feed, mantissa: i64, conf: u64, expo: i32, publish_time: i64) -> PriceData {
+    let exact = ExactPrice::new(mantissa, conf, expo).unwrap();
+    PriceData {
+        symbol: feed.symbol.clone(),
+        price: exact.price().to_f64(),
+        confidence: exact.conf().to_f64(),
+        exact,
+        publish_time,
+        price_feed_id: feed.id.clone(),
+        last_updated: chrono::Utc::now(),
+        source: "test".to_string(),
+        description: feed.description.clone(),
+        unit: feed.unit.clone(),
+        ema_price: None,
+        ema_confidence: None,
+        slot: None,
+        prev_publish_time: None,
+        update_data: None,
+        verified: false,
+        status: None,
+        stale: false,
+        low_confidence: false,
+        metadata: feed.metadata.clone(),
+    }
+}
+
+/// `PriceUpdateV2` account data for `feed_id`, priced at 2.80000 ± 0.01000;
+/// `level` is the Borsh-encoded verification level.
+pub fn price_update_account(discriminator: [u8; 8], level: &[u8], feed_id: &[u8; 32]) -> Vec<u8> {
+    let mut data = discriminator.to_vec();
+    data.extend([7u8; 32]); // write authority
+    data.extend(level);
+    data.extend(feed_id);
+    data.extend(280_000i64.to_le_bytes());
+    data.extend(1_000u64.to_le_bytes());
+    data.extend((-5i32).to_le_bytes());
+    data.extend(1_753_920_000i64.to_le_bytes());
+    data.extend(1_745_971_200i64.to_le_bytes());
+    data.extend(279_000i64.to_le_bytes());
+    data.extend(1_100u64.to_le_bytes());
+    data.extend(301_234_567u64.to_le_bytes());
+    data
+}
+
+/// Legacy V2 price account data with a distinct value in every field read.
+pub fn legacy_price_account(magic: u32, version: u32, account_type: u32, status: u32) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend(magic.to_le_bytes());
+    data.extend(version.to_le_bytes());
+    data.extend(account_type.to_le_bytes());
+    data.extend(3_312u32.to_le_bytes()); // size
+    data.extend(1u32.to_le_bytes()); // price type
+    data.extend((-5i32).to_le_bytes()); // exponent
+    data.extend(9u32.to_le_bytes()); // number of components
+    data.extend(8u32.to_le_bytes()); // number of quoters
+    data.extend(301_000_001u64.to_le_bytes()); // last slot
+    data.extend(301_000_002u64.to_le_bytes()); // valid slot
+    for value in [279_500i64, 55_900_000, 200] {
+        data.extend(value.to_le_bytes()); // ema price, numerator, denominator
+    }
+    for value in [1_150i64, 230_000, 200] {
+        data.extend(value.to_le_bytes()); // ema confidence, numerator, denominator
+    }
+    data.extend(1_753_920_005i64.to_le_bytes()); // timestamp
+    data.extend([3, 0, 0, 0, 0, 0, 0, 0]); // min publishers, padding
+    data.extend([11u8; 32]); // product
+    data.extend([12u8; 32]); // next price account
+    data.extend(300_999_990u64.to_le_bytes()); // previous slot
+    data.extend(275_000i64.to_le_bytes()); // previous price
+    data.extend(900u64.to_le_bytes()); // previous confidence
+    data.extend(1_745_971_200i64.to_le_bytes()); // previous publish time
+    data.extend(280_000i64.to_le_bytes()); // aggregate price
+    data.extend(1_000u64.to_le_bytes()); // aggregate confidence
+    data.extend(status.to_le_bytes());
+    data.extend(0u32.to_le_bytes()); // corporate action
+    data.extend(301_000_000u64.to_le_bytes()); // aggregate publish slot
+    data.resize(3_312, 0); // publisher components
+    data
+}
+
+/// One `/v2/updates/price/stream` event body carrying a single parsed price.
+pub fn price_update_json(feed: &Feed, mantissa: i64, publish_time: i64) -> String {
+    serde_json::json!({
+        "binary": { "encoding": "hex", "data": [] },
+        "parsed": [{
+            "id": feed.id.trim_start_matches("0x"),
+            "price": { "price": mantissa.to_string(), "conf": "1000", "expo": -5, "publish_time": publish_time },
+            "ema_price": { "price": mantissa.to_string(), "conf": "1000", "expo": -5, "publish_time": publish_time },
+            "metadata": { "slot": 1, "prev_publish_time": publish_time - 1 },
+        }],
+    })
+    .to_string()
+}
+
+/// Serves `text/event-stream` on a local port: the `n`th connection gets the
+/// `n`th list of chunks, each written separately, and is then closed.
+/// Connections beyond the script are closed at once. Returns the base URL.
+pub async fn serve_sse(connections: Vec<Vec<Vec<u8>>>) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut connections = connections.into_iter();
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else { return };
+            let Some(chunks) = connections.next() else { continue };
+
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n";
+            let _ = socket.write_all(head.as_bytes()).await;
+            for chunk in chunks {
+                let _ = socket.write_all(&chunk).await;
+                let _ = socket.flush().await;
+                // Give the client a chance to read each chunk on its own.
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        }
+    });
+    url
+}
+
+/// Serves `router` on a local port. Returns the base URL.
+pub async fn serve(router: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    url
+}
+
+/// The `ids[]` of a Hermes request's query string.
+pub fn hermes_ids(query: &str) -> Vec<String> {
+    let url = reqwest::Url::parse(&format!("http://hermes/?{}", query)).unwrap();
+    url.query_pairs().filter(|(key, _)| key == "ids[]").map(|(_, id)| id.into_owned()).collect()
+}
+
+/// Serves JSON-RPC on a local port, answering each call with
+/// `handler(method, params)`: `Ok` is the result, `Err` an error message.
+/// `getVersion`, which the RPC client sends first, is answered here.
+/// Returns the URL.
+pub async fn serve_rpc<F>(handler: F) -> String
+where
+    F: Fn(&str, &serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync + 'static,
+{
+    use axum::{routing::post, Json, Router};
+
+    let handler = Arc::new(handler);
+    let router = Router::new().route(
+        "/",
+        post(move |Json(request): Json<serde_json::Value>| async move {
+            let method = request["method"].as_str().unwrap_or_default();
+            let result = match method {
+                "getVersion" => Ok(serde_json::json!({ "solana-core": "1.16.27", "feature-set": 0 })),
+                _ => handler(method, &request["params"]),
+            };
+            let response = match result {
+                Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "result": result, "id": request["id"] }),
+                Err(message) => serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "error": { "code": -32000, "message": message },
+                    "id": request["id"],
+                }),
+            };
+            Json(response)
+        }),
+    );
+    serve(router).await
+}
+
+/// `account` as JSON-RPC returns it, base64-encoded.
+pub fn ui_account(pubkey: &Pubkey, account: &Account) -> serde_json::Value {
+    let account = UiAccount::encode(pubkey, account, UiAccountEncoding::Base64, None, None);
+    serde_json::to_value(account).unwrap()
+}
+
+/// What a [`StubSource`] does for a feed.
+#[derive(Clone, Copy)]
+pub enum Outcome {
+    /// Returns the feed's price with this mantissa (at exponent -5).
+    Price(i64),
+    Fail,
+    /// Never answers; the whole batch hangs.
+    Hang,
+}
+
+/// A scripted [`PriceSource`] that records every batch it is asked for.
+pub struct StubSource {
+    name: &'static str,
+    default: Mutex<Outcome>,
+    outcomes: HashMap<String, Outcome>,
+    batches: Mutex<Vec<Vec<String>>>,
+}
+
+impl StubSource {
+    /// A source that returns `default` for every feed.
+    pub fn new(name: &'static str, default: Outcome) -> Self {
+        Self { name, default: Mutex::new(default), outcomes: HashMap::new(), batches: Mutex::new(Vec::new()) }
+    }
+
+    pub fn with(mut self, symbol: &str, outcome: Outcome) -> Self {
+        self.outcomes.insert(symbol.to_string(), outcome);
+        self
+    }
+
+    /// Changes what the source does for feeds without their own outcome.
+    pub fn set_default(&self, outcome: Outcome) {
+        *self.default.lock().unwrap() = outcome;
+    }
+
+    /// The symbols of every `fetch_many` call so far, in call order.
+    pub fn batches(&self) -> Vec<Vec<String>> {
+        self.batches.lock().unwrap().clone()
+    }
+
+    fn outcome(&self, feed: &Feed) -> Outcome {
+        self.outcomes.get(&feed.symbol).copied().unwrap_or(*self.default.lock().unwrap())
+    }
+}
+
+#[async_trait]
+impl PriceSource for StubSource {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn label(&self) -> &'static str {
+        self.name
+    }
+
+    async fn fetch(&self, feed: &Feed) -> Result<PriceData, anyhow::Error> {
+        self.fetch_many(&[feed], 1).await.remove(0)
+    }
+
+    async fn fetch_many(&self, feeds: &[&Feed], _concurrency: usize) -> Vec<Result<PriceData, anyhow::Error>> {
+        self.batches.lock().unwrap().push(feeds.iter().map(|feed| feed.symbol.clone()).collect());
+        if feeds.iter().any(|feed| matches!(self.outcome(feed), Outcome::Hang)) {
+            std::future::pending::<()>().await;
+        }
+        feeds
+            .iter()
+            .map(|feed| match self.outcome(feed) {
+                Outcome::Price(mantissa) => {
+                    let mut data = price(feed, mantissa, 1_000, -5, chrono::Utc::now().timestamp());
+                    data.source = self.name.to_string();
+                    Ok(data)
+                }
+                _ => Err(anyhow::anyhow!("{} is down", self.name)),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,475 @@
+//! `accountSubscribe` watcher for the feed accounts read by the on-chain
+//! sources. Every notification is decoded like a fetched account and pushed
+//! into the cache; while a source's PubSub endpoint is unavailable its
+//! accounts are polled instead.
+
+use crate::{
+    cache::PriceCache,
+    config::{Feed, FeedRegistry, Upstreams, WatcherSettings},
+    retry,
+    sources::{AccountSource, SourceChain},
+};
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig, rpc_response::Response,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    #[default]
+    Connecting,
+    /// Every account is subscribed.
+    Subscribed,
+    /// PubSub is unavailable; the accounts are polled until it is back.
+    Polling,
+}
+
+/// State of the watch on one source.
+#[derive(Clone, Default, Serialize)]
+pub struct SourceWatch {
+    pub mode: WatchMode,
+    /// Feed accounts the source has.
+    pub accounts: usize,
+    pub connects: u64,
+    pub notifications: u64,
+    pub polls: u64,
+    /// Prices pushed into the cache, from notifications and polls.
+    pub prices: u64,
+    /// Account reads that failed, or accounts that could not be decoded or
+    /// failed the quality policy.
+    pub failed: u64,
+    pub last_update: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// State of the account watcher, served by `/watcher/status`.
+#[derive(Clone, Default, Serialize)]
+pub struct WatcherStatus {
+    pub sources: BTreeMap<&'static str, SourceWatch>,
+}
+
+/// Background tasks, one per watched source, that keep an `accountSubscribe`
+/// subscription open for every feed account, resubscribing with exponential
+/// backoff when the connection drops.
+pub struct Watcher {
+    sources: Vec<Arc<dyn AccountSource>>,
+    chain: Arc<SourceChain>,
+    registry: Arc<FeedRegistry>,
+    cache: Arc<PriceCache>,
+    upstreams: Arc<Upstreams>,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    poll_interval: Duration,
+    status: Arc<RwLock<WatcherStatus>>,
+}
+
+impl Watcher {
+    /// Watches the sources in `available` named by `[watcher].sources`.
+    pub fn new(
+        available: Vec<Arc<dyn AccountSource>>,
+        chain: Arc<SourceChain>,
+        registry: Arc<FeedRegistry>,
+        cache: Arc<PriceCache>,
+        upstreams: Arc<Upstreams>,
+        settings: &WatcherSettings,
+    ) -> Result<Self, anyhow::Error> {
+        let mut sources = Vec::new();
+        for name in &settings.sources {
+            let source = available
+                .iter()
+                .find(|source| source.name() == name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown on-chain source {}", name))?;
+            if !sources.iter().any(|s: &Arc<dyn AccountSource>| s.name() == name) {
+                sources.push(source.clone());
+            }
+        }
+
+        let min_backoff = Duration::from_secs(settings.min_backoff_secs.max(1));
+        Ok(Self {
+            sources,
+            chain,
+            registry,
+            cache,
+            upstreams,
+            min_backoff,
+            max_backoff: Duration::from_secs(settings.max_backoff_secs).max(min_backoff),
+            poll_interval: Duration::from_secs(settings.poll_interval_secs.max(1)),
+            status: Arc::new(RwLock::new(WatcherStatus::default())),
+        })
+    }
+
+    pub fn status(&self) -> Arc<RwLock<WatcherStatus>> {
+        self.status.clone()
+    }
+
+    pub fn spawn(self) {
+        let watcher = Arc::new(self);
+        for source in watcher.sources.clone() {
+            let feeds: Vec<(Feed, Pubkey)> = watcher
+                .registry
+                .feeds()
+                .iter()
+                .filter_map(|feed| Some((feed.clone(), source.account(feed)?)))
+                .collect();
+            if feeds.is_empty() {
+                warn!("No feed accounts to watch on {}", source.label());
+                continue;
+            }
+            watcher.update(source.as_ref(), |watch| watch.accounts = feeds.len());
+
+            let watcher = watcher.clone();
+            tokio::spawn(async move { watcher.watch(source, feeds).await });
+        }
+    }
+
+    /// Keeps `source`'s accounts subscribed forever, polling them whenever
+    /// PubSub cannot be reached.
+    async fn watch(&self, source: Arc<dyn AccountSource>, feeds: Vec<(Feed, Pubkey)>) {
+        let mut failures = 0;
+        let mut last_poll: Option<Instant> = None;
+        loop {
+            // A connection that got its subscriptions in was healthy; start backing off afresh.
+            match self.connect(source.as_ref(), &feeds).await {
+                Ok(()) => {
+                    info!("{} PubSub connection closed", source.label());
+                    failures = 0;
+                }
+                Err(e) => {
+                    warn!("{} PubSub unavailable: {}", source.label(), e);
+                    self.update(source.as_ref(), |watch| watch.last_error = Some(e.to_string()));
+                    failures += 1;
+                }
+            }
+
+            let retry_at = Instant::now() + self.backoff(failures);
+            if failures == 0 {
+                self.update(source.as_ref(), |watch| watch.mode = WatchMode::Connecting);
+                tokio::time::sleep_until(retry_at).await;
+                continue;
+            }
+
+            // PubSub is unavailable: poll until the next attempt.
+            self.update(source.as_ref(), |watch| watch.mode = WatchMode::Polling);
+            loop {
+                if last_poll.is_none_or(|at| at.elapsed() >= self.poll_interval) {
+                    self.poll(source.as_ref(), &feeds).await;
+                    last_poll = Some(Instant::now());
+                }
+                let next_poll = last_poll.map_or(retry_at, |at| at + self.poll_interval);
+                if next_poll >= retry_at {
+                    break;
+                }
+                tokio::time::sleep_until(next_poll).await;
+            }
+            info!("Resubscribing to {} accounts in {:?}", source.label(), retry_at.saturating_duration_since(Instant::now()));
+            tokio::time::sleep_until(retry_at).await;
+        }
+    }
+
+    /// Subscribes to every account over one connection and handles its
+    /// notifications until it closes. Fails if the subscriptions cannot be made.
+    async fn connect(&self, source: &dyn AccountSource, feeds: &[(Feed, Pubkey)]) -> Result<(), anyhow::Error> {
+        let fail = |e: String| anyhow::anyhow!("{}", self.upstreams.scrub(&e));
+        let endpoint = source.ws_endpoint();
+        let client = self.bounded(PubsubClient::new(&endpoint)).await.map_err(fail)?;
+
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.upstreams.commitment),
+            ..RpcAccountInfoConfig::default()
+        };
+        let mut subscriptions = Vec::new();
+        for (index, (_, pubkey)) in feeds.iter().enumerate() {
+            // Dropping the client closes the connection, so the unsubscribe
+            // functions are never needed.
+            let (notifications, _) = self.bounded(client.account_subscribe(pubkey, Some(config.clone()))).await.map_err(fail)?;
+            subscriptions.push(notifications.map(move |response| (index, response)));
+        }
+        info!("Subscribed to {} {} accounts", feeds.len(), source.label());
+        self.update(source, |watch| {
+            watch.mode = WatchMode::Subscribed;
+            watch.connects += 1;
+        });
+
+        // Catch up on anything that changed while unsubscribed.
+        self.poll(source, feeds).await;
+
+        let mut notifications = stream::select_all(subscriptions);
+        while let Some((index, response)) = notifications.next().await {
+            let (feed, pubkey) = &feeds[index];
+            self.notify(source, feed, pubkey, response);
+        }
+        Ok(())
+    }
+
+    /// Bounds a PubSub request by the RPC timeout.
+    async fn bounded<T, E: std::fmt::Display>(&self, request: impl Future<Output = Result<T, E>>) -> Result<T, String> {
+        match tokio::time::timeout(self.upstreams.rpc_timeout, request).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("no answer within {:?}", self.upstreams.rpc_timeout)),
+        }
+    }
+
+    fn notify(&self, source: &dyn AccountSource, feed: &Feed, pubkey: &Pubkey, response: Response<UiAccount>) {
+        self.update(source, |watch| watch.notifications += 1);
+        let result = response
+            .value
+            .decode::<Account>()
+            .ok_or_else(|| anyhow::anyhow!("Notification for {} carries no account data", pubkey))
+            .and_then(|account| source.decode(feed, pubkey, &account))
+            .map(|mut data| {
+                data.source = format!("{} PubSub", source.label());
+                data
+            });
+        self.push(source, feed, result);
+    }
+
+    /// Reads every account once, as a fetch from the source would.
+    async fn poll(&self, source: &dyn AccountSource, feeds: &[(Feed, Pubkey)]) {
+        let refs: Vec<&Feed> = feeds.iter().map(|(feed, _)| feed).collect();
+        let results = source.fetch_many(&refs, self.chain.concurrency()).await;
+        self.update(source, |watch| watch.polls += 1);
+        for (feed, result) in refs.into_iter().zip(results) {
+            self.push(source, feed, result);
+        }
+    }
+
+    fn push(&self, source: &dyn AccountSource, feed: &Feed, result: Result<crate::PriceData, anyhow::Error>) {
+        match result.and_then(|data| self.chain.ingest(feed, data)) {
+            Ok(data) => {
+                let (price, publish_time) = (data.price, data.publish_time);
+                if self.cache.insert(feed, data) {
+                    info!("{} account of {} changed: {:.2} published at {}", source.label(), feed.symbol, price, publish_time);
+                }
+                self.update(source, |watch| {
+                    watch.prices += 1;
+                    watch.last_update = Some(chrono::Utc::now());
+                });
+            }
+            Err(e) => {
+                warn!("Dropping {} account update of {}: {}", source.label(), feed.symbol, e);
+                self.update(source, |watch| watch.failed += 1);
+            }
+        }
+    }
+
+    fn update(&self, source: &dyn AccountSource, apply: impl FnOnce(&mut SourceWatch)) {
+        apply(self.status.write().unwrap().sources.entry(source.name()).or_default());
+    }
+
+    /// The minimum backoff doubled for every consecutive failed attempt, up
+    /// to the maximum, plus up to half of it again as random jitter.
+    fn backoff(&self, failures: u32) -> Duration {
+        let base = retry::backoff(self.min_backoff, self.max_backoff, failures);
+        base + retry::jitter(base / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sources::PriceSource, testing, PriceData};
+    use async_trait::async_trait;
+    use std::{
+        str::FromStr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
+
+    /// An on-chain source whose accounts hold a little-endian price mantissa
+    /// and whose reads return `polled`.
+    struct StubAccounts {
+        ws_endpoint: String,
+        polled: i64,
+        polls: Mutex<Vec<Instant>>,
+        connects: AtomicUsize,
+    }
+
+    impl StubAccounts {
+        fn new(ws_endpoint: &str, polled: i64) -> Arc<Self> {
+            Arc::new(Self { ws_endpoint: ws_endpoint.to_string(), polled, polls: Mutex::new(Vec::new()), connects: AtomicUsize::new(0) })
+        }
+    }
+
+    #[async_trait]
+    impl PriceSource for StubAccounts {
+        fn name(&self) -> &'static str {
+            "sonic"
+        }
+
+        fn label(&self) -> &'static str {
+            "Stub"
+        }
+
+        async fn fetch(&self, feed: &Feed) -> Result<PriceData, anyhow::Error> {
+            self.polls.lock().unwrap().push(Instant::now());
+            let mut data = testing::price(feed, self.polled, 1_000, -5, chrono::Utc::now().timestamp());
+            data.source = "Stub".to_string();
+            Ok(data)
+        }
+    }
+
+    impl AccountSource for StubAccounts {
+        fn ws_endpoint(&self) -> String {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            self.ws_endpoint.clone()
+        }
+
+        fn account(&self, feed: &Feed) -> Option<Pubkey> {
+            Some(Pubkey::new_from_array(feed.id_bytes))
+        }
+
+        fn decode(&self, feed: &Feed, _pubkey: &Pubkey, account: &Account) -> Result<PriceData, anyhow::Error> {
+            let mantissa = i64::from_le_bytes(account.data[..8].try_into()?);
+            Ok(testing::price(feed, mantissa, 1_000, -5, chrono::Utc::now().timestamp()))
+        }
+    }
+
+    /// Watches `source` for a single feed, returned with the cache.
+    fn watcher(source: &Arc<StubAccounts>, min_backoff_secs: u64, poll_interval_secs: u64) -> (Watcher, Arc<PriceCache>, Feed) {
+        let registry = testing::feed_registry(&[("ECO.US.GDP", None)]);
+        let feed = registry.headline().clone();
+        let chain = SourceChain::new(vec![source.clone()], &["sonic".to_string()], &[], &registry).unwrap();
+        let cache = Arc::new(PriceCache::new(Duration::from_secs(60), Duration::ZERO, 16, 0));
+        let settings = WatcherSettings {
+            enabled: true,
+            sources: vec!["sonic".to_string()],
+            min_backoff_secs,
+            max_backoff_secs: min_backoff_secs,
+            poll_interval_secs,
+        };
+        let watcher = Watcher::new(
+            vec![source.clone()],
+            Arc::new(chain),
+            Arc::new(registry),
+            cache.clone(),
+            Arc::new(testing::upstreams()),
+            &settings,
+        )
+        .unwrap();
+        (watcher, cache, feed)
+    }
+
+    /// Serves RPC PubSub on a local port: each connection has its
+    /// `accountSubscribe` calls answered, gets one notification per
+    /// subscription carrying the connection's number as the price, and is
+    /// then closed. Returns the URL.
+    async fn serve_pubsub() -> String {
+        use axum::{
+            extract::ws::{Message, WebSocket, WebSocketUpgrade},
+            routing::get,
+            Router,
+        };
+
+        async fn session(mut socket: WebSocket, number: i64) {
+            while let Some(Ok(Message::Text(text))) = socket.recv().await {
+                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                assert_eq!(request["method"], "accountSubscribe");
+                let subscription = request["id"].as_u64().unwrap() + 100;
+                let response = serde_json::json!({ "jsonrpc": "2.0", "result": subscription, "id": request["id"] });
+                socket.send(Message::Text(response.to_string())).await.unwrap();
+
+                let pubkey = Pubkey::from_str(request["params"][0].as_str().unwrap()).unwrap();
+                let account = Account { data: number.to_le_bytes().to_vec(), ..Account::default() };
+                let notification = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "accountNotification",
+                    "params": {
+                        "result": { "context": { "slot": 1 }, "value": testing::ui_account(&pubkey, &account) },
+                        "subscription": subscription,
+                    },
+                });
+                socket.send(Message::Text(notification.to_string())).await.unwrap();
+                let _ = socket.send(Message::Close(None)).await;
+            }
+        }
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route(
+            "/",
+            get(move |upgrade: WebSocketUpgrade| {
+                let number = connections.fetch_add(1, Ordering::SeqCst) as i64 + 1;
+                async move { upgrade.on_upgrade(move |socket| session(socket, number)) }
+            }),
+        );
+        testing::serve(router).await.replace("http", "ws")
+    }
+
+    fn watch(status: &Arc<RwLock<WatcherStatus>>) -> SourceWatch {
+        status.read().unwrap().sources.get("sonic").cloned().unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn resubscribes_after_a_disconnect() {
+        let source = StubAccounts::new(&serve_pubsub().await, 7);
+        let (watcher, cache, feed) = watcher(&source, 1, 30);
+        let status = watcher.status();
+        watcher.spawn();
+
+        let started = std::time::Instant::now();
+        while watch(&status).notifications < 2 {
+            assert!(started.elapsed() < Duration::from_secs(5), "no second notification");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let watch = watch(&status);
+        assert_eq!((watch.connects, watch.accounts), (2, 1));
+        // Each connection starts with a poll; the second one's notification came after it.
+        assert_eq!(watch.polls, 2);
+        assert_eq!(watch.last_error, None);
+        let latest = cache.latest(&feed).unwrap();
+        assert_eq!((latest.exact.price, latest.source.as_str()), (2, "Stub PubSub (cached)"));
+    }
+
+    #[tokio::test]
+    async fn polls_when_pubsub_cannot_be_reached() {
+        let source = StubAccounts::new("ws://127.0.0.1:1", 7);
+        let (watcher, cache, feed) = watcher(&source, 60, 30);
+        let status = watcher.status();
+        watcher.spawn();
+
+        let started = std::time::Instant::now();
+        while watch(&status).polls == 0 {
+            assert!(started.elapsed() < Duration::from_secs(5), "never polled");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let watch = watch(&status);
+        assert_eq!((watch.mode, watch.connects, watch.prices), (WatchMode::Polling, 0, 1));
+        assert!(watch.last_error.is_some());
+        let latest = cache.latest(&feed).unwrap();
+        assert_eq!((latest.exact.price, latest.source.as_str()), (7, "Stub (cached)"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_the_poll_interval_across_reconnect_attempts() {
+        // Not a URL: every attempt fails at once, without touching the network.
+        let source = StubAccounts::new("not a url", 7);
+        let (watcher, _, _) = watcher(&source, 3, 2);
+        watcher.spawn();
+
+        let start = Instant::now();
+        tokio::time::sleep(Duration::from_secs(29)).await;
+
+        // Attempts every 3 to 4.5s: the backoff, capped at 3s, plus jitter.
+        let attempts = source.connects.load(Ordering::SeqCst);
+        assert!((7..=10).contains(&attempts), "{} attempts", attempts);
+        // Polls every 2s from the start, however the attempts fall between them.
+        let polls: Vec<Duration> = source.polls.lock().unwrap().iter().map(|at| *at - start).collect();
+        let expected: Vec<Duration> = (0..=28).step_by(2).map(Duration::from_secs).collect();
+        assert_eq!(polls, expected);
+    }
+}
@@ -0,0 +1,360 @@
+//! `/ws`: live price updates for the feeds a client subscribes to.
+//!
+//! Clients send JSON text messages:
+//!
+//! - `{"type": "subscribe", "symbols": ["ECO.US.GDP"]}` adds feeds (symbols or
+//!   hex IDs; every feed when `symbols` is empty) and is answered with a
+//!   snapshot of their current prices
+//! - `{"type": "unsubscribe", "symbols": [...]}` removes feeds (all when empty)
+//! - `{"type": "ping"}` is answered with `{"type": "pong"}`
+//!
+//! and receive a `price` message whenever a subscribed feed's `publish_time`
+//! or price changes.
+
+use crate::{config::{Feed, StreamSettings}, AppState, PriceData};
+use axum::extract::ws::{Message, WebSocket};
+use futures::{stream::SplitSink, SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+use tokio::{sync::broadcast::error::RecvError, time::Instant};
+use tracing::{info, warn};
+
+/// Heartbeat and backpressure limits of every connection.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub heartbeat: Duration,
+    pub send_timeout: Duration,
+}
+
+impl From<&StreamSettings> for Limits {
+    fn from(settings: &StreamSettings) -> Self {
+        Self {
+            heartbeat: Duration::from_secs(settings.heartbeat_secs.max(1)),
+            send_timeout: Duration::from_secs(settings.send_timeout_secs.max(1)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    Subscribe {
+        #[serde(default)]
+        symbols: Vec<String>,
+    },
+    Unsubscribe {
+        #[serde(default)]
+        symbols: Vec<String>,
+    },
+    Ping,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerMessage {
+    /// The full set of subscribed symbols after a subscribe or unsubscribe.
+    Subscribed { symbols: Vec<String> },
+    Price { data: Box<PriceData> },
+    /// The client fell `skipped` updates behind; a snapshot of its feeds follows.
+    Lagged { skipped: u64 },
+    Error { error: String },
+    Pong,
+}
+
+/// Last `(publish_time, price mantissa, exponent)` sent per feed ID, so that
+/// a price reaching the client twice (from a snapshot and the broadcast) is
+/// only sent once.
+type Sent = HashMap<String, (i64, i64, i32)>;
+
+struct Connection {
+    state: AppState,
+    sink: SplitSink<WebSocket, Message>,
+    send_timeout: Duration,
+    /// Subscribed feed IDs.
+    feeds: HashSet<String>,
+    sent: Sent,
+}
+
+/// Serves one client until it disconnects, stops answering heartbeats or
+/// blocks a send for longer than the send timeout.
+pub async fn serve(socket: WebSocket, state: AppState, limits: Limits) {
+    let mut updates = state.cache.subscribe();
+    let (sink, mut stream) = socket.split();
+    let mut connection = Connection {
+        state,
+        sink,
+        send_timeout: limits.send_timeout,
+        feeds: HashSet::new(),
+        sent: HashMap::new(),
+    };
+
+    let mut heartbeat = tokio::time::interval_at(Instant::now() + limits.heartbeat, limits.heartbeat);
+    let mut last_seen = Instant::now();
+    info!("WebSocket client connected");
+
+    let reason = loop {
+        let result = tokio::select! {
+            message = stream.next() => {
+                last_seen = Instant::now();
+                match message {
+                    Some(Ok(Message::Text(text))) => connection.handle(&text).await,
+                    Some(Ok(Message::Binary(_))) => {
+                        connection.send(ServerMessage::Error { error: "Expected a JSON text message".to_string() }).await
+                    }
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => Ok(()),
+                    Some(Ok(Message::Close(_))) | None => break "closed by client".to_string(),
+                    Some(Err(e)) => break e.to_string(),
+                }
+            }
+            update = updates.recv() => match update {
+                Ok(data) => connection.push(data).await,
+                Err(RecvError::Lagged(skipped)) => {
+                    // Older updates still queued would follow the catch-up
+                    // out of order; start over at the newest instead.
+                    updates = updates.resubscribe();
+                    connection.catch_up(skipped).await
+                }
+                Err(RecvError::Closed) => break "server shutting down".to_string(),
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > limits.heartbeat * 2 {
+                    break "no response to heartbeats".to_string();
+                }
+                connection.send_raw(Message::Ping(Vec::new())).await
+            }
+        };
+        if let Err(e) = result {
+            break e.to_string();
+        }
+    };
+
+    info!("WebSocket client disconnected: {}", reason);
+}
+
+impl Connection {
+    async fn handle(&mut self, text: &str) -> Result<(), anyhow::Error> {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => return self.send(ServerMessage::Error { error: format!("Invalid message: {}", e) }).await,
+        };
+
+        match message {
+            ClientMessage::Subscribe { symbols } => {
+                let feeds = self.resolve(&symbols).await?;
+                self.feeds.extend(feeds.iter().map(|feed| feed.id.clone()));
+                self.send(ServerMessage::Subscribed { symbols: self.symbols() }).await?;
+                self.snapshot(feeds).await
+            }
+            ClientMessage::Unsubscribe { symbols } => {
+                if symbols.is_empty() {
+                    self.feeds.clear();
+                } else {
+                    for feed in self.resolve(&symbols).await? {
+                        self.feeds.remove(&feed.id);
+                    }
+                }
+                self.sent.retain(|id, _| self.feeds.contains(id));
+                self.send(ServerMessage::Subscribed { symbols: self.symbols() }).await
+            }
+            ClientMessage::Ping => self.send(ServerMessage::Pong).await,
+        }
+    }
+
+    /// Registered feeds for `symbols`, or every feed when it is empty.
+    /// Unknown symbols are reported to the client and skipped.
+    async fn resolve(&mut self, symbols: &[String]) -> Result<Vec<Feed>, anyhow::Error> {
+        if symbols.is_empty() {
+            return Ok(self.state.registry.feeds().to_vec());
+        }
+        let mut feeds = Vec::new();
+        for symbol in symbols {
+            match self.state.registry.resolve(symbol) {
+                Some(feed) => feeds.push(feed.clone()),
+                None => self.send(ServerMessage::Error { error: format!("Unknown feed {}", symbol) }).await?,
+            }
+        }
+        Ok(feeds)
+    }
+
+    fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self
+            .state
+            .registry
+            .feeds()
+            .iter()
+            .filter(|feed| self.feeds.contains(&feed.id))
+            .map(|feed| feed.symbol.clone())
+            .collect();
+        symbols.sort_unstable();
+        symbols
+    }
+
+    /// Current prices of newly subscribed feeds, fetched like `/gdp/all`.
+    async fn snapshot(&mut self, feeds: Vec<Feed>) -> Result<(), anyhow::Error> {
+        if feeds.is_empty() {
+            return Ok(());
+        }
+        let results = self.state.get_feeds(feeds.iter().collect()).await;
+        for data in results.feeds {
+            self.sent.remove(&data.price_feed_id);
+            self.push(data).await?;
+        }
+        for failed in results.failed {
+            self.send(ServerMessage::Error { error: format!("Failed to fetch {}: {}", failed.symbol, failed.error) })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// After missing broadcasts, sends whatever changed from the cache.
+    async fn catch_up(&mut self, skipped: u64) -> Result<(), anyhow::Error> {
+        warn!("WebSocket client fell {} updates behind", skipped);
+        self.send(ServerMessage::Lagged { skipped }).await?;
+        let latest: Vec<PriceData> = self
+            .state
+            .registry
+            .feeds()
+            .iter()
+            .filter(|feed| self.feeds.contains(&feed.id))
+            .filter_map(|feed| self.state.cache.latest(feed))
+            .collect();
+        for data in latest {
+            self.push(data).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends `data` if its feed is subscribed and it differs from what the
+    /// client last received.
+    async fn push(&mut self, data: PriceData) -> Result<(), anyhow::Error> {
+        if !self.feeds.contains(&data.price_feed_id) {
+            return Ok(());
+        }
+        let key = (data.publish_time, data.exact.price, data.exact.expo);
+        if self.sent.get(&data.price_feed_id) == Some(&key) {
+            return Ok(());
+        }
+        self.sent.insert(data.price_feed_id.clone(), key);
+        self.send(ServerMessage::Price { data: Box::new(data) }).await
+    }
+
+    async fn send(&mut self, message: ServerMessage) -> Result<(), anyhow::Error> {
+        let text = serde_json::to_string(&message).map_err(|e| anyhow::anyhow!("Failed to encode message: {}", e))?;
+        self.send_raw(Message::Text(text)).await
+    }
+
+    /// Sends one frame, giving up on a client that does not accept it within
+    /// the send timeout.
+    async fn send_raw(&mut self, message: Message) -> Result<(), anyhow::Error> {
+        match tokio::time::timeout(self.send_timeout, self.sink.send(message)).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(anyhow::anyhow!("send failed: {}", e)),
+            Err(_) => Err(anyhow::anyhow!("send blocked for over {:?}", self.send_timeout)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cache::PriceCache,
+        testing::{self, Outcome, StubSource},
+        AppState, PriceData,
+    };
+    use futures::{SinkExt, StreamExt};
+    use serde_json::{json, Value};
+    use std::{sync::Arc, time::Duration};
+    use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+    async fn connect(state: AppState) -> Client {
+        let url = testing::serve(crate::router(state)).await;
+        let (client, _) = tokio_tungstenite::connect_async(format!("{}/ws", url.replace("http", "ws"))).await.unwrap();
+        client
+    }
+
+    async fn send(client: &mut Client, message: Value) {
+        client.send(Message::Text(message.to_string())).await.unwrap();
+    }
+
+    /// The next JSON message, or `None` when none comes within 200ms.
+    async fn recv(client: &mut Client) -> Option<Value> {
+        loop {
+            let message = tokio::time::timeout(Duration::from_millis(200), client.next()).await.ok()??.unwrap();
+            if let Message::Text(text) = message {
+                return Some(serde_json::from_str(&text).unwrap());
+            }
+        }
+    }
+
+    /// The type of the next message and, for prices, symbol and mantissa.
+    async fn next(client: &mut Client) -> Option<(String, Option<(String, i64)>)> {
+        let message = recv(client).await?;
+        let price = message.get("data").map(|data| {
+            let data: PriceData = serde_json::from_value(data.clone()).unwrap();
+            (data.symbol, data.exact.price)
+        });
+        Some((message["type"].as_str().unwrap().to_string(), price))
+    }
+
+    fn price(symbol: &str, mantissa: i64) -> Option<(String, Option<(String, i64)>)> {
+        Some(("price".to_string(), Some((symbol.to_string(), mantissa))))
+    }
+
+    #[tokio::test]
+    async fn pushes_changes_of_subscribed_feeds() {
+        let state = testing::app_state(Arc::new(StubSource::new("stub", Outcome::Price(280_000))));
+        let gdp = testing::feed("ECO.US.GDP");
+        let quarterly = testing::feed("ECO.US.GDPQ125");
+        let mut client = connect(state.clone()).await;
+
+        send(&mut client, json!({ "type": "subscribe", "symbols": ["ECO.US.GDPQ125", "ECO.US.CPI"] })).await;
+        assert_eq!(recv(&mut client).await.unwrap(), json!({ "type": "error", "error": "Unknown feed ECO.US.CPI" }));
+        assert_eq!(recv(&mut client).await.unwrap(), json!({ "type": "subscribed", "symbols": ["ECO.US.GDPQ125"] }));
+        // The snapshot's fetch is also broadcast, but sent once.
+        assert_eq!(next(&mut client).await, price("ECO.US.GDPQ125", 280_000));
+        assert_eq!(next(&mut client).await, None);
+
+        state.cache.insert(&gdp, testing::price(&gdp, 1, 1_000, -5, 1));
+        state.cache.insert(&quarterly, testing::price(&quarterly, 281_000, 1_000, -5, 1));
+        assert_eq!(next(&mut client).await, price("ECO.US.GDPQ125", 281_000));
+
+        send(&mut client, json!({ "type": "ping" })).await;
+        assert_eq!(recv(&mut client).await.unwrap(), json!({ "type": "pong" }));
+        client.send(Message::Binary(vec![1])).await.unwrap();
+        assert_eq!(recv(&mut client).await.unwrap()["error"], "Expected a JSON text message");
+
+        send(&mut client, json!({ "type": "unsubscribe" })).await;
+        assert_eq!(recv(&mut client).await.unwrap(), json!({ "type": "subscribed", "symbols": [] }));
+        state.cache.insert(&quarterly, testing::price(&quarterly, 282_000, 1_000, -5, 2));
+        assert_eq!(next(&mut client).await, None);
+    }
+
+    #[tokio::test]
+    async fn catches_up_after_lagging() {
+        let mut state = testing::app_state(Arc::new(StubSource::new("stub", Outcome::Price(280_000))));
+        state.cache = Arc::new(PriceCache::new(Duration::from_secs(60), Duration::ZERO, 2, 0));
+        let quarterly = testing::feed("ECO.US.GDPQ125");
+        let mut client = connect(state.clone()).await;
+
+        send(&mut client, json!({ "type": "subscribe", "symbols": ["ECO.US.GDPQ125"] })).await;
+        assert_eq!(next(&mut client).await.unwrap().0, "subscribed");
+        assert_eq!(next(&mut client).await, price("ECO.US.GDPQ125", 280_000));
+        assert_eq!(next(&mut client).await, None);
+
+        // Five changes before the connection reads any: it misses three.
+        for mantissa in 1..=5 {
+            state.cache.insert(&quarterly, testing::price(&quarterly, mantissa, 1_000, -5, mantissa));
+        }
+        assert_eq!(recv(&mut client).await.unwrap(), json!({ "type": "lagged", "skipped": 3 }));
+        assert_eq!(next(&mut client).await, price("ECO.US.GDPQ125", 5));
+        assert_eq!(next(&mut client).await, None);
+
+        state.cache.insert(&quarterly, testing::price(&quarterly, 6, 1_000, -5, 6));
+        assert_eq!(next(&mut client).await, price("ECO.US.GDPQ125", 6));
+    }
+}